@@ -41,6 +41,9 @@ class Chain(str, Enum):
     ethereum = "ethereum"
     starknet = "starknet"
     arbitrum = "arbitrum"
+    optimism = "optimism"
+    base = "base"
+    polygon = "polygon"
 
 
 class ChangeType(str, Enum):
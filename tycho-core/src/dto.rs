@@ -51,6 +51,9 @@ pub enum Chain {
     Starknet,
     ZkSync,
     Arbitrum,
+    Optimism,
+    Base,
+    Polygon,
 }
 
 impl From<models::contract::Account> for ResponseAccount {
@@ -77,6 +80,9 @@ impl From<models::Chain> for Chain {
             models::Chain::Starknet => Chain::Starknet,
             models::Chain::ZkSync => Chain::ZkSync,
             models::Chain::Arbitrum => Chain::Arbitrum,
+            models::Chain::Optimism => Chain::Optimism,
+            models::Chain::Base => Chain::Base,
+            models::Chain::Polygon => Chain::Polygon,
         }
     }
 }
@@ -96,6 +102,7 @@ impl From<models::ChangeType> for ChangeType {
             models::ChangeType::Update => ChangeType::Update,
             models::ChangeType::Creation => ChangeType::Creation,
             models::ChangeType::Deletion => ChangeType::Deletion,
+            models::ChangeType::Unspecified => ChangeType::Unspecified,
         }
     }
 }
@@ -228,6 +235,9 @@ pub struct BlockChanges {
     #[serde(alias = "state_deltas")]
     pub state_updates: HashMap<String, ProtocolStateDelta>,
     pub new_protocol_components: HashMap<String, ProtocolComponent>,
+    /// Static attribute updates of components created in earlier blocks.
+    #[serde(default)]
+    pub updated_protocol_components: HashMap<String, ProtocolComponent>,
     pub deleted_protocol_components: HashMap<String, ProtocolComponent>,
     pub component_balances: HashMap<String, TokenBalances>,
     pub component_tvl: HashMap<String, f64>,
@@ -257,6 +267,7 @@ impl BlockChanges {
             account_updates,
             state_updates,
             new_protocol_components,
+            updated_protocol_components: HashMap::new(),
             deleted_protocol_components,
             component_balances: component_balances
                 .into_iter()
@@ -305,6 +316,8 @@ impl BlockChanges {
             .extend(other.component_tvl);
         self.new_protocol_components
             .extend(other.new_protocol_components);
+        self.updated_protocol_components
+            .extend(other.updated_protocol_components);
         self.deleted_protocol_components
             .extend(other.deleted_protocol_components);
         self.revert = other.revert;
@@ -381,6 +394,13 @@ impl AccountUpdate {
         self.code.clone_from(&other.code);
         self.change = self.change.merge(&other.change);
     }
+
+    /// Returns the keys of all slots this update sets, in ascending order.
+    pub fn touched_slots(&self) -> impl Iterator<Item = &Bytes> {
+        let mut keys: Vec<_> = self.slots.keys().collect();
+        keys.sort_unstable();
+        keys.into_iter()
+    }
 }
 
 impl From<models::contract::AccountDelta> for AccountUpdate {
@@ -764,9 +784,10 @@ pub struct TokensRequestBody {
     ///  - 100: Normal token
     ///  - 75: Rebase token
     ///  - 50: Fee token
+    ///  - 30: Token without decimals, e.g. NFT-like or point tokens
     ///  - 10: Token analysis failed at creation
     ///  - 5: Token analysis failed on cronjob (after creation).
-    ///  - 0: Failed to extract decimals onchain
+    ///  - 0: Failed to extract decimals onchain or token is not transferable
     #[serde(default)]
     pub min_quality: Option<i32>,
     #[serde(default)]
@@ -1236,6 +1257,26 @@ mod test {
         assert_eq!(body1, body2);
     }
 
+    #[test]
+    fn test_account_update_touched_slots_sorted() {
+        let update = AccountUpdate::new(
+            Bytes::from("0x01"),
+            Chain::Ethereum,
+            hashmap! {
+                Bytes::from("0x03") => Bytes::from("0x01"),
+                Bytes::from("0x01") => Bytes::from("0x02"),
+                Bytes::from("0x02") => Bytes::from("0x03"),
+            },
+            None,
+            None,
+            ChangeType::Update,
+        );
+
+        let keys: Vec<_> = update.touched_slots().collect();
+
+        assert_eq!(keys, vec![&Bytes::from("0x01"), &Bytes::from("0x02"), &Bytes::from("0x03")]);
+    }
+
     #[test]
     fn test_protocol_components_inequality() {
         let body1 = ProtocolComponentsRequestBody {
@@ -1525,6 +1566,7 @@ mod test {
                     id: "pc_2".to_string(),
                     protocol_system: "native_protocol_system".to_string(),
                     protocol_type_name: "pt_1".to_string(),
+                    additional_protocol_types: Vec::new(),
                     chain: models::Chain::Ethereum,
                     tokens: vec![
                         Bytes::from_str("0xdac17f958d2ee523a2206206994597c13d831ec7").unwrap(),
@@ -1537,11 +1579,13 @@ mod test {
                     created_at: NaiveDateTime::from_timestamp_opt(base_ts + 5000, 0).unwrap(),
                 }),
             ]),
+            updated_protocol_components: HashMap::new(),
             deleted_protocol_components: HashMap::from([
                 ("pc_3".to_string(), crate::models::protocol::ProtocolComponent {
                     id: "pc_3".to_string(),
                     protocol_system: "native_protocol_system".to_string(),
                     protocol_type_name: "pt_2".to_string(),
+                    additional_protocol_types: Vec::new(),
                     chain: models::Chain::Ethereum,
                     tokens: vec![
                         Bytes::from_str("0x6b175474e89094c44da98b954eedeac495271d0f").unwrap(),
@@ -1573,6 +1617,7 @@ mod test {
                 ])),
             ]),
             component_tvl: HashMap::new(),
+            seq: 0,
             account_deltas: Default::default(),
         }
     }
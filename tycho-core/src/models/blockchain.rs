@@ -1,18 +1,24 @@
 use crate::{
     models::{
-        contract::AccountDelta,
-        protocol::{ComponentBalance, ProtocolComponent, ProtocolComponentStateDelta},
-        Chain, ComponentId,
+        contract::{Account, AccountDelta, AccountState, CodeChange},
+        protocol::{
+            decode_u256_be, AttributeDecodeError, ComponentBalance, ProtocolComponent,
+            ProtocolComponentState, ProtocolComponentStateDelta,
+        },
+        AttrStoreKey, Balance, Chain, ChangeType, ComponentId, DeltaError, StoreKey,
     },
     Bytes,
 };
 use chrono::NaiveDateTime;
+use primitive_types::U256;
 use serde::{Deserialize, Serialize};
 use std::{
     any::Any,
-    collections::{hash_map::Entry, HashMap},
-    sync::Arc,
+    borrow::Cow,
+    collections::{hash_map::Entry, HashMap, HashSet},
+    sync::{Arc, OnceLock},
 };
+use thiserror::Error;
 use tracing::warn;
 
 use super::{
@@ -27,6 +33,9 @@ pub struct Block {
     pub hash: Bytes,
     pub parent_hash: Bytes,
     pub ts: NaiveDateTime,
+    /// EIP-1559 base fee per gas, `None` for chains without one or if not provided by the source.
+    #[serde(default)]
+    pub base_fee_per_gas: Option<U256>,
 }
 
 impl Block {
@@ -37,8 +46,45 @@ impl Block {
         parent_hash: Bytes,
         ts: NaiveDateTime,
     ) -> Self {
-        Block { hash, parent_hash, number, chain, ts }
+        Block { hash, parent_hash, number, chain, ts, base_fee_per_gas: None }
     }
+
+    /// Sets the base fee per gas of the block.
+    pub fn with_base_fee_per_gas(mut self, base_fee_per_gas: Option<U256>) -> Self {
+        self.base_fee_per_gas = base_fee_per_gas;
+        self
+    }
+}
+
+/// Distinguishes contract creations from other transactions without a receiver.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum TransactionType {
+    #[default]
+    Call,
+    Creation,
+    /// Internal or system transaction, these may have no receiver without creating a contract.
+    System,
+}
+
+impl TransactionType {
+    /// Infers the type from the receiver: transactions without one are creations.
+    pub fn infer(to: Option<&Bytes>) -> Self {
+        match to {
+            Some(_) => Self::Call,
+            None => Self::Creation,
+        }
+    }
+}
+
+/// Whether a transaction succeeded.
+///
+/// State changes of reverted transactions are not applied, but the transaction is still
+/// recorded.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum TxStatus {
+    #[default]
+    Success,
+    Reverted,
 }
 
 #[derive(Clone, Default, PartialEq, Debug)]
@@ -48,11 +94,57 @@ pub struct Transaction {
     pub from: Bytes,
     pub to: Option<Bytes>,
     pub index: u64,
+    /// Gas used by the transaction, if provided by the source.
+    pub gas_used: Option<u64>,
+    pub tx_type: TransactionType,
+    pub status: TxStatus,
+    /// Storage slots the transaction declared it accesses (EIP-2930), if provided by the source.
+    pub access_list: Option<Vec<(Address, Vec<StoreKey>)>>,
 }
 
 impl Transaction {
     pub fn new(hash: Bytes, block_hash: Bytes, from: Bytes, to: Option<Bytes>, index: u64) -> Self {
-        Transaction { hash, block_hash, from, to, index }
+        let tx_type = TransactionType::infer(to.as_ref());
+        Transaction {
+            hash,
+            block_hash,
+            from,
+            to,
+            index,
+            gas_used: None,
+            tx_type,
+            status: TxStatus::default(),
+            access_list: None,
+        }
+    }
+
+    /// Sets the type of the transaction, overriding the one inferred from its receiver.
+    pub fn with_tx_type(mut self, tx_type: TransactionType) -> Self {
+        self.tx_type = tx_type;
+        self
+    }
+
+    /// Sets whether the transaction succeeded.
+    pub fn with_status(mut self, status: TxStatus) -> Self {
+        self.status = status;
+        self
+    }
+
+    /// Returns whether the transaction creates a contract.
+    pub fn is_creation(&self) -> bool {
+        self.tx_type == TransactionType::Creation
+    }
+
+    /// Sets the storage slots the transaction declared it accesses.
+    pub fn with_access_list(mut self, access_list: Option<Vec<(Address, Vec<StoreKey>)>>) -> Self {
+        self.access_list = access_list;
+        self
+    }
+
+    /// Sets the gas used by the transaction.
+    pub fn with_gas_used(mut self, gas_used: Option<u64>) -> Self {
+        self.gas_used = gas_used;
+        self
     }
 }
 
@@ -84,9 +176,17 @@ pub struct BlockAggregatedChanges {
     pub account_deltas: HashMap<Bytes, AccountDelta>,
     pub new_tokens: HashMap<Address, CurrencyToken>,
     pub new_protocol_components: HashMap<String, ProtocolComponent>,
+    /// Static attribute updates of components that were not created in this block. Each
+    /// component only holds the changed static attributes.
+    #[serde(default)]
+    pub updated_protocol_components: HashMap<String, ProtocolComponent>,
     pub deleted_protocol_components: HashMap<String, ProtocolComponent>,
     pub component_balances: HashMap<ComponentId, HashMap<Bytes, ComponentBalance>>,
     pub component_tvl: HashMap<String, f64>,
+    /// Sequence number assigned by the extractor. It strictly increases with every emitted
+    /// message, including reverts, while the block number may regress.
+    #[serde(default)]
+    pub seq: u64,
 }
 
 impl BlockAggregatedChanges {
@@ -114,12 +214,774 @@ impl BlockAggregatedChanges {
             state_deltas,
             account_deltas,
             new_protocol_components: new_components,
+            updated_protocol_components: HashMap::new(),
             deleted_protocol_components: deleted_components,
             component_balances,
             component_tvl,
             new_tokens,
+            seq: 0,
+        }
+    }
+
+    /// Flattens the state deltas into one row per changed attribute.
+    ///
+    /// Rows are ordered by component id and attribute name. Aggregated changes do not keep track
+    /// of the transaction that modified each attribute, so rows are identified by block number.
+    pub fn to_attribute_rows(&self) -> Vec<AttributeRow> {
+        let mut rows: Vec<AttributeRow> = self
+            .state_deltas
+            .iter()
+            .flat_map(|(component_id, delta)| {
+                let updated = delta
+                    .updated_attributes
+                    .iter()
+                    .map(|(name, value)| (name, Some(value.to_string()), ChangeType::Update));
+                let deleted = delta
+                    .deleted_attributes
+                    .iter()
+                    .map(|name| (name, None, ChangeType::Deletion));
+                updated
+                    .chain(deleted)
+                    .map(move |(name, value_hex, change)| AttributeRow {
+                        block_number: self.block.number,
+                        component_id: component_id.clone(),
+                        attribute_name: name.clone(),
+                        value_hex,
+                        change,
+                    })
+            })
+            .collect();
+        rows.sort_unstable_by(|a, b| {
+            (&a.component_id, &a.attribute_name).cmp(&(&b.component_id, &b.attribute_name))
+        });
+        rows
+    }
+
+    /// Returns the updated value of the attribute `attr` of component `component_id`.
+    ///
+    /// Returns `None` if the component has no state delta in this block, or if the delta does not
+    /// update the attribute.
+    pub fn attribute(&self, component_id: &str, attr: &str) -> Option<Bytes> {
+        self.state_deltas
+            .get(component_id)?
+            .updated_attributes
+            .get(attr)
+            .cloned()
+    }
+
+    /// Returns the updated value of an attribute decoded as a big-endian `U256`.
+    ///
+    /// See [`ProtocolComponentState::get_u256_be`] for the decoding rules.
+    pub fn attribute_u256(
+        &self,
+        component_id: &str,
+        attr: &str,
+    ) -> Result<Option<U256>, AttributeDecodeError> {
+        self.attribute(component_id, attr)
+            .map(|value| decode_u256_be(attr, &value))
+            .transpose()
+    }
+
+    /// Returns the component balances that exceed the total supply of their token.
+    ///
+    /// Only tokens with a known supply in `total_supplies` are checked. Balances may exceed the
+    /// supply by the relative `tolerance` before they are flagged. The result is sorted by
+    /// component id and token.
+    pub fn balances_exceeding_supply(
+        &self,
+        total_supplies: &HashMap<Address, U256>,
+        tolerance: f64,
+    ) -> Vec<&ComponentBalance> {
+        let mut flagged: Vec<_> = self
+            .component_balances
+            .values()
+            .flat_map(|balances| balances.values())
+            .filter(|balance| {
+                total_supplies
+                    .get(&balance.token)
+                    .is_some_and(|supply| balance.exceeds_supply(*supply, tolerance))
+            })
+            .collect();
+        flagged
+            .sort_unstable_by(|a, b| (&a.component_id, &a.token).cmp(&(&b.component_id, &b.token)));
+        flagged
+    }
+
+    /// Partitions the changes of this block by the protocol system owning them.
+    ///
+    /// Components are associated with their `protocol_system`, accounts with the system of the
+    /// component listing them in its `contract_addresses`. Components created or deleted in this
+    /// block are known from the block itself, all other components must be passed in as
+    /// `known_components`. Changes that can't be associated with any system are not part of the
+    /// result. New tokens are routed to every system with a new component referencing them.
+    /// Block metadata is copied to each partition.
+    pub fn split_by_system(
+        &self,
+        known_components: &HashMap<ComponentId, ProtocolComponent>,
+    ) -> HashMap<String, BlockAggregatedChanges> {
+        let mut component_systems = HashMap::new();
+        let mut account_systems = HashMap::new();
+        for (component_id, component) in known_components
+            .iter()
+            .chain(self.new_protocol_components.iter())
+            .chain(self.deleted_protocol_components.iter())
+        {
+            let system = component.protocol_system.as_str();
+            component_systems.insert(component_id.as_str(), system);
+            for address in component.contract_addresses.iter() {
+                account_systems.insert(address, system);
+            }
+        }
+
+        let mut splits: HashMap<String, BlockAggregatedChanges> = HashMap::new();
+
+        for (address, delta) in self.account_deltas.iter() {
+            if let Some(system) = account_systems.get(address) {
+                self.split_entry(&mut splits, system)
+                    .account_deltas
+                    .insert(address.clone(), delta.clone());
+            }
+        }
+        for (component_id, component) in self.new_protocol_components.iter() {
+            let split = self.split_entry(&mut splits, &component.protocol_system);
+            for token in component.tokens.iter() {
+                if let Some(new_token) = self.new_tokens.get(token) {
+                    split
+                        .new_tokens
+                        .insert(token.clone(), new_token.clone());
+                }
+            }
+            split
+                .new_protocol_components
+                .insert(component_id.clone(), component.clone());
+        }
+        for (component_id, component) in self.updated_protocol_components.iter() {
+            self.split_entry(&mut splits, &component.protocol_system)
+                .updated_protocol_components
+                .insert(component_id.clone(), component.clone());
+        }
+        for (component_id, component) in self.deleted_protocol_components.iter() {
+            self.split_entry(&mut splits, &component.protocol_system)
+                .deleted_protocol_components
+                .insert(component_id.clone(), component.clone());
+        }
+        for (component_id, delta) in self.state_deltas.iter() {
+            if let Some(system) = component_systems.get(component_id.as_str()) {
+                self.split_entry(&mut splits, system)
+                    .state_deltas
+                    .insert(component_id.clone(), delta.clone());
+            }
+        }
+        for (component_id, balances) in self.component_balances.iter() {
+            if let Some(system) = component_systems.get(component_id.as_str()) {
+                self.split_entry(&mut splits, system)
+                    .component_balances
+                    .insert(component_id.clone(), balances.clone());
+            }
+        }
+        for (component_id, tvl) in self.component_tvl.iter() {
+            if let Some(system) = component_systems.get(component_id.as_str()) {
+                self.split_entry(&mut splits, system)
+                    .component_tvl
+                    .insert(component_id.clone(), *tvl);
+            }
+        }
+        splits
+    }
+
+    /// Removes account updates that would apply nothing, see [`AccountDelta::is_empty_update`].
+    ///
+    /// Merging and pruning may leave updates without any slots, balance or code behind. Account
+    /// creations and deletions are always kept.
+    pub fn drop_empty_updates(&mut self) {
+        self.account_deltas
+            .retain(|_, delta| !delta.is_empty_update());
+    }
+
+    /// Returns the partition of `system`, creating it with this block's metadata if missing.
+    fn split_entry<'a>(
+        &self,
+        splits: &'a mut HashMap<String, BlockAggregatedChanges>,
+        system: &str,
+    ) -> &'a mut BlockAggregatedChanges {
+        splits
+            .entry(system.to_string())
+            .or_insert_with(|| BlockAggregatedChanges {
+                extractor: self.extractor.clone(),
+                chain: self.chain,
+                block: self.block.clone(),
+                finalized_block_height: self.finalized_block_height,
+                revert: self.revert,
+                seq: self.seq,
+                ..Default::default()
+            })
+    }
+
+    /// Approximates the size of this message serialized as JSON without serializing it.
+    ///
+    /// Byte values are counted at their exact hex encoded width, while field names, punctuation
+    /// and fixed size fields are approximated by constant overheads per entry. For messages
+    /// dominated by storage slots, code, attributes or components, the estimate is within 10% of
+    /// the actual size. Small messages are dominated by the constant overheads and may deviate
+    /// by a few hundred bytes.
+    pub fn estimated_size(&self) -> usize {
+        let accounts: usize = self
+            .account_deltas
+            .iter()
+            .map(|(address, delta)| {
+                let slots: usize = delta
+                    .slots
+                    .iter()
+                    .map(|(key, value)| hex_len(key) + opt_hex_len(value.as_ref()) + 2)
+                    .sum();
+                ENTRY_OVERHEAD +
+                    hex_len(address) +
+                    hex_len(&delta.address) +
+                    slots +
+                    opt_hex_len(delta.balance.as_ref()) +
+                    opt_hex_len(delta.code.as_ref())
+            })
+            .sum();
+        let states: usize = self
+            .state_deltas
+            .iter()
+            .map(|(component_id, delta)| {
+                let updated: usize = delta
+                    .updated_attributes
+                    .iter()
+                    .map(|(name, value)| name.len() + hex_len(value) + 4)
+                    .sum();
+                let deleted: usize = delta
+                    .deleted_attributes
+                    .iter()
+                    .map(|name| name.len() + 3)
+                    .sum();
+                ENTRY_OVERHEAD + component_id.len() + delta.component_id.len() + updated + deleted
+            })
+            .sum();
+        let components: usize = self
+            .new_protocol_components
+            .iter()
+            .chain(self.updated_protocol_components.iter())
+            .chain(self.deleted_protocol_components.iter())
+            .map(|(component_id, component)| {
+                let addresses: usize = component
+                    .tokens
+                    .iter()
+                    .chain(component.contract_addresses.iter())
+                    .map(|address| hex_len(address) + 1)
+                    .sum();
+                let attributes: usize = component
+                    .static_attributes
+                    .iter()
+                    .map(|(name, value)| name.len() + hex_len(value) + 4)
+                    .sum();
+                let types: usize = component
+                    .protocol_type_names()
+                    .map(|name| name.len() + 3)
+                    .sum();
+                3 * ENTRY_OVERHEAD +
+                    component_id.len() +
+                    component.id.len() +
+                    component.protocol_system.len() +
+                    types +
+                    addresses +
+                    attributes +
+                    hex_len(&component.creation_tx)
+            })
+            .sum();
+        let tokens: usize = self
+            .new_tokens
+            .iter()
+            .map(|(address, token)| {
+                2 * ENTRY_OVERHEAD + hex_len(address) + hex_len(&token.address) + token.symbol.len()
+            })
+            .sum();
+        let balances: usize = self
+            .component_balances
+            .iter()
+            .map(|(component_id, balances)| {
+                component_id.len() +
+                    balances
+                        .iter()
+                        .map(|(token, balance)| {
+                            ENTRY_OVERHEAD +
+                                hex_len(token) +
+                                hex_len(&balance.token) +
+                                hex_len(&balance.balance) +
+                                hex_len(&balance.modify_tx) +
+                                balance.component_id.len()
+                        })
+                        .sum::<usize>()
+            })
+            .sum();
+        let tvl: usize = self
+            .component_tvl
+            .keys()
+            .map(|component_id| component_id.len() + 25)
+            .sum();
+
+        BASE_MESSAGE_SIZE +
+            self.extractor.len() +
+            hex_len(&self.block.hash) +
+            hex_len(&self.block.parent_hash) +
+            accounts +
+            states +
+            components +
+            tokens +
+            balances +
+            tvl
+    }
+
+    /// Applies the account, protocol state and balance changes of this block to `store`.
+    ///
+    /// Account deltas are applied as described by [`AccountState::apply_delta`], creations of
+    /// accounts already present in the store are handled according to its
+    /// [`CreationReplayPolicy`]. States that are not present in the store yet are created from
+    /// their deltas, deleted components are removed. This does not check the block order, see
+    /// [`apply_blocks`] for that.
+    ///
+    /// # Errors
+    /// Returns `DeltaError::UnknownAccount` if an account is updated before it was created.
+    pub fn apply_to(&self, store: &mut StateStore) -> Result<(), DeltaError> {
+        let tx = Transaction { block_hash: self.block.hash.clone(), ..Default::default() };
+        for (address, delta) in self.account_deltas.iter() {
+            let delta = store.resolve_proxy_impl(delta)?;
+            let mut state = AccountState::from(store.accounts.remove(address));
+            let res = match (&state, store.creation_replay_policy) {
+                (AccountState::Present(_), CreationReplayPolicy::Skip) if delta.is_creation() => {
+                    warn!(%address, "Skipping creation of existing account");
+                    Ok(())
+                }
+                (AccountState::Present(_), CreationReplayPolicy::Overwrite)
+                    if delta.is_creation() =>
+                {
+                    state = AccountState::Unknown;
+                    state.apply_delta(&delta, &tx)
+                }
+                _ => state.apply_delta(&delta, &tx),
+            };
+            if let AccountState::Present(account) = state {
+                store
+                    .accounts
+                    .insert(address.clone(), account);
+            }
+            res?;
+        }
+
+        for component_id in self.deleted_protocol_components.keys() {
+            store
+                .protocol_states
+                .remove(component_id);
+        }
+        for (component_id, delta) in self.state_deltas.iter() {
+            store
+                .protocol_states
+                .entry(component_id.clone())
+                .or_insert_with(|| {
+                    ProtocolComponentState::new(component_id, HashMap::new(), HashMap::new())
+                })
+                .apply_state_delta(delta)?;
+        }
+        for (component_id, balances) in self.component_balances.iter() {
+            store
+                .protocol_states
+                .entry(component_id.clone())
+                .or_insert_with(|| {
+                    ProtocolComponentState::new(component_id, HashMap::new(), HashMap::new())
+                })
+                .apply_balance_delta(balances)?;
+        }
+        Ok(())
+    }
+}
+
+/// A single attribute change of a protocol component, flattened for tabular consumers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttributeRow {
+    pub block_number: u64,
+    pub component_id: ComponentId,
+    pub attribute_name: AttrStoreKey,
+    /// Hex encoded value, `None` for deleted attributes.
+    pub value_hex: Option<String>,
+    /// `ChangeType::Update` for updated and `ChangeType::Deletion` for deleted attributes.
+    pub change: ChangeType,
+}
+
+/// Approximate serialized size of a message without any changes, mostly field names and block
+/// metadata.
+const BASE_MESSAGE_SIZE: usize = 340;
+/// Approximate serialized size of the field names, punctuation and fixed size fields of a single
+/// entry, e.g. an account delta or a component balance.
+const ENTRY_OVERHEAD: usize = 80;
+
+/// Serialized size of a byte value: hex encoded, `0x` prefixed and quoted.
+fn hex_len(value: &Bytes) -> usize {
+    value.len() * 2 + 4
+}
+
+/// Serialized size of an optional byte value, `null` if absent.
+fn opt_hex_len(value: Option<&Bytes>) -> usize {
+    value.map_or(4, hex_len)
+}
+
+/// How to handle the creation of an account that already exists in a [`StateStore`].
+///
+/// Such a creation usually means a block is replayed, in which case the stored account may
+/// already contain newer state than the creation.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CreationReplayPolicy {
+    /// Fail with `DeltaError::DuplicateCreation`.
+    #[default]
+    Error,
+    /// Keep the existing account and ignore the creation.
+    Skip,
+    /// Replace the existing account with the created one.
+    Overwrite,
+}
+
+/// Contract and protocol component states kept up to date by applying block changes.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct StateStore {
+    pub accounts: HashMap<Address, Account>,
+    pub protocol_states: HashMap<ComponentId, ProtocolComponentState>,
+    continuity: BlockContinuityChecker,
+    creation_replay_policy: CreationReplayPolicy,
+}
+
+impl StateStore {
+    /// Sets how creations of already existing accounts are handled, failing by default.
+    pub fn with_creation_replay_policy(mut self, policy: CreationReplayPolicy) -> Self {
+        self.creation_replay_policy = policy;
+        self
+    }
+
+    /// Replaces a proxy implementation swap in `delta` with the full code of the implementation.
+    ///
+    /// # Errors
+    /// Returns `DeltaError::UnknownProxyImpl` if the implementation is not in the store.
+    fn resolve_proxy_impl<'a>(
+        &self,
+        delta: &'a AccountDelta,
+    ) -> Result<Cow<'a, AccountDelta>, DeltaError> {
+        match delta.code_change() {
+            CodeChange::ProxyImpl(implementation) => {
+                let code = self
+                    .accounts
+                    .get(&implementation)
+                    .ok_or_else(|| DeltaError::UnknownProxyImpl(implementation.to_string()))?
+                    .code
+                    .clone();
+                let mut resolved = delta.clone();
+                resolved.set_code_change(CodeChange::Full(code));
+                Ok(Cow::Owned(resolved))
+            }
+            _ => Ok(Cow::Borrowed(delta)),
+        }
+    }
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum ApplyError {
+    #[error("Block {received} does not directly follow block {last}")]
+    BlockGap { last: u64, received: u64 },
+    #[error("Block {received} regresses from block {last} without a revert")]
+    BlockRegression { last: u64, received: u64 },
+    #[error("Block {received} is not the last applied block {last:?}")]
+    NotLastBlock { last: Option<u64>, received: u64 },
+    #[error("Failed to apply delta: {0}")]
+    Delta(#[from] DeltaError),
+}
+
+/// Ensures blocks are received in contiguous order.
+///
+/// Every block must directly follow the previously checked block, unless it is a revert, in
+/// which case it may go back to any earlier (or the same) block.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct BlockContinuityChecker {
+    last_block: Option<u64>,
+}
+
+impl BlockContinuityChecker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Checks that `changes` may follow the previously checked block and records it.
+    pub fn check(&mut self, changes: &BlockAggregatedChanges) -> Result<(), ApplyError> {
+        let received = changes.block.number;
+        if let Some(last) = self.last_block {
+            if changes.revert {
+                if received > last {
+                    return Err(ApplyError::BlockGap { last, received });
+                }
+            } else if received <= last {
+                return Err(ApplyError::BlockRegression { last, received });
+            } else if received != last + 1 {
+                return Err(ApplyError::BlockGap { last, received });
+            }
+        }
+        self.last_block = Some(received);
+        Ok(())
+    }
+}
+
+/// Applies a sequence of block changes to `store`, enforcing block continuity.
+///
+/// Continuity of all blocks (relative to the blocks previously applied to the store) is checked
+/// before any of them is applied, so a gap or regression leaves the store untouched. A failure
+/// to apply a delta may leave the store partially updated.
+pub fn apply_blocks(
+    store: &mut StateStore,
+    blocks: &[BlockAggregatedChanges],
+) -> Result<(), ApplyError> {
+    let mut continuity = store.continuity.clone();
+    for block in blocks {
+        continuity.check(block)?;
+    }
+    for block in blocks {
+        block.apply_to(store)?;
+    }
+    store.continuity = continuity;
+    Ok(())
+}
+
+/// Running token balances of all components, maintained from a stream of block changes.
+///
+/// Balances are kept at full precision. The previous values of the balances changed by each
+/// applied block are retained, so applied blocks can be reverted in reverse order.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct TvlState {
+    balances: HashMap<(ComponentId, Address), Balance>,
+    history: Vec<(u64, Vec<((ComponentId, Address), Option<Balance>)>)>,
+}
+
+impl TvlState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the balance of `token` in `component_id`, `None` if it was never set.
+    pub fn balance(&self, component_id: &str, token: &Address) -> Option<&Balance> {
+        self.balances
+            .get(&(component_id.to_string(), token.clone()))
+    }
+
+    /// Sets the balances changed by `changes`, remembering their previous values.
+    pub fn apply_block(&mut self, changes: &BlockAggregatedChanges) {
+        let mut previous = Vec::new();
+        for (component_id, balances) in changes.component_balances.iter() {
+            for (token, balance) in balances.iter() {
+                let key = (component_id.clone(), token.clone());
+                let old = self
+                    .balances
+                    .insert(key.clone(), balance.balance.clone());
+                previous.push((key, old));
+            }
+        }
+        self.history
+            .push((changes.block.number, previous));
+    }
+
+    /// Restores the balances from before the last applied block, which must be `changes`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ApplyError::NotLastBlock` if `changes` is not the last applied block, leaving the
+    /// state untouched.
+    pub fn revert_block(&mut self, changes: &BlockAggregatedChanges) -> Result<(), ApplyError> {
+        let received = changes.block.number;
+        let last = self
+            .history
+            .last()
+            .map(|(number, _)| *number);
+        if last != Some(received) {
+            return Err(ApplyError::NotLastBlock { last, received });
+        }
+        let (_, previous) = self
+            .history
+            .pop()
+            .expect("history checked to be non-empty");
+        for (key, old) in previous.into_iter().rev() {
+            match old {
+                Some(balance) => {
+                    self.balances.insert(key, balance);
+                }
+                None => {
+                    self.balances.remove(&key);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Index of the components trading each token, maintained from a stream of block changes.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ComponentIndex {
+    by_token: HashMap<Address, HashSet<ComponentId>>,
+}
+
+impl ComponentIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the components created in `changes` and removes the ones it deletes.
+    pub fn add_block(&mut self, changes: &BlockAggregatedChanges) {
+        for (id, component) in changes.new_protocol_components.iter() {
+            for token in component.tokens.iter() {
+                self.by_token
+                    .entry(token.clone())
+                    .or_default()
+                    .insert(id.clone());
+            }
+        }
+        for (id, component) in changes
+            .deleted_protocol_components
+            .iter()
+        {
+            for token in component.tokens.iter() {
+                if let Entry::Occupied(mut entry) = self.by_token.entry(token.clone()) {
+                    entry.get_mut().remove(id);
+                    if entry.get().is_empty() {
+                        entry.remove();
+                    }
+                }
+            }
         }
     }
+
+    /// Removes `component_id` from the index, doing nothing if it isn't indexed.
+    pub fn on_component_deleted(&mut self, component_id: &str) {
+        self.by_token.retain(|_, ids| {
+            ids.remove(component_id);
+            !ids.is_empty()
+        });
+    }
+
+    /// Returns the ids of all known components trading `token`.
+    pub fn components_for_token(&self, token: &Address) -> &HashSet<ComponentId> {
+        static EMPTY: OnceLock<HashSet<ComponentId>> = OnceLock::new();
+        self.by_token
+            .get(token)
+            .unwrap_or_else(|| EMPTY.get_or_init(HashSet::new))
+    }
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum CoalesceError {
+    #[error("Block {0} carries changes other than state deltas")]
+    UnsupportedChanges(u64),
+    #[error("Failed to merge state deltas: {0}")]
+    Merge(String),
+}
+
+/// Coalesces the state deltas of consecutive blocks into windowed snapshots.
+///
+/// Once `window_size` blocks were pushed, their state deltas are merged into a single message
+/// carrying the header of the last block. Only state deltas can be coalesced, blocks carrying
+/// other changes are rejected. A revert flushes the window: the blocks collected so far are
+/// emitted before the revert, which then undoes whatever part of them it covers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StateWindowCoalescer {
+    window_size: usize,
+    window: Option<BlockAggregatedChanges>,
+    collected: usize,
+}
+
+impl StateWindowCoalescer {
+    /// Creates a coalescer emitting one message every `window_size` blocks, at least one.
+    pub fn new(window_size: usize) -> Self {
+        Self { window_size: window_size.max(1), window: None, collected: 0 }
+    }
+
+    /// Adds the next block and returns the messages ready to be emitted.
+    ///
+    /// That's the coalesced window once it is full, or on a revert the partially collected window
+    /// followed by the revert itself.
+    ///
+    /// # Errors
+    /// Returns an error if a block carries changes other than state deltas, or if a state delta is
+    /// keyed by another component than its own. The window is left untouched in both cases.
+    pub fn push(
+        &mut self,
+        changes: BlockAggregatedChanges,
+    ) -> Result<Vec<BlockAggregatedChanges>, CoalesceError> {
+        if changes.revert {
+            let mut messages: Vec<_> = self.flush().into_iter().collect();
+            messages.push(changes);
+            return Ok(messages);
+        }
+        if !(changes.account_deltas.is_empty() &&
+            changes.new_tokens.is_empty() &&
+            changes
+                .new_protocol_components
+                .is_empty() &&
+            changes
+                .updated_protocol_components
+                .is_empty() &&
+            changes
+                .deleted_protocol_components
+                .is_empty() &&
+            changes.component_balances.is_empty() &&
+            changes.component_tvl.is_empty())
+        {
+            return Err(CoalesceError::UnsupportedChanges(changes.block.number));
+        }
+        if let Some((id, delta)) = changes
+            .state_deltas
+            .iter()
+            .find(|(id, delta)| **id != delta.component_id)
+        {
+            return Err(CoalesceError::Merge(format!(
+                "State delta of {} is keyed by {}",
+                delta.component_id, id
+            )));
+        }
+        let window = match self.window.take() {
+            None => BlockAggregatedChanges {
+                extractor: changes.extractor,
+                chain: changes.chain,
+                block: changes.block,
+                finalized_block_height: changes.finalized_block_height,
+                revert: false,
+                state_deltas: changes.state_deltas,
+                seq: changes.seq,
+                ..Default::default()
+            },
+            Some(mut window) => {
+                for (id, delta) in changes.state_deltas {
+                    match window.state_deltas.entry(id) {
+                        Entry::Occupied(mut entry) => entry
+                            .get_mut()
+                            .merge(delta)
+                            .map_err(CoalesceError::Merge)?,
+                        Entry::Vacant(entry) => {
+                            entry.insert(delta);
+                        }
+                    }
+                }
+                window.block = changes.block;
+                window.finalized_block_height = changes.finalized_block_height;
+                window.seq = changes.seq;
+                window
+            }
+        };
+        self.collected += 1;
+        if self.collected < self.window_size {
+            self.window = Some(window);
+            return Ok(Vec::new());
+        }
+        self.collected = 0;
+        Ok(vec![window])
+    }
+
+    /// Returns the partially collected window, if any, and starts a new one.
+    pub fn flush(&mut self) -> Option<BlockAggregatedChanges> {
+        self.collected = 0;
+        self.window.take()
+    }
 }
 
 impl std::fmt::Display for BlockAggregatedChanges {
@@ -145,12 +1007,18 @@ impl NormalisedMessage for BlockAggregatedChanges {
             state_deltas: HashMap::new(),
             new_tokens: self.new_tokens.clone(),
             new_protocol_components: self.new_protocol_components.clone(),
+            updated_protocol_components: self.updated_protocol_components.clone(),
             deleted_protocol_components: self.deleted_protocol_components.clone(),
             component_balances: self.component_balances.clone(),
             component_tvl: self.component_tvl.clone(),
+            seq: self.seq,
         })
     }
 
+    fn sequence(&self) -> u64 {
+        self.seq
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
@@ -193,6 +1061,38 @@ impl TxWithChanges {
         }
     }
 
+    /// Returns the slot changes not declared in the transaction's access list, sorted.
+    ///
+    /// Useful to check that the changes of a transaction are complete, as they should only touch
+    /// declared slots. Empty if the transaction has no access list.
+    pub fn undeclared_slot_changes(&self) -> Vec<(Address, StoreKey)> {
+        let Some(access_list) = &self.tx.access_list else {
+            return Vec::new();
+        };
+        let declared: HashSet<_> = access_list
+            .iter()
+            .flat_map(|(address, slots)| {
+                slots
+                    .iter()
+                    .map(move |slot| (address, slot))
+            })
+            .collect();
+        let mut undeclared: Vec<_> = self
+            .account_deltas
+            .values()
+            .flat_map(|delta| {
+                delta
+                    .slots
+                    .keys()
+                    .map(move |slot| (&delta.address, slot))
+            })
+            .filter(|change| !declared.contains(change))
+            .map(|(address, slot)| (address.clone(), slot.clone()))
+            .collect();
+        undeclared.sort_unstable();
+        undeclared
+    }
+
     /// Merges this update with another one.
     ///
     /// The method combines two `ChangesWithTx` instances if they are for the same
@@ -226,10 +1126,16 @@ impl TxWithChanges {
         self.tx = other.tx;
 
         // Merge new protocol components
-        // Log a warning if a new protocol component for the same id already exists, because this
-        // should never happen.
+        // Update-type components only change static attributes of the existing entry, updates
+        // without an existing entry are kept as updates. Log a warning if a new protocol
+        // component for the same id already exists, because this should never happen.
         for (key, value) in other.protocol_components {
             match self.protocol_components.entry(key) {
+                Entry::Occupied(mut entry) if value.change == ChangeType::Update => {
+                    entry
+                        .get_mut()
+                        .apply_static_attribute_update(value);
+                }
                 Entry::Occupied(mut entry) => {
                     warn!(
                         "Overwriting new protocol component for id {} with a new one. This should never happen! Please check logic",
@@ -345,3 +1251,758 @@ pub mod fixtures {
         )
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+
+    use rstest::rstest;
+
+    use super::*;
+
+    #[test]
+    fn test_to_attribute_rows() {
+        let changes = BlockAggregatedChanges {
+            block: Block { number: 1, ..Default::default() },
+            state_deltas: HashMap::from([
+                (
+                    "pc_2".to_string(),
+                    ProtocolComponentStateDelta::new(
+                        "pc_2",
+                        HashMap::from([("reserve".to_string(), Bytes::from("0x01"))]),
+                        HashSet::new(),
+                    ),
+                ),
+                (
+                    "pc_1".to_string(),
+                    ProtocolComponentStateDelta::new(
+                        "pc_1",
+                        HashMap::from([("reserve".to_string(), Bytes::from("0x02"))]),
+                        HashSet::from(["fee".to_string()]),
+                    ),
+                ),
+            ]),
+            ..Default::default()
+        };
+
+        let rows = changes.to_attribute_rows();
+
+        let row = |component_id: &str, name: &str, value_hex: Option<&str>, change| AttributeRow {
+            block_number: 1,
+            component_id: component_id.to_string(),
+            attribute_name: name.to_string(),
+            value_hex: value_hex.map(str::to_string),
+            change,
+        };
+        assert_eq!(
+            rows,
+            vec![
+                row("pc_1", "fee", None, ChangeType::Deletion),
+                row("pc_1", "reserve", Some("0x02"), ChangeType::Update),
+                row("pc_2", "reserve", Some("0x01"), ChangeType::Update),
+            ]
+        );
+    }
+
+    fn state_block(number: u64, revert: bool, attr: &str, value: u64) -> BlockAggregatedChanges {
+        BlockAggregatedChanges {
+            block: Block { number, ..Default::default() },
+            revert,
+            state_deltas: HashMap::from([(
+                "pc_1".to_string(),
+                ProtocolComponentStateDelta::new(
+                    "pc_1",
+                    HashMap::from([(attr.to_string(), Bytes::from(value))]),
+                    HashSet::new(),
+                ),
+            )]),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_state_window_coalescer() {
+        let mut coalescer = StateWindowCoalescer::new(3);
+
+        assert_eq!(coalescer.push(state_block(1, false, "reserve", 1)), Ok(vec![]));
+        assert_eq!(coalescer.push(state_block(2, false, "fee", 2)), Ok(vec![]));
+        let window = coalescer
+            .push(state_block(3, false, "reserve", 3))
+            .unwrap()
+            .remove(0);
+
+        assert_eq!(window.block.number, 3);
+        assert_eq!(
+            window.state_deltas["pc_1"].updated_attributes,
+            HashMap::from([
+                ("reserve".to_string(), Bytes::from(3u64)),
+                ("fee".to_string(), Bytes::from(2u64)),
+            ])
+        );
+        assert_eq!(coalescer.flush(), None);
+    }
+
+    #[test]
+    fn test_state_window_coalescer_revert() {
+        let mut coalescer = StateWindowCoalescer::new(3);
+        coalescer
+            .push(state_block(1, false, "reserve", 1))
+            .unwrap();
+        coalescer
+            .push(state_block(2, false, "reserve", 2))
+            .unwrap();
+
+        let revert = state_block(1, true, "reserve", 1);
+        let messages = coalescer.push(revert.clone()).unwrap();
+
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].block.number, 2);
+        assert_eq!(
+            messages[0].state_deltas["pc_1"].updated_attributes["reserve"],
+            Bytes::from(2u64)
+        );
+        assert_eq!(messages[1], revert);
+        assert_eq!(coalescer.flush(), None);
+
+        assert_eq!(coalescer.push(state_block(2, false, "reserve", 5)), Ok(vec![]));
+        let partial = coalescer.flush().unwrap();
+        assert_eq!(partial.block.number, 2);
+        assert_eq!(partial.state_deltas["pc_1"].updated_attributes["reserve"], Bytes::from(5u64));
+    }
+
+    #[test]
+    fn test_state_window_coalescer_rejects_other_changes() {
+        let mut coalescer = StateWindowCoalescer::new(3);
+        coalescer
+            .push(state_block(1, false, "reserve", 1))
+            .unwrap();
+        let mut block = state_block(2, false, "reserve", 2);
+        block
+            .component_tvl
+            .insert("pc_1".to_string(), 1.0);
+
+        assert_eq!(coalescer.push(block), Err(CoalesceError::UnsupportedChanges(2)));
+        assert_eq!(coalescer.flush().unwrap().block.number, 1);
+    }
+
+    #[test]
+    fn test_undeclared_slot_changes() {
+        let address = Bytes::from("0x01");
+        let slot = |s: u64| Bytes::from(s).lpad(32, 0);
+        let delta = AccountDelta::new(
+            Chain::Ethereum,
+            address.clone(),
+            HashMap::from([(slot(1), Some(slot(2))), (slot(3), None)]),
+            None,
+            None,
+            ChangeType::Update,
+        );
+        let mut changes = TxWithChanges {
+            account_deltas: HashMap::from([(address.clone(), delta)]),
+            ..Default::default()
+        };
+        assert!(changes
+            .undeclared_slot_changes()
+            .is_empty());
+
+        changes.tx = Transaction::default()
+            .with_access_list(Some(vec![(address.clone(), vec![slot(1), slot(2)])]));
+
+        assert_eq!(changes.undeclared_slot_changes(), vec![(address, slot(3))]);
+    }
+
+    #[test]
+    fn test_merge_static_attribute_update() {
+        let block_hash = "0x0000000000000000000000000000000000000000000000000000000000000001";
+        let creation_tx = fixtures::create_transaction("0x01", block_hash, 1);
+        let update_tx = fixtures::create_transaction("0x02", block_hash, 2);
+        let component = |change, static_attributes| {
+            ProtocolComponent::builder("pc_1", "ambient", "pool", Chain::Ethereum)
+                .tokens(vec![Bytes::from("0x01")])
+                .static_attributes(static_attributes)
+                .change(change)
+                .creation_tx(creation_tx.hash.clone())
+                .build()
+                .unwrap()
+        };
+        let created = component(
+            ChangeType::Creation,
+            HashMap::from([
+                ("fee".to_string(), Bytes::from(500u64)),
+                ("tick_spacing".to_string(), Bytes::from(10u64)),
+            ]),
+        );
+        let updated = component(
+            ChangeType::Update,
+            HashMap::from([("fee".to_string(), Bytes::from(3000u64))]),
+        );
+        let changes = |component: ProtocolComponent, tx| {
+            TxWithChanges::new(
+                HashMap::from([(component.id.clone(), component)]),
+                HashMap::new(),
+                HashMap::new(),
+                HashMap::new(),
+                tx,
+            )
+        };
+        let mut merged = changes(created.clone(), creation_tx);
+
+        merged
+            .merge(changes(updated, update_tx))
+            .unwrap();
+
+        let expected = ProtocolComponent {
+            static_attributes: HashMap::from([
+                ("fee".to_string(), Bytes::from(3000u64)),
+                ("tick_spacing".to_string(), Bytes::from(10u64)),
+            ]),
+            ..created
+        };
+        assert_eq!(merged.protocol_components, HashMap::from([("pc_1".to_string(), expected)]));
+    }
+
+    #[rstest]
+    #[case::present("pc_1", "reserve", Some(Bytes::from(1000u64)))]
+    #[case::absent_attribute("pc_1", "fee", None)]
+    #[case::absent_component("pc_2", "reserve", None)]
+    fn test_attribute(
+        #[case] component_id: &str,
+        #[case] attr: &str,
+        #[case] expected: Option<Bytes>,
+    ) {
+        let changes = block_changes(1, false, 1000);
+
+        let res = changes.attribute(component_id, attr);
+        let res_u256 = changes
+            .attribute_u256(component_id, attr)
+            .unwrap();
+
+        assert_eq!(
+            res_u256,
+            expected
+                .as_ref()
+                .map(|_| U256::from(1000u64))
+        );
+        assert_eq!(res, expected);
+    }
+
+    #[test]
+    fn test_attribute_u256_too_long() {
+        let mut changes = block_changes(1, false, 1000);
+        changes
+            .state_deltas
+            .get_mut("pc_1")
+            .unwrap()
+            .updated_attributes
+            .insert("reserve".to_string(), Bytes::from(vec![1u8; 33]));
+
+        let res = changes.attribute_u256("pc_1", "reserve");
+
+        assert_eq!(res, Err(AttributeDecodeError::TooLong("reserve".to_string(), 33)));
+    }
+
+    #[test]
+    fn test_balances_exceeding_supply() {
+        let balance = |token: &str, balance_float: f64| {
+            ComponentBalance::new(
+                Bytes::from(token),
+                Bytes::from(balance_float as u64),
+                balance_float,
+                Bytes::zero(32),
+                "pc_1",
+            )
+        };
+        let changes = BlockAggregatedChanges {
+            component_balances: HashMap::from([(
+                "pc_1".to_string(),
+                HashMap::from([
+                    (Bytes::from("0x01"), balance("0x01", 1000.0)),
+                    (Bytes::from("0x02"), balance("0x02", 1005.0)),
+                    (Bytes::from("0x03"), balance("0x03", 1e30)),
+                    (Bytes::from("0x04"), balance("0x04", 1e30)),
+                ]),
+            )]),
+            ..Default::default()
+        };
+        let total_supplies = HashMap::from([
+            (Bytes::from("0x01"), U256::from(1000u64)),
+            (Bytes::from("0x02"), U256::from(1000u64)),
+            (Bytes::from("0x03"), U256::from(1000u64)),
+        ]);
+
+        let res = changes.balances_exceeding_supply(&total_supplies, 0.01);
+
+        assert_eq!(res, vec![&balance("0x03", 1e30)]);
+    }
+
+    #[test]
+    fn test_split_by_system() {
+        let component = |id: &str, system: &str, contract: &str| {
+            ProtocolComponent::builder(id, system, "pool", Chain::Ethereum)
+                .tokens(vec![Bytes::from("0xaa")])
+                .contract_addresses(vec![Bytes::from(contract)])
+                .change(ChangeType::Creation)
+                .build()
+                .unwrap()
+        };
+        let account_delta = |address: &str| {
+            AccountDelta::new(
+                Chain::Ethereum,
+                Bytes::from(address),
+                HashMap::new(),
+                Some(Bytes::from(1u64)),
+                None,
+                ChangeType::Update,
+            )
+        };
+        let state_delta = |id: &str| {
+            ProtocolComponentStateDelta::new(
+                id,
+                HashMap::from([("reserve".to_string(), Bytes::from(1u64))]),
+                HashSet::new(),
+            )
+        };
+        let token =
+            CurrencyToken::new(&Bytes::from("0xaa"), "TKN", 18, 0, &[], Chain::Ethereum, 100);
+        let block = Block { number: 7, ..Default::default() };
+        let changes = BlockAggregatedChanges {
+            block: block.clone(),
+            new_protocol_components: HashMap::from([(
+                "pc_1".to_string(),
+                component("pc_1", "uniswap_v2", "0x01"),
+            )]),
+            new_tokens: HashMap::from([(token.address.clone(), token.clone())]),
+            account_deltas: HashMap::from([
+                (Bytes::from("0x01"), account_delta("0x01")),
+                (Bytes::from("0x02"), account_delta("0x02")),
+                (Bytes::from("0x03"), account_delta("0x03")),
+            ]),
+            state_deltas: HashMap::from([
+                ("pc_1".to_string(), state_delta("pc_1")),
+                ("pc_2".to_string(), state_delta("pc_2")),
+            ]),
+            ..Default::default()
+        };
+        let known_components =
+            HashMap::from([("pc_2".to_string(), component("pc_2", "ambient", "0x02"))]);
+
+        let res = changes.split_by_system(&known_components);
+
+        let expected = HashMap::from([
+            (
+                "uniswap_v2".to_string(),
+                BlockAggregatedChanges {
+                    block: block.clone(),
+                    new_protocol_components: changes.new_protocol_components.clone(),
+                    new_tokens: changes.new_tokens.clone(),
+                    account_deltas: HashMap::from([(Bytes::from("0x01"), account_delta("0x01"))]),
+                    state_deltas: HashMap::from([("pc_1".to_string(), state_delta("pc_1"))]),
+                    ..Default::default()
+                },
+            ),
+            (
+                "ambient".to_string(),
+                BlockAggregatedChanges {
+                    block,
+                    account_deltas: HashMap::from([(Bytes::from("0x02"), account_delta("0x02"))]),
+                    state_deltas: HashMap::from([("pc_2".to_string(), state_delta("pc_2"))]),
+                    ..Default::default()
+                },
+            ),
+        ]);
+        assert_eq!(res, expected);
+    }
+
+    fn block_changes(number: u64, revert: bool, reserve: u64) -> BlockAggregatedChanges {
+        let address = Bytes::from("0x01");
+        BlockAggregatedChanges {
+            block: Block { number, ..Default::default() },
+            revert,
+            account_deltas: HashMap::from([(
+                address.clone(),
+                AccountDelta::new(
+                    Chain::Ethereum,
+                    address,
+                    HashMap::from([(Bytes::from("0x00"), Some(Bytes::from(reserve)))]),
+                    None,
+                    None,
+                    ChangeType::Update,
+                ),
+            )]),
+            state_deltas: HashMap::from([(
+                "pc_1".to_string(),
+                ProtocolComponentStateDelta::new(
+                    "pc_1",
+                    HashMap::from([("reserve".to_string(), Bytes::from(reserve))]),
+                    HashSet::new(),
+                ),
+            )]),
+            ..Default::default()
+        }
+    }
+
+    /// Returns a store containing the account updated by [`block_changes`].
+    fn store_with_account() -> StateStore {
+        let address = Bytes::from("0x01");
+        let mut store = StateStore::default();
+        BlockAggregatedChanges {
+            account_deltas: HashMap::from([(
+                address.clone(),
+                AccountDelta::new(
+                    Chain::Ethereum,
+                    address,
+                    HashMap::new(),
+                    None,
+                    None,
+                    ChangeType::Creation,
+                ),
+            )]),
+            ..Default::default()
+        }
+        .apply_to(&mut store)
+        .unwrap();
+        store
+    }
+
+    #[test]
+    fn test_apply_blocks_ordered() {
+        let mut store = store_with_account();
+
+        apply_blocks(&mut store, &[block_changes(1, false, 1), block_changes(2, false, 2)])
+            .unwrap();
+        apply_blocks(&mut store, &[block_changes(3, false, 3), block_changes(2, true, 2)]).unwrap();
+
+        assert_eq!(
+            store.accounts[&Bytes::from("0x01")].slots,
+            HashMap::from([(Bytes::from("0x00"), Bytes::from(2u64))])
+        );
+        assert_eq!(
+            store.protocol_states["pc_1"].attributes,
+            HashMap::from([("reserve".to_string(), Bytes::from(2u64))])
+        );
+    }
+
+    #[rstest]
+    #[case::gap(vec![(1, false), (3, false)], ApplyError::BlockGap { last: 1, received: 3 })]
+    #[case::regression(
+        vec![(1, false), (2, false), (1, false)],
+        ApplyError::BlockRegression { last: 2, received: 1 }
+    )]
+    #[case::forward_revert(vec![(1, false), (2, true)], ApplyError::BlockGap { last: 1, received: 2 })]
+    fn test_apply_blocks_out_of_order(#[case] blocks: Vec<(u64, bool)>, #[case] exp: ApplyError) {
+        let mut store = store_with_account();
+        let blocks: Vec<_> = blocks
+            .into_iter()
+            .map(|(number, revert)| block_changes(number, revert, number))
+            .collect();
+
+        let res = apply_blocks(&mut store, &blocks);
+
+        assert_eq!(res, Err(exp));
+        assert_eq!(store, store_with_account());
+    }
+
+    #[test]
+    fn test_apply_update_to_unknown_account() {
+        let mut store = StateStore::default();
+
+        let res = apply_blocks(&mut store, &[block_changes(1, false, 1)]);
+
+        assert_eq!(res, Err(ApplyError::Delta(DeltaError::UnknownAccount("0x01".to_string()))));
+        assert!(store.accounts.is_empty());
+    }
+
+    #[rstest]
+    #[case::error(
+        CreationReplayPolicy::Error,
+        Err(DeltaError::DuplicateCreation("0x01".to_string())),
+        2
+    )]
+    #[case::skip(CreationReplayPolicy::Skip, Ok(()), 2)]
+    #[case::overwrite(CreationReplayPolicy::Overwrite, Ok(()), 1)]
+    fn test_apply_duplicate_creation(
+        #[case] policy: CreationReplayPolicy,
+        #[case] exp: Result<(), DeltaError>,
+        #[case] exp_slot: u64,
+    ) {
+        let address = Bytes::from("0x01");
+        let creation = BlockAggregatedChanges {
+            account_deltas: HashMap::from([(
+                address.clone(),
+                AccountDelta::new(
+                    Chain::Ethereum,
+                    address.clone(),
+                    HashMap::from([(Bytes::from("0x00"), Some(Bytes::from(1u64)))]),
+                    None,
+                    None,
+                    ChangeType::Creation,
+                ),
+            )]),
+            ..Default::default()
+        };
+        let mut store = StateStore::default().with_creation_replay_policy(policy);
+        creation.apply_to(&mut store).unwrap();
+        block_changes(1, false, 2)
+            .apply_to(&mut store)
+            .unwrap();
+
+        let res = creation.apply_to(&mut store);
+
+        assert_eq!(res, exp);
+        assert_eq!(
+            store.accounts[&address].slots,
+            HashMap::from([(Bytes::from("0x00"), Bytes::from(exp_slot))])
+        );
+    }
+
+    #[test]
+    fn test_apply_proxy_impl_swap() {
+        let proxy = Bytes::from("0x01");
+        let implementation = Bytes::from("0x02");
+        let implementation_code = Bytes::from("0x6080604052");
+        let account = |address: &Bytes, code: Bytes| {
+            AccountDelta::new(
+                Chain::Ethereum,
+                address.clone(),
+                HashMap::new(),
+                None,
+                Some(code),
+                ChangeType::Creation,
+            )
+        };
+        let mut store = StateStore::default();
+        BlockAggregatedChanges {
+            account_deltas: HashMap::from([
+                (proxy.clone(), account(&proxy, Bytes::from("0x60ff"))),
+                (implementation.clone(), account(&implementation, implementation_code.clone())),
+            ]),
+            ..Default::default()
+        }
+        .apply_to(&mut store)
+        .unwrap();
+        let mut upgrade = AccountDelta::new(
+            Chain::Ethereum,
+            proxy.clone(),
+            HashMap::new(),
+            None,
+            None,
+            ChangeType::Update,
+        );
+        upgrade.set_code_change(CodeChange::ProxyImpl(implementation));
+        let upgrade = BlockAggregatedChanges {
+            account_deltas: HashMap::from([(proxy.clone(), upgrade)]),
+            ..Default::default()
+        };
+
+        upgrade.apply_to(&mut store).unwrap();
+
+        assert_eq!(store.accounts[&proxy].code, implementation_code);
+        assert_eq!(
+            upgrade.apply_to(&mut StateStore::default()),
+            Err(DeltaError::UnknownProxyImpl("0x02".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_estimated_size() {
+        let address = Bytes::from(vec![1u8; 20]);
+        let slots = (0..50u64)
+            .map(|i| (Bytes::from(i).lpad(32, 0), Some(Bytes::from(i + 1).lpad(32, 0))))
+            .collect();
+        let account = AccountDelta::new(
+            Chain::Ethereum,
+            address.clone(),
+            slots,
+            Some(Bytes::from(vec![2u8; 32])),
+            Some(Bytes::from(vec![3u8; 500])),
+            ChangeType::Update,
+        );
+        let component = ProtocolComponent::builder("pc_1", "ambient", "pool", Chain::Ethereum)
+            .tokens(vec![Bytes::from(vec![4u8; 20]), Bytes::from(vec![5u8; 20])])
+            .contract_addresses(vec![address.clone()])
+            .static_attributes(HashMap::from([("fee".to_string(), Bytes::from(3000u64))]))
+            .change(ChangeType::Creation)
+            .build()
+            .unwrap();
+        let state = ProtocolComponentStateDelta::new(
+            "pc_1",
+            HashMap::from([
+                ("reserve0".to_string(), Bytes::from(vec![6u8; 32])),
+                ("reserve1".to_string(), Bytes::from(vec![7u8; 32])),
+            ]),
+            HashSet::from(["tick".to_string()]),
+        );
+        let changes = BlockAggregatedChanges {
+            extractor: "test".to_string(),
+            block: Block {
+                hash: Bytes::zero(32),
+                parent_hash: Bytes::zero(32),
+                ..Default::default()
+            },
+            account_deltas: HashMap::from([(address, account)]),
+            new_protocol_components: HashMap::from([("pc_1".to_string(), component)]),
+            state_deltas: HashMap::from([("pc_1".to_string(), state)]),
+            ..Default::default()
+        };
+
+        let estimate = changes.estimated_size() as f64;
+        let actual = serde_json::to_vec(&changes)
+            .unwrap()
+            .len() as f64;
+
+        assert!((estimate - actual).abs() / actual < 0.1, "estimate {estimate}, actual {actual}");
+    }
+
+    #[test]
+    fn test_drop_empty_updates() {
+        let empty = Bytes::from("0x01");
+        let deleted = Bytes::from("0x02");
+        let updated = Bytes::from("0x03");
+        let mut changes = BlockAggregatedChanges {
+            account_deltas: HashMap::from([
+                (
+                    empty.clone(),
+                    AccountDelta::new(
+                        Chain::Ethereum,
+                        empty.clone(),
+                        HashMap::new(),
+                        None,
+                        None,
+                        ChangeType::Update,
+                    ),
+                ),
+                (deleted.clone(), AccountDelta::deleted(&Chain::Ethereum, &deleted)),
+                (
+                    updated.clone(),
+                    AccountDelta::new(
+                        Chain::Ethereum,
+                        updated.clone(),
+                        HashMap::new(),
+                        Some(Bytes::from(100u64)),
+                        None,
+                        ChangeType::Update,
+                    ),
+                ),
+            ]),
+            ..Default::default()
+        };
+
+        changes.drop_empty_updates();
+
+        assert_eq!(
+            changes
+                .account_deltas
+                .keys()
+                .collect::<HashSet<_>>(),
+            HashSet::from([&deleted, &updated])
+        );
+    }
+
+    fn balance_changes(number: u64, balances: Vec<(&str, u64)>) -> BlockAggregatedChanges {
+        let token = Bytes::from("0x01");
+        BlockAggregatedChanges {
+            block: Block { number, ..Default::default() },
+            component_balances: balances
+                .into_iter()
+                .map(|(component_id, balance)| {
+                    let balance = ComponentBalance::new(
+                        token.clone(),
+                        Bytes::from(balance),
+                        balance as f64,
+                        Bytes::zero(32),
+                        component_id,
+                    );
+                    (component_id.to_string(), HashMap::from([(token.clone(), balance)]))
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_tvl_state_apply_and_revert() {
+        let token = Bytes::from("0x01");
+        let first = balance_changes(1, vec![("pc_1", 100), ("pc_2", 50)]);
+        let second = balance_changes(2, vec![("pc_1", 150), ("pc_3", 10)]);
+        let mut state = TvlState::new();
+
+        state.apply_block(&first);
+        state.apply_block(&second);
+
+        assert_eq!(state.balance("pc_1", &token), Some(&Bytes::from(150u64)));
+        assert_eq!(state.balance("pc_3", &token), Some(&Bytes::from(10u64)));
+
+        assert_eq!(
+            state.revert_block(&first),
+            Err(ApplyError::NotLastBlock { last: Some(2), received: 1 })
+        );
+        state.revert_block(&second).unwrap();
+
+        assert_eq!(state.balance("pc_1", &token), Some(&Bytes::from(100u64)));
+        assert_eq!(state.balance("pc_2", &token), Some(&Bytes::from(50u64)));
+        assert_eq!(state.balance("pc_3", &token), None);
+    }
+
+    #[test]
+    fn test_component_index_add_and_delete() {
+        let component = ProtocolComponent::builder("pc_1", "ambient", "pool", Chain::Ethereum)
+            .tokens(vec![Bytes::from("0x01"), Bytes::from("0x02")])
+            .change(ChangeType::Creation)
+            .build()
+            .unwrap();
+        let other = ProtocolComponent::builder("pc_2", "ambient", "pool", Chain::Ethereum)
+            .tokens(vec![Bytes::from("0x02")])
+            .change(ChangeType::Creation)
+            .build()
+            .unwrap();
+        let mut index = ComponentIndex::new();
+
+        index.add_block(&BlockAggregatedChanges {
+            new_protocol_components: HashMap::from([
+                ("pc_1".to_string(), component.clone()),
+                ("pc_2".to_string(), other),
+            ]),
+            ..Default::default()
+        });
+
+        assert_eq!(
+            index.components_for_token(&Bytes::from("0x02")),
+            &HashSet::from(["pc_1".to_string(), "pc_2".to_string()])
+        );
+
+        index.add_block(&BlockAggregatedChanges {
+            deleted_protocol_components: HashMap::from([("pc_1".to_string(), component)]),
+            ..Default::default()
+        });
+
+        assert!(index
+            .components_for_token(&Bytes::from("0x01"))
+            .is_empty());
+        assert_eq!(
+            index.components_for_token(&Bytes::from("0x02")),
+            &HashSet::from(["pc_2".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_component_index_on_component_deleted() {
+        let component = ProtocolComponent::builder("pc_1", "ambient", "pool", Chain::Ethereum)
+            .tokens(vec![Bytes::from("0x01"), Bytes::from("0x02")])
+            .change(ChangeType::Creation)
+            .build()
+            .unwrap();
+        let mut index = ComponentIndex::new();
+        index.add_block(&BlockAggregatedChanges {
+            new_protocol_components: HashMap::from([("pc_1".to_string(), component)]),
+            ..Default::default()
+        });
+
+        index.on_component_deleted("pc_1");
+        index.on_component_deleted("pc_1");
+
+        assert!(index
+            .components_for_token(&Bytes::from("0x01"))
+            .is_empty());
+        assert_eq!(index, ComponentIndex::new());
+    }
+}
@@ -67,22 +67,156 @@ impl Account {
         if self_id != other_id {
             return Err(DeltaError::IdMismatch(format!("{:?}", self_id), format!("{:?}", other_id)));
         }
+        match delta.code_change() {
+            CodeChange::Full(code) => self.code = code,
+            // Resolving the implementation's code requires the state of other accounts.
+            CodeChange::ProxyImpl(implementation) => {
+                return Err(DeltaError::UnknownProxyImpl(implementation.to_string()))
+            }
+            CodeChange::Unchanged => {}
+        }
         if let Some(balance) = delta.balance.as_ref() {
             self.native_balance.clone_from(balance);
         }
-        if let Some(code) = delta.code.as_ref() {
-            self.code.clone_from(code);
+        // Deleted slots are removed, so they can't be told apart from slots set to empty bytes.
+        for (key, value) in delta.slots.iter() {
+            match value {
+                Some(value) => {
+                    self.slots
+                        .insert(key.clone(), value.clone());
+                }
+                None => {
+                    self.slots.remove(key);
+                }
+            }
         }
-        self.slots.extend(
-            delta
-                .slots
-                .clone()
-                .into_iter()
-                .map(|(k, v)| (k, v.unwrap_or_default())),
-        );
         // TODO: Update modify_tx, code_modify_tx and code_hash.
         Ok(())
     }
+
+    /// Captures the last known state of this account before it is removed.
+    ///
+    /// Deletion deltas do not carry any state, so this should be called with the account as it
+    /// was right before `delete_tx` was applied.
+    pub fn archive_on_delete(&self, delete_tx: &Transaction) -> ArchivedAccount {
+        ArchivedAccount { account: self.clone(), delete_tx: delete_tx.clone() }
+    }
+
+    /// Computes the update that turns this account into `newer`.
+    ///
+    /// Only changed or added slots are included (as `Some`), slots missing in `newer` are
+    /// included as `None`. Balance and code are only set if they differ. Storage loads deleted
+    /// slots with empty values, so slots set to empty bytes are treated like missing ones.
+    ///
+    /// Both accounts must exist, the returned delta is always an update. Accounts that are
+    /// created or deleted in between are described by a creation or a deletion delta instead.
+    ///
+    /// # Errors
+    /// Returns `DeltaError::IdMismatch` if the accounts differ in chain or address.
+    pub fn diff(&self, newer: &Account) -> Result<AccountDelta, DeltaError> {
+        let self_id = (self.chain, &self.address);
+        let other_id = (newer.chain, &newer.address);
+        if self_id != other_id {
+            return Err(DeltaError::IdMismatch(format!("{:?}", self_id), format!("{:?}", other_id)));
+        }
+        let slot = |account: &Account, key: &StoreKey| {
+            account
+                .slots
+                .get(key)
+                .filter(|value| !value.is_empty())
+                .cloned()
+        };
+        let slots: HashMap<StoreKey, Option<StoreVal>> = self
+            .slots
+            .keys()
+            .chain(newer.slots.keys())
+            .filter_map(|key| {
+                let value = slot(newer, key);
+                (slot(self, key) != value).then(|| (key.clone(), value))
+            })
+            .collect();
+        let balance =
+            (self.native_balance != newer.native_balance).then(|| newer.native_balance.clone());
+        let code = (self.code != newer.code).then(|| newer.code.clone());
+
+        Ok(AccountDelta::new(
+            self.chain,
+            self.address.clone(),
+            slots,
+            balance,
+            code,
+            ChangeType::Update,
+        ))
+    }
+}
+
+/// The final state of a deleted account together with the transaction that deleted it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ArchivedAccount {
+    pub account: Account,
+    pub delete_tx: Transaction,
+}
+
+/// What is known about an account when applying a delta to it.
+///
+/// Distinguishes an account that was never seen from one that exists but is empty, which
+/// `Option<Account>` can't if empty accounts are represented by absence.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AccountState {
+    /// The account was never seen or was deleted. Only a creation may be applied to it.
+    Unknown,
+    Present(Account),
+}
+
+impl AccountState {
+    /// Applies `delta` emitted by `tx`.
+    ///
+    /// A creation turns an unknown account into a present one, a deletion makes the account
+    /// unknown again and an update is applied to the present account.
+    ///
+    /// # Errors
+    /// Returns `DeltaError::UnknownAccount` if an update is applied to an unknown account and
+    /// `DeltaError::DuplicateCreation` if a creation is applied to a present account.
+    pub fn apply_delta(
+        &mut self,
+        delta: &AccountDelta,
+        tx: &Transaction,
+    ) -> Result<(), DeltaError> {
+        match delta.change {
+            ChangeType::Deletion => *self = AccountState::Unknown,
+            ChangeType::Creation => match self {
+                AccountState::Unknown => *self = AccountState::Present(delta.ref_into_account(tx)),
+                AccountState::Present(account) => {
+                    return Err(DeltaError::DuplicateCreation(account.address.to_string()))
+                }
+            },
+            _ => match self {
+                AccountState::Unknown => {
+                    return Err(DeltaError::UnknownAccount(delta.address.to_string()))
+                }
+                AccountState::Present(account) => account.apply_delta(delta)?,
+            },
+        }
+        Ok(())
+    }
+}
+
+impl From<Option<Account>> for AccountState {
+    fn from(value: Option<Account>) -> Self {
+        value.map_or(AccountState::Unknown, AccountState::Present)
+    }
+}
+
+/// How a delta changes the code of an account.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum CodeChange {
+    /// The new code in full.
+    Full(Code),
+    /// The account is a proxy that now delegates to the given implementation. Its code becomes
+    /// the implementation's code, which saves sending the full code on every upgrade.
+    ProxyImpl(Address),
+    #[default]
+    Unchanged,
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Default)]
@@ -92,6 +226,10 @@ pub struct AccountDelta {
     pub slots: HashMap<StoreKey, Option<StoreVal>>,
     pub balance: Option<Balance>,
     pub code: Option<Code>,
+    /// New implementation of a proxy account, only used if `code` is not set. See
+    /// [`CodeChange::ProxyImpl`].
+    #[serde(default)]
+    pub proxy_impl: Option<Address>,
     pub change: ChangeType,
 }
 
@@ -113,7 +251,25 @@ impl AccountDelta {
         code: Option<Code>,
         change: ChangeType,
     ) -> Self {
-        Self { chain, address, change, slots, balance, code }
+        Self { chain, address, change, slots, balance, code, proxy_impl: None }
+    }
+
+    /// Returns how this delta changes the account's code.
+    pub fn code_change(&self) -> CodeChange {
+        match (&self.code, &self.proxy_impl) {
+            (Some(code), _) => CodeChange::Full(code.clone()),
+            (None, Some(implementation)) => CodeChange::ProxyImpl(implementation.clone()),
+            (None, None) => CodeChange::Unchanged,
+        }
+    }
+
+    /// Sets how this delta changes the account's code.
+    pub fn set_code_change(&mut self, change: CodeChange) {
+        (self.code, self.proxy_impl) = match change {
+            CodeChange::Full(code) => (Some(code), None),
+            CodeChange::ProxyImpl(implementation) => (None, Some(implementation)),
+            CodeChange::Unchanged => (None, None),
+        };
     }
 
     pub fn contract_id(&self) -> ContractId {
@@ -121,7 +277,6 @@ impl AccountDelta {
     }
 
     pub fn into_account(self, tx: &Transaction) -> Account {
-        let empty_hash = keccak256(Vec::new());
         Account::new(
             self.chain,
             self.address.clone(),
@@ -134,9 +289,8 @@ impl AccountDelta {
             self.code.clone().unwrap_or_default(),
             self.code
                 .as_ref()
-                .map(keccak256)
-                .unwrap_or(empty_hash)
-                .into(),
+                .map(|code| self.chain.code_hash(code))
+                .unwrap_or_else(|| self.chain.empty_code_hash()),
             tx.hash.clone(),
             tx.hash.clone(),
             Some(tx.hash.clone()),
@@ -146,7 +300,6 @@ impl AccountDelta {
     /// Convert the delta into an account. Note that data not present in the delta, such as
     /// creation_tx etc, will be initialized to default values.
     pub fn into_account_without_tx(self) -> Account {
-        let empty_hash = keccak256(Vec::new());
         Account::new(
             self.chain,
             self.address.clone(),
@@ -159,9 +312,8 @@ impl AccountDelta {
             self.code.clone().unwrap_or_default(),
             self.code
                 .as_ref()
-                .map(keccak256)
-                .unwrap_or(empty_hash)
-                .into(),
+                .map(|code| self.chain.code_hash(code))
+                .unwrap_or_else(|| self.chain.empty_code_hash()),
             Bytes::from("0x00"),
             Bytes::from("0x00"),
             None,
@@ -170,7 +322,6 @@ impl AccountDelta {
 
     // Convert AccountUpdate into Account using references.
     pub fn ref_into_account(&self, tx: &Transaction) -> Account {
-        let empty_hash = keccak256(Vec::new());
         if self.change != ChangeType::Creation {
             warn!("Creating an account from a partial change!")
         }
@@ -188,9 +339,8 @@ impl AccountDelta {
             self.code.clone().unwrap_or_default(),
             self.code
                 .as_ref()
-                .map(keccak256)
-                .unwrap_or(empty_hash)
-                .into(),
+                .map(|code| self.chain.code_hash(code))
+                .unwrap_or_else(|| self.chain.empty_code_hash()),
             tx.hash.clone(),
             tx.hash.clone(),
             Some(tx.hash.clone()),
@@ -201,7 +351,9 @@ impl AccountDelta {
     ///
     /// This function is utilized for aggregating multiple updates into a single
     /// update. The attribute values of `other` are set on `self`.
-    /// Meanwhile, contract storage maps are merged, with keys from `other` taking precedence.
+    /// Meanwhile, contract storage maps are merged, with keys from `other` taking precedence. A
+    /// slot deleted by `other` stays deleted (`None`), a slot deleted by `self` and set again by
+    /// `other` takes the new value.
     ///
     /// Be noted that, this function will mutate the state of the calling
     /// struct. An error will occur if merging updates from different accounts.
@@ -227,12 +379,15 @@ impl AccountDelta {
             ));
         }
 
+        let code_change = other.code_change();
         self.slots.extend(other.slots);
 
         if let Some(balance) = other.balance {
             self.balance = Some(balance)
         }
-        self.code = other.code.or(self.code.take());
+        if code_change != CodeChange::Unchanged {
+            self.set_code_change(code_change);
+        }
 
         Ok(())
     }
@@ -244,6 +399,93 @@ impl AccountDelta {
     pub fn is_creation(&self) -> bool {
         self.change == ChangeType::Creation
     }
+
+    /// Returns the keys of all slots this delta sets or resets, in ascending order.
+    pub fn touched_slots(&self) -> impl Iterator<Item = &StoreKey> {
+        let mut keys: Vec<_> = self.slots.keys().collect();
+        keys.sort_unstable();
+        keys.into_iter()
+    }
+
+    /// Returns whether applying this delta would change nothing.
+    ///
+    /// Only updates can be no-ops: a creation brings the account into existence and a deletion
+    /// removes it, even if they carry no data.
+    pub fn is_empty_update(&self) -> bool {
+        self.is_update() &&
+            self.slots.is_empty() &&
+            self.balance.is_none() &&
+            self.code_change() == CodeChange::Unchanged
+    }
+
+    /// Checks that the payload of this delta agrees with its change type.
+    ///
+    /// A deletion must not carry any state. A creation that sets storage slots must also set
+    /// code, as accounts without code have no storage.
+    ///
+    /// # Errors
+    /// Returns `DeltaError::InconsistentChange` describing the first inconsistency found.
+    pub fn validate(&self) -> Result<(), DeltaError> {
+        let inconsistency = match self.change {
+            ChangeType::Deletion if !self.slots.is_empty() => Some("deletion sets slots"),
+            ChangeType::Deletion if self.balance.is_some() => Some("deletion sets a balance"),
+            ChangeType::Deletion if self.code_change() != CodeChange::Unchanged => {
+                Some("deletion sets code")
+            }
+            ChangeType::Creation
+                if !self.slots.is_empty() && self.code_change() == CodeChange::Unchanged =>
+            {
+                Some("creation sets slots but no code")
+            }
+            _ => None,
+        };
+        match inconsistency {
+            Some(reason) => {
+                Err(DeltaError::InconsistentChange(self.address.to_string(), reason.to_string()))
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Computes the delta that undoes this delta, given the account `prior` to applying it.
+    ///
+    /// Touched slots are restored to their prior value, slots that didn't exist before are
+    /// deleted (`None`). Balance and code are restored if this delta changed them. Inverting a
+    /// creation deletes the account and inverting a deletion recreates `prior`.
+    ///
+    /// # Errors
+    /// Returns `DeltaError::IdMismatch` if `prior` differs in chain or address.
+    pub fn invert(&self, prior: &Account) -> Result<AccountDelta, DeltaError> {
+        let self_id = (self.chain, &self.address);
+        let prior_id = (prior.chain, &prior.address);
+        if self_id != prior_id {
+            return Err(DeltaError::IdMismatch(format!("{:?}", self_id), format!("{:?}", prior_id)));
+        }
+        match self.change {
+            ChangeType::Creation => return Ok(AccountDelta::deleted(&self.chain, &self.address)),
+            ChangeType::Deletion => return Ok(prior.clone().into()),
+            ChangeType::Update | ChangeType::Unspecified => {}
+        }
+        let slots = self
+            .slots
+            .keys()
+            .map(|key| (key.clone(), prior.slots.get(key).cloned()))
+            .collect();
+        let balance = self
+            .balance
+            .as_ref()
+            .map(|_| prior.native_balance.clone());
+        let code = (self.code_change() != CodeChange::Unchanged).then(|| prior.code.clone());
+
+        Ok(AccountDelta::new(
+            self.chain,
+            self.address.clone(),
+            slots,
+            balance,
+            code,
+            ChangeType::Update,
+        ))
+    }
 }
 
 impl From<Account> for AccountDelta {
@@ -258,6 +500,7 @@ impl From<Account> for AccountDelta {
                 .collect(),
             balance: Some(value.native_balance),
             code: Some(value.code),
+            proxy_impl: None,
             change: ChangeType::Creation,
         }
     }
@@ -382,7 +625,7 @@ impl From<&TransactionVMUpdates> for Vec<Account> {
                     update
                         .slots
                         .into_iter()
-                        .map(|(k, v)| (k, v.unwrap_or_default())) //TODO: is default ok here or should it be Bytes::zero(32)
+                        .filter_map(|(k, v)| Some((k, v?)))
                         .collect(),
                     update.balance.unwrap_or_default(),
                     update.code.clone().unwrap_or_default(),
@@ -446,6 +689,69 @@ mod test {
             .collect()
     }
 
+    #[test]
+    fn test_touched_slots_sorted() {
+        let mut delta = update_slots_delta();
+        delta
+            .slots
+            .extend(slots([(5, 0), (3, 1)]));
+        delta
+            .slots
+            .insert(Bytes::from(2u64).lpad(32, 0), None);
+
+        let keys: Vec<_> = delta.touched_slots().cloned().collect();
+
+        assert_eq!(
+            keys,
+            [0u64, 1, 2, 3, 5]
+                .map(|s| Bytes::from(s).lpad(32, 0))
+                .to_vec()
+        );
+    }
+
+    #[rstest]
+    #[case::deletion_with_slots(
+        AccountDelta {
+            slots: slots([(0, 1)]),
+            ..AccountDelta::deleted(&Chain::Ethereum, &Bytes::zero(20))
+        },
+        Some("deletion sets slots")
+    )]
+    #[case::deletion_with_code(
+        AccountDelta {
+            code: Some(Bytes::from("0x60ff")),
+            ..AccountDelta::deleted(&Chain::Ethereum, &Bytes::zero(20))
+        },
+        Some("deletion sets code")
+    )]
+    #[case::creation_with_slots_without_code(
+        AccountDelta { change: ChangeType::Creation, ..update_slots_delta() },
+        Some("creation sets slots but no code")
+    )]
+    #[case::creation(
+        AccountDelta {
+            change: ChangeType::Creation,
+            code: Some(Bytes::from("0x60ff")),
+            ..update_slots_delta()
+        },
+        None
+    )]
+    #[case::update(update_slots_delta(), None)]
+    fn test_validate(#[case] delta: AccountDelta, #[case] exp: Option<&str>) {
+        let res = delta.validate();
+
+        assert_eq!(
+            res,
+            match exp {
+                Some(reason) => Err(DeltaError::InconsistentChange(
+                    delta.address.to_string(),
+                    reason.to_string()
+                )),
+                None => Ok(()),
+            }
+        );
+    }
+
     #[test]
     fn test_merge_account_deltas() {
         let mut update_left = update_balance_delta();
@@ -458,6 +764,38 @@ mod test {
         assert_eq!(update_left, exp);
     }
 
+    #[test]
+    fn test_merge_account_deltas_slot_deletion() {
+        let key = |k: u64| Bytes::from(k).lpad(32, 0);
+        let mut update_left = update_slots_delta();
+        let mut update_right = update_slots_delta();
+        update_right.slots = HashMap::from([(key(0), None), (key(5), Some(Bytes::from(5u64)))]);
+        let mut deleted_then_set = update_slots_delta();
+        deleted_then_set.slots = HashMap::from([(key(0), Some(Bytes::from(9u64)))]);
+
+        update_left.merge(update_right).unwrap();
+
+        assert_eq!(update_left.slots[&key(0)], None);
+        assert_eq!(update_left.slots[&key(5)], Some(Bytes::from(5u64)));
+        update_left
+            .merge(deleted_then_set)
+            .unwrap();
+        assert_eq!(update_left.slots[&key(0)], Some(Bytes::from(9u64)));
+    }
+
+    #[test]
+    fn test_apply_delta_deletes_slots() {
+        let key = |k: u64| Bytes::from(k).lpad(32, 0);
+        let mut account = account();
+        account.slots = HashMap::from([(key(0), Bytes::from(1u64)), (key(1), Bytes::from(2u64))]);
+        let mut delta = update_slots_delta();
+        delta.slots = HashMap::from([(key(0), None)]);
+
+        account.apply_delta(&delta).unwrap();
+
+        assert_eq!(account.slots, HashMap::from([(key(1), Bytes::from(2u64))]));
+    }
+
     #[test]
     fn test_merge_account_delta_wrong_address() {
         let mut update_left = update_balance_delta();
@@ -517,6 +855,168 @@ mod test {
         )
     }
 
+    #[test]
+    fn test_apply_delta_to_unknown_account() {
+        let mut state = AccountState::Unknown;
+
+        let res = state.apply_delta(&update_slots_delta(), &block_fixtures::transaction01());
+
+        assert_eq!(
+            res,
+            Err(DeltaError::UnknownAccount(
+                "0xe688b84b23f322a994a53dbf8e15fa82cdb71127".to_string()
+            ))
+        );
+        assert_eq!(state, AccountState::Unknown);
+    }
+
+    #[test]
+    fn test_apply_delta_to_empty_account() {
+        let mut empty = account();
+        empty.native_balance = Bytes::zero(32);
+        empty.code = Bytes::new();
+        let mut state = AccountState::Present(empty.clone());
+
+        state
+            .apply_delta(&update_slots_delta(), &block_fixtures::transaction01())
+            .unwrap();
+
+        empty.slots = slots([(0, 1), (1, 2)])
+            .into_iter()
+            .map(|(k, v)| (k, v.unwrap()))
+            .collect();
+        assert_eq!(state, AccountState::Present(empty));
+    }
+
+    #[test]
+    fn test_archive_on_delete() {
+        let mut account = account();
+        account.slots = HashMap::from([(Bytes::from(1u64).lpad(32, 0), Bytes::from(2u64))]);
+        let delete_tx = block_fixtures::create_transaction(HASH_256_1, HASH_256_0, 12);
+
+        let archived = account.archive_on_delete(&delete_tx);
+
+        assert_eq!(archived.account.slots, account.slots);
+        assert_eq!(archived.account.native_balance, Bytes::from(10000u64).lpad(32, 0));
+        assert_eq!(archived.account.code, Bytes::from(vec![0, 0, 0, 0]));
+        assert_eq!(archived.delete_tx, delete_tx);
+    }
+
+    #[test]
+    fn test_diff() {
+        let key = |k: u64| Bytes::from(k).lpad(32, 0);
+        let mut older = account();
+        older.slots = HashMap::from([
+            (key(0), Bytes::from(1u64)),
+            (key(1), Bytes::from(2u64)),
+            (key(2), Bytes::from(3u64)),
+        ]);
+        let mut newer = older.clone();
+        newer.slots = HashMap::from([
+            (key(0), Bytes::from(1u64)),
+            (key(1), Bytes::from(5u64)),
+            (key(3), Bytes::from(4u64)),
+        ]);
+        newer.native_balance = Bytes::from(20000u64).lpad(32, 0);
+
+        let res = older.diff(&newer).unwrap();
+
+        assert_eq!(
+            res,
+            AccountDelta::new(
+                Chain::Ethereum,
+                older.address.clone(),
+                HashMap::from([
+                    (key(1), Some(Bytes::from(5u64))),
+                    (key(2), None),
+                    (key(3), Some(Bytes::from(4u64))),
+                ]),
+                Some(Bytes::from(20000u64).lpad(32, 0)),
+                None,
+                ChangeType::Update,
+            )
+        );
+        let mut applied = older.clone();
+        applied.apply_delta(&res).unwrap();
+        assert_eq!(applied, newer);
+    }
+
+    #[test]
+    fn test_diff_empty_slot_values() {
+        let key = |k: u64| Bytes::from(k).lpad(32, 0);
+        let mut older = account();
+        older.slots = HashMap::from([(key(0), Bytes::from(1u64)), (key(1), Bytes::new())]);
+        let mut newer = older.clone();
+        newer.slots = HashMap::from([(key(0), Bytes::new()), (key(2), Bytes::new())]);
+
+        let res = older.diff(&newer).unwrap();
+
+        assert_eq!(res.slots, HashMap::from([(key(0), None)]));
+        let mut applied = older.clone();
+        applied.apply_delta(&res).unwrap();
+        assert_eq!(applied.slots, HashMap::from([(key(1), Bytes::new())]));
+    }
+
+    #[test]
+    fn test_invert_balance() {
+        let prior = account();
+        let delta = update_balance_delta();
+
+        let res = delta.invert(&prior).unwrap();
+
+        let mut reverted = prior.clone();
+        reverted.apply_delta(&delta).unwrap();
+        reverted.apply_delta(&res).unwrap();
+        assert_eq!(res.balance, Some(prior.native_balance.clone()));
+        assert!(res.slots.is_empty());
+        assert_eq!(res.code, None);
+        assert_eq!(reverted, prior);
+    }
+
+    #[test]
+    fn test_invert_code() {
+        let prior = account();
+        let mut delta = update_balance_delta();
+        delta.balance = None;
+        delta.code = Some(Bytes::from(vec![1, 2, 3]));
+
+        let res = delta.invert(&prior).unwrap();
+
+        assert_eq!(res.code, Some(prior.code.clone()));
+        assert_eq!(res.balance, None);
+        assert!(res.slots.is_empty());
+    }
+
+    #[test]
+    fn test_invert_slots() {
+        let mut prior = account();
+        prior.slots = HashMap::from([(Bytes::from(0u64).lpad(32, 0), Bytes::from(7u64))]);
+        let delta = update_slots_delta();
+
+        let res = delta.invert(&prior).unwrap();
+
+        assert_eq!(
+            res.slots,
+            HashMap::from([
+                (Bytes::from(0u64).lpad(32, 0), Some(Bytes::from(7u64))),
+                (Bytes::from(1u64).lpad(32, 0), None),
+            ])
+        );
+        assert_eq!(res.balance, None);
+        assert_eq!(res.code, None);
+    }
+
+    #[test]
+    fn test_diff_wrong_address() {
+        let older = account();
+        let mut newer = account();
+        newer.address = Bytes::zero(20);
+
+        let res = older.diff(&newer);
+
+        assert!(matches!(res, Err(DeltaError::IdMismatch(_, _))));
+    }
+
     #[test]
     fn test_account_from_update_w_tx() {
         let update = tx_vm_update();
@@ -557,27 +1057,29 @@ mod test {
     }
 
     fn create_protocol_component(tx_hash: Bytes) -> ProtocolComponent {
-        ProtocolComponent {
-            id: "d417ff54652c09bd9f31f216b1a2e5d1e28c1dce1ba840c40d16f2b4d09b5902".to_owned(),
-            protocol_system: "ambient".to_string(),
-            protocol_type_name: String::from("WeightedPool"),
-            chain: Chain::Ethereum,
-            tokens: vec![
-                Bytes::from_str("0x6B175474E89094C44Da98b954EedeAC495271d0F").unwrap(),
-                Bytes::from_str("0x6B175474E89094C44Da98b954EedeAC495271d0F").unwrap(),
-            ],
-            contract_addresses: vec![
-                Bytes::from_str("0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2").unwrap(),
-                Bytes::from_str("0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2").unwrap(),
-            ],
-            static_attributes: HashMap::from([
-                ("key1".to_string(), Bytes::from(b"value1".to_vec())),
-                ("key2".to_string(), Bytes::from(b"value2".to_vec())),
-            ]),
-            change: ChangeType::Creation,
-            creation_tx: tx_hash,
-            created_at: NaiveDateTime::from_timestamp_opt(1000, 0).unwrap(),
-        }
+        ProtocolComponent::builder(
+            "d417ff54652c09bd9f31f216b1a2e5d1e28c1dce1ba840c40d16f2b4d09b5902",
+            "ambient",
+            "WeightedPool",
+            Chain::Ethereum,
+        )
+        .tokens(vec![
+            Bytes::from_str("0x6B175474E89094C44Da98b954EedeAC495271d0F").unwrap(),
+            Bytes::from_str("0x6B175474E89094C44Da98b954EedeAC495271d0F").unwrap(),
+        ])
+        .contract_addresses(vec![
+            Bytes::from_str("0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2").unwrap(),
+            Bytes::from_str("0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2").unwrap(),
+        ])
+        .static_attributes(HashMap::from([
+            ("key1".to_string(), Bytes::from(b"value1".to_vec())),
+            ("key2".to_string(), Bytes::from(b"value2".to_vec())),
+        ]))
+        .change(ChangeType::Creation)
+        .creation_tx(tx_hash)
+        .created_at(NaiveDateTime::from_timestamp_opt(1000, 0).unwrap())
+        .build()
+        .unwrap()
     }
 
     #[rstest]
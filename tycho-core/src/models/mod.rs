@@ -3,9 +3,14 @@ pub mod contract;
 pub mod protocol;
 pub mod token;
 
-use crate::{dto, Bytes};
+use crate::{dto, keccak256, Bytes};
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, fmt::Display, sync::Arc};
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    str::FromStr,
+    sync::{Arc, OnceLock},
+};
 use strum_macros::{Display, EnumString};
 use thiserror::Error;
 
@@ -59,6 +64,165 @@ pub enum Chain {
     Starknet,
     ZkSync,
     Arbitrum,
+    Optimism,
+    Base,
+    Polygon,
+}
+
+/// StarkNet mainnet's chain id, the ASCII string `SN_MAIN` read as a big-endian integer.
+const STARKNET_MAINNET_ID: u64 = 0x534e_5f4d_4149_4e;
+
+impl Chain {
+    /// Returns the numeric chain id, e.g. 1 for Ethereum mainnet.
+    ///
+    /// StarkNet has no EVM chain id, its id is the ASCII string `SN_MAIN` as an integer.
+    pub fn id(&self) -> u64 {
+        match self {
+            Chain::Ethereum => 1,
+            Chain::ZkSync => 324,
+            Chain::Arbitrum => 42161,
+            Chain::Optimism => 10,
+            Chain::Base => 8453,
+            Chain::Polygon => 137,
+            Chain::Starknet => STARKNET_MAINNET_ID,
+        }
+    }
+
+    /// Returns the chain with the numeric chain id `id`, `None` if it isn't supported.
+    pub fn from_id(id: u64) -> Option<Self> {
+        [
+            Chain::Ethereum,
+            Chain::Starknet,
+            Chain::ZkSync,
+            Chain::Arbitrum,
+            Chain::Optimism,
+            Chain::Base,
+            Chain::Polygon,
+        ]
+        .into_iter()
+        .find(|chain| chain.id() == id)
+    }
+
+    /// Returns the format of account addresses on this chain.
+    pub fn address_format(&self) -> AddressFormat {
+        match self {
+            Chain::Starknet => AddressFormat::Felt,
+            Chain::Ethereum |
+            Chain::ZkSync |
+            Chain::Arbitrum |
+            Chain::Optimism |
+            Chain::Base |
+            Chain::Polygon => AddressFormat::Evm,
+        }
+    }
+
+    /// Parses a hex encoded account address for this chain.
+    ///
+    /// EVM addresses must be exactly 20 bytes long. StarkNet felts are commonly rendered without
+    /// leading zeros, so shorter values (including odd length hex) are left padded to 32 bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AddressError` if the value is not valid hex or does not fit the chain's
+    /// address width.
+    pub fn parse_address(&self, value: &str) -> Result<Address, AddressError> {
+        let format = self.address_format();
+        let hex = value
+            .strip_prefix("0x")
+            .unwrap_or(value);
+        let address = match format {
+            AddressFormat::Felt if hex.len() % 2 == 1 => Bytes::from_str(&format!("0{hex}")),
+            _ => Bytes::from_str(hex),
+        }
+        .map_err(|e| AddressError::InvalidHex(e.to_string()))?;
+        let expected = format.len();
+        match format {
+            AddressFormat::Evm if address.len() == expected => Ok(address),
+            AddressFormat::Felt if address.len() <= expected => Ok(address.lpad(expected, 0)),
+            _ => Err(AddressError::InvalidLength { chain: *self, expected, actual: address.len() }),
+        }
+    }
+
+    /// Returns the address under which balances of the chain's native token are reported.
+    pub fn native_token(&self) -> Address {
+        Bytes::zero(self.address_format().len())
+    }
+
+    /// Returns the address of the ERC20 wrapped version of the chain's native token, e.g. WETH.
+    ///
+    /// StarkNet's native token already is an ERC20 token, so there is no wrapped version.
+    pub fn wrapped_native_token(&self) -> Option<Address> {
+        let address = match self {
+            Chain::Ethereum => "0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2",
+            Chain::ZkSync => "0x5aea5775959fbc2557cc8789bc1bf90a239d9a91",
+            Chain::Arbitrum => "0x82af49447d8a07e3bd95bd0d56f35241523fbab1",
+            Chain::Optimism | Chain::Base => "0x4200000000000000000000000000000000000006",
+            Chain::Polygon => "0x0d500b1d8e8ef31e21c99d1db9a6444d3adf1270",
+            Chain::Starknet => return None,
+        };
+        Some(Bytes::from(address))
+    }
+
+    /// Hashes contract code using this chain's code hashing scheme.
+    ///
+    /// All supported chains currently identify code by its Keccak-256 hash.
+    pub fn code_hash(&self, code: &[u8]) -> CodeHash {
+        match self {
+            Chain::Ethereum |
+            Chain::Starknet |
+            Chain::ZkSync |
+            Chain::Arbitrum |
+            Chain::Optimism |
+            Chain::Base |
+            Chain::Polygon => keccak256(code).into(),
+        }
+    }
+
+    /// Returns the hash of empty code on this chain.
+    ///
+    /// The value is computed once per hashing scheme and cached for the lifetime of the process.
+    pub fn empty_code_hash(&self) -> CodeHash {
+        static KECCAK256_EMPTY: OnceLock<CodeHash> = OnceLock::new();
+        match self {
+            Chain::Ethereum |
+            Chain::Starknet |
+            Chain::ZkSync |
+            Chain::Arbitrum |
+            Chain::Optimism |
+            Chain::Base |
+            Chain::Polygon => KECCAK256_EMPTY
+                .get_or_init(|| self.code_hash(&[]))
+                .clone(),
+        }
+    }
+}
+
+/// Account address formats used by the supported chains.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressFormat {
+    /// 20 byte EVM address.
+    Evm,
+    /// 32 byte StarkNet field element.
+    Felt,
+}
+
+impl AddressFormat {
+    /// Returns the address width in bytes.
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> usize {
+        match self {
+            AddressFormat::Evm => 20,
+            AddressFormat::Felt => 32,
+        }
+    }
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum AddressError {
+    #[error("Invalid hex address: {0}")]
+    InvalidHex(String),
+    #[error("Invalid address length for {chain}: expected {expected} bytes, got {actual}")]
+    InvalidLength { chain: Chain, expected: usize, actual: usize },
 }
 
 impl From<dto::Chain> for Chain {
@@ -68,6 +232,9 @@ impl From<dto::Chain> for Chain {
             dto::Chain::Starknet => Chain::Starknet,
             dto::Chain::ZkSync => Chain::ZkSync,
             dto::Chain::Arbitrum => Chain::Arbitrum,
+            dto::Chain::Optimism => Chain::Optimism,
+            dto::Chain::Base => Chain::Base,
+            dto::Chain::Polygon => Chain::Polygon,
         }
     }
 }
@@ -97,6 +264,8 @@ pub struct ExtractionState {
     pub attributes: serde_json::Value,
     pub cursor: Vec<u8>,
     pub block_hash: Bytes,
+    /// Number of the block at `block_hash`, 0 if unknown.
+    pub block_number: u64,
 }
 
 impl ExtractionState {
@@ -113,8 +282,40 @@ impl ExtractionState {
             attributes: attributes.unwrap_or_default(),
             cursor: cursor.to_vec(),
             block_hash,
+            block_number: 0,
         }
     }
+
+    pub fn with_block_number(mut self, block_number: u64) -> Self {
+        self.block_number = block_number;
+        self
+    }
+
+    /// Adopts the cursor, attributes and block of `other` if it is further advanced.
+    ///
+    /// The state only advances if `other` is at a strictly higher block, equal or lower blocks
+    /// leave it untouched. Returns whether the state advanced.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeltaError::IdMismatch` if `other` belongs to a different extractor or chain.
+    pub fn advance_to(&mut self, other: &ExtractionState) -> Result<bool, DeltaError> {
+        let self_id = (&self.name, self.chain);
+        let other_id = (&other.name, other.chain);
+        if self_id != other_id {
+            return Err(DeltaError::IdMismatch(format!("{:?}", self_id), format!("{:?}", other_id)));
+        }
+        if other.block_number <= self.block_number {
+            return Ok(false);
+        }
+        self.cursor.clone_from(&other.cursor);
+        self.attributes
+            .clone_from(&other.attributes);
+        self.block_hash
+            .clone_from(&other.block_hash);
+        self.block_number = other.block_number;
+        Ok(true)
+    }
 }
 
 // TODO: replace with types from dto on extractor
@@ -126,6 +327,9 @@ pub trait NormalisedMessage:
 
     fn drop_state(&self) -> Arc<dyn NormalisedMessage>;
 
+    /// Per extractor sequence number, strictly increasing across all emitted messages.
+    fn sequence(&self) -> u64;
+
     fn as_any(&self) -> &dyn std::any::Any;
 }
 
@@ -164,12 +368,17 @@ impl ProtocolType {
     }
 }
 
+/// The kind of change applied to an entity.
+///
+/// Defaults to `Unspecified` so that entities constructed without an explicit change type are
+/// rejected when changes are routed, instead of silently being treated as one of the others.
 #[derive(Debug, PartialEq, Default, Copy, Clone, Deserialize, Serialize)]
 pub enum ChangeType {
-    #[default]
     Update,
     Deletion,
     Creation,
+    #[default]
+    Unspecified,
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
@@ -221,4 +430,168 @@ impl From<&dto::PaginationParams> for PaginationParams {
 pub enum DeltaError {
     #[error("Id mismatch: {0} vs {1}")]
     IdMismatch(String, String),
+    #[error("Account {0} is created but already exists")]
+    DuplicateCreation(String),
+    #[error("Proxy implementation {0} is unknown")]
+    UnknownProxyImpl(String),
+    #[error("Account {0} has an inconsistent change: {1}")]
+    InconsistentChange(String, String),
+    #[error("Account {0} is updated but unknown")]
+    UnknownAccount(String),
+}
+
+#[cfg(test)]
+mod test {
+    use rstest::rstest;
+
+    use super::*;
+
+    fn extraction_state(name: &str, cursor: &str, block_number: u64) -> ExtractionState {
+        ExtractionState::new(
+            name.to_string(),
+            Chain::Ethereum,
+            Some(serde_json::json!({ "cursor": cursor })),
+            cursor.as_bytes(),
+            Bytes::from(block_number),
+        )
+        .with_block_number(block_number)
+    }
+
+    #[rstest]
+    #[case::ethereum(Chain::Ethereum, 1)]
+    #[case::zksync(Chain::ZkSync, 324)]
+    #[case::arbitrum(Chain::Arbitrum, 42161)]
+    #[case::optimism(Chain::Optimism, 10)]
+    #[case::base(Chain::Base, 8453)]
+    #[case::polygon(Chain::Polygon, 137)]
+    #[case::starknet(Chain::Starknet, 23448594291968334)]
+    fn test_chain_id(#[case] chain: Chain, #[case] id: u64) {
+        assert_eq!(chain.id(), id);
+        assert_eq!(Chain::from_id(id), Some(chain));
+    }
+
+    #[rstest]
+    #[case::ethereum(Chain::Ethereum, "ethereum")]
+    #[case::arbitrum(Chain::Arbitrum, "arbitrum")]
+    #[case::optimism(Chain::Optimism, "optimism")]
+    #[case::base(Chain::Base, "base")]
+    #[case::polygon(Chain::Polygon, "polygon")]
+    fn test_chain_name_round_trip(#[case] chain: Chain, #[case] name: &str) {
+        assert_eq!(chain.to_string(), name);
+        assert_eq!(Chain::from_str(name).unwrap(), chain);
+        assert_eq!(serde_json::to_string(&chain).unwrap(), format!("\"{name}\""));
+        assert_eq!(serde_json::from_str::<Chain>(&format!("\"{name}\"")).unwrap(), chain);
+    }
+
+    #[test]
+    fn test_chain_default() {
+        assert_eq!(Chain::default(), Chain::Ethereum);
+    }
+
+    #[test]
+    fn test_chain_from_unknown_id() {
+        assert_eq!(Chain::from_id(5), None);
+    }
+
+    #[rstest]
+    #[case::forward(11, true)]
+    #[case::equal(10, false)]
+    #[case::regress(9, false)]
+    fn test_advance_to(#[case] other_block: u64, #[case] exp_advanced: bool) {
+        let mut state = extraction_state("extractor", "cursor@10", 10);
+        let other = extraction_state("extractor", "cursor@other", other_block);
+        let expected = if exp_advanced { other.clone() } else { state.clone() };
+
+        let advanced = state.advance_to(&other).unwrap();
+
+        assert_eq!(advanced, exp_advanced);
+        assert_eq!(state, expected);
+    }
+
+    #[test]
+    fn test_advance_to_different_extractor() {
+        let mut state = extraction_state("extractor", "cursor@10", 10);
+        let other = extraction_state("other_extractor", "cursor@11", 11);
+
+        let res = state.advance_to(&other);
+
+        assert!(matches!(res, Err(DeltaError::IdMismatch(_, _))));
+        assert_eq!(state, extraction_state("extractor", "cursor@10", 10));
+    }
+
+    #[rstest]
+    #[case::ethereum(Chain::Ethereum, "0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2")]
+    #[case::zksync(Chain::ZkSync, "0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2")]
+    #[case::starknet(
+        Chain::Starknet,
+        "0x049d36570d4e46f48e99674bd3fcc84644ddd6b96f7c741b1562b82f9e004dc7"
+    )]
+    fn test_parse_address(#[case] chain: Chain, #[case] value: &str) {
+        let address = chain.parse_address(value).unwrap();
+
+        assert_eq!(address, Bytes::from(value));
+        assert_eq!(address.len(), chain.address_format().len());
+    }
+
+    #[test]
+    fn test_parse_address_pads_felt() {
+        let address = Chain::Starknet
+            .parse_address("0x49d36570d4e46f48e99674bd3fcc84644ddd6b96f7c741b1562b82f9e004dc7")
+            .unwrap();
+
+        assert_eq!(
+            address,
+            Bytes::from("0x049d36570d4e46f48e99674bd3fcc84644ddd6b96f7c741b1562b82f9e004dc7")
+        );
+    }
+
+    #[rstest]
+    #[case::felt_on_evm(
+        Chain::Ethereum,
+        "0x049d36570d4e46f48e99674bd3fcc84644ddd6b96f7c741b1562b82f9e004dc7",
+        32
+    )]
+    #[case::short_evm(Chain::Arbitrum, "0xc02aaa39b223fe8d0a0e5c4f27ead9083c756c", 19)]
+    #[case::too_long_felt(
+        Chain::Starknet,
+        "0x00049d36570d4e46f48e99674bd3fcc84644ddd6b96f7c741b1562b82f9e004dc7",
+        33
+    )]
+    fn test_parse_address_invalid_length(
+        #[case] chain: Chain,
+        #[case] value: &str,
+        #[case] actual: usize,
+    ) {
+        let res = chain.parse_address(value);
+
+        assert_eq!(
+            res,
+            Err(AddressError::InvalidLength {
+                chain,
+                expected: chain.address_format().len(),
+                actual
+            })
+        );
+    }
+
+    #[rstest]
+    #[case::ethereum(Chain::Ethereum)]
+    #[case::starknet(Chain::Starknet)]
+    #[case::zksync(Chain::ZkSync)]
+    #[case::arbitrum(Chain::Arbitrum)]
+    #[case::optimism(Chain::Optimism)]
+    #[case::base(Chain::Base)]
+    #[case::polygon(Chain::Polygon)]
+    fn test_empty_code_hash(#[case] chain: Chain) {
+        assert_eq!(chain.empty_code_hash(), chain.code_hash(&[]));
+        assert_eq!(chain.empty_code_hash(), chain.empty_code_hash());
+    }
+
+    #[test]
+    fn test_empty_code_hash_ethereum() {
+        assert_eq!(
+            Chain::Ethereum.empty_code_hash(),
+            Bytes::from("0xc5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470")
+        );
+    }
 }
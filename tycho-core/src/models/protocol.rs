@@ -1,15 +1,21 @@
 use crate::{
-    models::{Chain, ChangeType},
+    models::{AddressError, Chain, ChangeType},
     Bytes,
 };
 use chrono::NaiveDateTime;
+use num_bigint::BigUint;
+use primitive_types::U256;
 use serde::{Deserialize, Serialize};
-use std::collections::{hash_map::Entry, HashMap, HashSet};
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet},
+    str::FromStr,
+};
+use thiserror::Error;
 use tracing::warn;
 
 use super::{
-    blockchain::Transaction, Address, AttrStoreKey, Balance, ComponentId, DeltaError, StoreVal,
-    TxHash,
+    blockchain::{Block, BlockAggregatedChanges, Transaction},
+    Address, AttrStoreKey, Balance, ComponentId, DeltaError, StoreVal, TxHash,
 };
 
 /// `ProtocolComponent` provides detailed descriptions of a component of a protocol,
@@ -27,7 +33,12 @@ use super::{
 pub struct ProtocolComponent {
     pub id: ComponentId,
     pub protocol_system: String,
+    /// The primary protocol type of the component.
     pub protocol_type_name: String,
+    /// Further protocol types of components that play multiple roles, e.g. a pool that also
+    /// lends. Only the primary type is persisted.
+    #[serde(default)]
+    pub additional_protocol_types: Vec<String>,
     pub chain: Chain,
     pub tokens: Vec<Address>,
     pub contract_addresses: Vec<Address>,
@@ -55,6 +66,7 @@ impl ProtocolComponent {
             id: id.to_string(),
             protocol_system: protocol_system.to_string(),
             protocol_type_name: protocol_type_name.to_string(),
+            additional_protocol_types: Vec::new(),
             chain,
             tokens,
             contract_addresses,
@@ -64,6 +76,257 @@ impl ProtocolComponent {
             created_at,
         }
     }
+
+    /// Starts building a component from its identifying fields.
+    ///
+    /// The remaining fields can be set on the returned builder, see
+    /// [`ProtocolComponentBuilder::build`] for the validation applied.
+    pub fn builder(
+        id: &str,
+        protocol_system: &str,
+        protocol_type_name: &str,
+        chain: Chain,
+    ) -> ProtocolComponentBuilder {
+        ProtocolComponentBuilder::new(id, protocol_system, protocol_type_name, chain)
+    }
+
+    /// Returns all protocol types of this component, starting with the primary one.
+    pub fn protocol_type_names(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.protocol_type_name.as_str()).chain(
+            self.additional_protocol_types
+                .iter()
+                .map(String::as_str),
+        )
+    }
+
+    /// Returns whether the component declares `protocol_type_name` as one of its types.
+    pub fn has_protocol_type(&self, protocol_type_name: &str) -> bool {
+        self.protocol_type_names()
+            .any(|name| name == protocol_type_name)
+    }
+
+    /// Applies the static attributes of an update-type component onto this component.
+    ///
+    /// Only the attributes present in `update` are overwritten, all other fields of this
+    /// component, including its change type and creation data, are kept.
+    pub fn apply_static_attribute_update(&mut self, update: ProtocolComponent) {
+        self.static_attributes
+            .extend(update.static_attributes);
+    }
+
+    /// Returns the address stored in the `factory_address` static attribute.
+    ///
+    /// The attribute may either hold the raw 20 address bytes or a hex encoded address string.
+    /// Returns `None` if the attribute is absent or does not contain a valid address.
+    pub fn factory_address(&self) -> Option<Address> {
+        let value = self
+            .static_attributes
+            .get("factory_address")?;
+        if value.len() == 20 {
+            return Some(value.clone());
+        }
+        std::str::from_utf8(value)
+            .ok()
+            .and_then(|s| Bytes::from_str(s).ok())
+            .filter(|address| address.len() == 20)
+    }
+
+    /// Checks that every contract id has the address width expected on the component's chain.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AddressError::InvalidLength` for the first contract id whose width does not
+    /// match, e.g. a 32 byte felt on an EVM chain.
+    pub fn validate_chain_consistency(&self) -> Result<(), AddressError> {
+        let expected = self.chain.address_format().len();
+        match self
+            .contract_addresses
+            .iter()
+            .find(|address| address.len() != expected)
+        {
+            Some(address) => Err(AddressError::InvalidLength {
+                chain: self.chain,
+                expected,
+                actual: address.len(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Decodes the static attributes listed in `schema` to their declared types.
+    ///
+    /// Attributes missing from the component or from the schema are not included in the result.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AttributeDecodeError` naming the first attribute whose value can't be decoded
+    /// to its declared type.
+    pub fn typed_attributes(
+        &self,
+        schema: &AttributeSchema,
+    ) -> Result<HashMap<AttrStoreKey, AttributeValue>, AttributeDecodeError> {
+        schema
+            .iter()
+            .filter_map(|(key, attr_type)| {
+                let value = self.static_attributes.get(key)?;
+                Some(
+                    attr_type
+                        .decode(key, value, self.chain)
+                        .map(|decoded| (key.clone(), decoded)),
+                )
+            })
+            .collect()
+    }
+}
+
+/// The type a static attribute is decoded to, see [`ProtocolComponent::typed_attributes`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttributeType {
+    /// A big-endian unsigned integer of up to 32 bytes.
+    U256,
+    /// An address with the width of the component's chain.
+    Address,
+    /// An UTF-8 encoded string.
+    String,
+    /// A single byte, either 0 or 1.
+    Bool,
+}
+
+impl AttributeType {
+    fn decode(
+        &self,
+        key: &str,
+        value: &Bytes,
+        chain: Chain,
+    ) -> Result<AttributeValue, AttributeDecodeError> {
+        match self {
+            Self::U256 => decode_u256_be(key, value).map(AttributeValue::U256),
+            Self::Address => {
+                let expected = chain.address_format().len();
+                if value.len() != expected {
+                    return Err(AttributeDecodeError::InvalidAddress(
+                        key.to_string(),
+                        expected,
+                        value.len(),
+                    ));
+                }
+                Ok(AttributeValue::Address(value.clone()))
+            }
+            Self::String => String::from_utf8(value.to_vec())
+                .map(AttributeValue::String)
+                .map_err(|_| AttributeDecodeError::InvalidUtf8(key.to_string())),
+            Self::Bool => match value.as_ref() {
+                [0] => Ok(AttributeValue::Bool(false)),
+                [1] => Ok(AttributeValue::Bool(true)),
+                _ => Err(AttributeDecodeError::InvalidBool(key.to_string(), value.clone())),
+            },
+        }
+    }
+}
+
+/// Declared types of static attributes by attribute name.
+pub type AttributeSchema = HashMap<AttrStoreKey, AttributeType>;
+
+/// A decoded static attribute value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttributeValue {
+    U256(U256),
+    Address(Address),
+    String(String),
+    Bool(bool),
+}
+
+/// Helper struct to build a `ProtocolComponent`.
+#[derive(Debug, Clone)]
+pub struct ProtocolComponentBuilder {
+    component: ProtocolComponent,
+}
+
+impl ProtocolComponentBuilder {
+    pub fn new(id: &str, protocol_system: &str, protocol_type_name: &str, chain: Chain) -> Self {
+        Self {
+            component: ProtocolComponent {
+                id: id.to_string(),
+                protocol_system: protocol_system.to_string(),
+                protocol_type_name: protocol_type_name.to_string(),
+                chain,
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Sets the protocol types of the component in addition to its primary type
+    pub fn additional_protocol_types(mut self, protocol_types: Vec<String>) -> Self {
+        self.component.additional_protocol_types = protocol_types;
+        self
+    }
+
+    /// Sets the tokens tradable on the component
+    pub fn tokens(mut self, tokens: Vec<Address>) -> Self {
+        self.component.tokens = tokens;
+        self
+    }
+
+    /// Sets the contracts the component is made of
+    pub fn contract_addresses(mut self, contract_addresses: Vec<Address>) -> Self {
+        self.component.contract_addresses = contract_addresses;
+        self
+    }
+
+    /// Sets the static attributes of the component
+    pub fn static_attributes(mut self, static_attributes: HashMap<AttrStoreKey, StoreVal>) -> Self {
+        self.component.static_attributes = static_attributes;
+        self
+    }
+
+    /// Sets the change type of the component
+    pub fn change(mut self, change: ChangeType) -> Self {
+        self.component.change = change;
+        self
+    }
+
+    /// Sets the transaction that created the component
+    pub fn creation_tx(mut self, creation_tx: TxHash) -> Self {
+        self.component.creation_tx = creation_tx;
+        self
+    }
+
+    /// Sets the creation timestamp of the component
+    pub fn created_at(mut self, created_at: NaiveDateTime) -> Self {
+        self.component.created_at = created_at;
+        self
+    }
+
+    /// Builds the component.
+    ///
+    /// # Errors
+    /// Returns an error if the component does not have any tokens.
+    pub fn build(self) -> Result<ProtocolComponent, String> {
+        if self.component.tokens.is_empty() {
+            return Err(format!("ProtocolComponent {} has no tokens", self.component.id));
+        }
+        Ok(self.component)
+    }
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum AttributeDecodeError {
+    #[error("Attribute {0} is {1} bytes long, at most 32 bytes fit into a U256")]
+    TooLong(AttrStoreKey, usize),
+    #[error("Attribute {0} is not an address: expected {1} bytes, got {2}")]
+    InvalidAddress(AttrStoreKey, usize, usize),
+    #[error("Attribute {0} is not valid UTF-8")]
+    InvalidUtf8(AttrStoreKey),
+    #[error("Attribute {0} is not a bool: {1}")]
+    InvalidBool(AttrStoreKey, Bytes),
+}
+
+/// Decodes a big-endian value of up to 32 bytes, left padding shorter values.
+pub(crate) fn decode_u256_be(key: &str, value: &Bytes) -> Result<U256, AttributeDecodeError> {
+    if value.len() > 32 {
+        return Err(AttributeDecodeError::TooLong(key.to_string(), value.len()));
+    }
+    Ok(U256::from_big_endian(value))
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -83,6 +346,21 @@ impl ProtocolComponentState {
         Self { component_id: component_id.to_string(), attributes, balances }
     }
 
+    /// Decodes the attribute `key` as a big-endian `U256`.
+    ///
+    /// Values shorter than 32 bytes are left padded with zeros, so attributes of any width up to
+    /// 32 bytes are supported. Returns `Ok(None)` if the attribute is not present.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AttributeDecodeError` if the value is longer than 32 bytes.
+    pub fn get_u256_be(&self, key: &str) -> Result<Option<U256>, AttributeDecodeError> {
+        self.attributes
+            .get(key)
+            .map(|value| decode_u256_be(key, value))
+            .transpose()
+    }
+
     /// Applies state deltas to this state.
     ///
     /// This method assumes that the passed delta is "newer" than the current state.
@@ -138,6 +416,11 @@ impl ProtocolComponentStateDelta {
         Self { component_id: component_id.to_string(), updated_attributes, deleted_attributes }
     }
 
+    /// Returns true if this delta neither updates nor deletes any attribute.
+    pub fn is_empty(&self) -> bool {
+        self.updated_attributes.is_empty() && self.deleted_attributes.is_empty()
+    }
+
     /// Merges this update with another one.
     ///
     /// The method combines two `ProtocolComponentStateDelta` instances if they are for the same
@@ -146,6 +429,8 @@ impl ProtocolComponentStateDelta {
     /// NB: It is assumed that `other` is a more recent update than `self` is and the two are
     /// combined accordingly.
     ///
+    /// After merging, no attribute is both updated and deleted.
+    ///
     /// # Errors
     /// This method will return `CoreError::MergeError` if any of the above
     /// conditions is violated.
@@ -156,16 +441,22 @@ impl ProtocolComponentStateDelta {
                 self.component_id, other.component_id
             ));
         }
-        for attr in &other.deleted_attributes {
-            self.updated_attributes.remove(attr);
+        for attr in other.deleted_attributes {
+            self.updated_attributes.remove(&attr);
+            self.deleted_attributes.insert(attr);
         }
-        for attr in other.updated_attributes.keys() {
-            self.deleted_attributes.remove(attr);
+        // Updates are applied last, so they win if `other` both updates and deletes an attribute.
+        for (attr, value) in other.updated_attributes {
+            self.deleted_attributes.remove(&attr);
+            self.updated_attributes
+                .insert(attr, value);
         }
-        self.updated_attributes
-            .extend(other.updated_attributes);
-        self.deleted_attributes
-            .extend(other.deleted_attributes);
+        debug_assert!(
+            self.updated_attributes
+                .keys()
+                .all(|attr| !self.deleted_attributes.contains(attr)),
+            "Attribute both updated and deleted after merge"
+        );
         Ok(())
     }
 }
@@ -195,6 +486,36 @@ impl ComponentBalance {
             component_id: component_id.to_string(),
         }
     }
+
+    /// Returns whether the balance exceeds `total_supply` by more than the relative `tolerance`.
+    ///
+    /// A component can't hold more than the total supply of a token, so such a balance usually
+    /// indicates a decoding error, e.g. a wrong endianness.
+    pub fn exceeds_supply(&self, total_supply: U256, tolerance: f64) -> bool {
+        self.balance_float > total_supply.to_f64_lossy() * (1.0 + tolerance)
+    }
+
+    /// Formats the raw balance as a decimal string, scaled by the token's `decimals`.
+    ///
+    /// The value is rounded half up to `precision` fractional digits. The computation is done on
+    /// arbitrary precision integers, so no precision is lost for large balances.
+    pub fn formatted_balance(&self, decimals: u32, precision: u32) -> String {
+        let raw = BigUint::from_bytes_be(&self.balance);
+        let scaled = if precision < decimals {
+            let divisor = BigUint::from(10u32).pow(decimals - precision);
+            (raw + &divisor / 2u32) / divisor
+        } else {
+            raw * BigUint::from(10u32).pow(precision - decimals)
+        };
+
+        let precision = precision as usize;
+        let digits = format!("{scaled:0>width$}", width = precision + 1);
+        if precision == 0 {
+            return digits;
+        }
+        let (integer, fraction) = digits.split_at(digits.len() - precision);
+        format!("{integer}.{fraction}")
+    }
 }
 
 /// Updates grouped by their respective transaction.
@@ -288,6 +609,81 @@ impl ProtocolChangesWithTx {
     }
 }
 
+/// A single attribute value transition observed by [`ProtocolStateTracker`].
+///
+/// `old` is `None` if the attribute was not known before, `new` is `None` if the attribute was
+/// deleted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttributeChange {
+    pub block: Block,
+    pub component_id: ComponentId,
+    pub attribute: AttrStoreKey,
+    pub old: Option<StoreVal>,
+    pub new: Option<StoreVal>,
+}
+
+/// Tracks protocol component attributes over a sequence of aggregated block changes.
+///
+/// Fed with successive [`BlockAggregatedChanges`], it keeps the latest value of each
+/// `(component_id, attribute)` pair and reports only the attributes whose value actually changed.
+#[derive(Debug, Default, Clone)]
+pub struct ProtocolStateTracker {
+    values: HashMap<(ComponentId, AttrStoreKey), StoreVal>,
+}
+
+impl ProtocolStateTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Applies the state deltas of `changes` and returns the resulting attribute transitions.
+    ///
+    /// The returned changes are sorted by component id and attribute name.
+    pub fn update(&mut self, changes: &BlockAggregatedChanges) -> Vec<AttributeChange> {
+        let mut transitions = Vec::new();
+
+        for (component_id, delta) in changes.state_deltas.iter() {
+            for (attribute, value) in delta.updated_attributes.iter() {
+                let old = self
+                    .values
+                    .insert((component_id.clone(), attribute.clone()), value.clone());
+                if old.as_ref() != Some(value) {
+                    transitions.push((component_id, attribute, old, Some(value.clone())));
+                }
+            }
+            for attribute in delta.deleted_attributes.iter() {
+                if let Some(old) = self
+                    .values
+                    .remove(&(component_id.clone(), attribute.clone()))
+                {
+                    transitions.push((component_id, attribute, Some(old), None));
+                }
+            }
+        }
+
+        transitions.sort_unstable_by(|a, b| (a.0, a.1).cmp(&(b.0, b.1)));
+        transitions
+            .into_iter()
+            .map(|(component_id, attribute, old, new)| AttributeChange {
+                block: changes.block.clone(),
+                component_id: component_id.clone(),
+                attribute: attribute.clone(),
+                old,
+                new,
+            })
+            .collect()
+    }
+
+    /// Forgets all attributes of `component_id`, doing nothing if none are tracked.
+    ///
+    /// Should be called once a component is deleted, so its attributes are reported as new if
+    /// the component is created again.
+    pub fn on_component_deleted(&mut self, component_id: &str) {
+        self.values
+            .retain(|(id, _), _| id != component_id);
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -299,6 +695,134 @@ mod test {
     const HASH_256_0: &str = "0x0000000000000000000000000000000000000000000000000000000000000000";
     const HASH_256_1: &str = "0x0000000000000000000000000000000000000000000000000000000000000001";
 
+    #[rstest]
+    #[case::evm(Chain::Ethereum, Bytes::from(vec![1u8; 20]), Ok(()))]
+    #[case::felt(Chain::Starknet, Bytes::from(vec![1u8; 32]), Ok(()))]
+    #[case::felt_on_evm(
+        Chain::Ethereum,
+        Bytes::from(vec![1u8; 32]),
+        Err(AddressError::InvalidLength { chain: Chain::Ethereum, expected: 20, actual: 32 })
+    )]
+    fn test_validate_chain_consistency(
+        #[case] chain: Chain,
+        #[case] contract: Bytes,
+        #[case] expected: Result<(), AddressError>,
+    ) {
+        let component = ProtocolComponent::builder("pc_1", "ambient", "pool", chain)
+            .tokens(vec![Bytes::from(vec![2u8; 20])])
+            .contract_addresses(vec![contract])
+            .change(ChangeType::Creation)
+            .build()
+            .unwrap();
+
+        assert_eq!(component.validate_chain_consistency(), expected);
+    }
+
+    fn typed_component(attributes: Vec<(&str, Bytes)>) -> ProtocolComponent {
+        ProtocolComponent::builder("pc_1", "ambient", "pool", Chain::Ethereum)
+            .tokens(vec![Bytes::from(vec![2u8; 20])])
+            .static_attributes(
+                attributes
+                    .into_iter()
+                    .map(|(key, value)| (key.to_string(), value))
+                    .collect(),
+            )
+            .change(ChangeType::Creation)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_typed_attributes() {
+        let factory = Bytes::from(vec![1u8; 20]);
+        let component = typed_component(vec![
+            ("balance", Bytes::from(1000u64)),
+            ("factory_address", factory.clone()),
+            ("name", Bytes::from(b"pool".to_vec())),
+            ("stable", Bytes::from(vec![1u8])),
+            ("untyped", Bytes::from("0x01")),
+        ]);
+        let schema = AttributeSchema::from([
+            ("balance".to_string(), AttributeType::U256),
+            ("factory_address".to_string(), AttributeType::Address),
+            ("name".to_string(), AttributeType::String),
+            ("stable".to_string(), AttributeType::Bool),
+            ("missing".to_string(), AttributeType::Bool),
+        ]);
+
+        let res = component
+            .typed_attributes(&schema)
+            .unwrap();
+
+        assert_eq!(
+            res,
+            HashMap::from([
+                ("balance".to_string(), AttributeValue::U256(U256::from(1000u64))),
+                ("factory_address".to_string(), AttributeValue::Address(factory)),
+                ("name".to_string(), AttributeValue::String("pool".to_string())),
+                ("stable".to_string(), AttributeValue::Bool(true)),
+            ])
+        );
+    }
+
+    #[rstest]
+    #[case::long_u256(
+        AttributeType::U256,
+        Bytes::from(vec![1u8; 33]),
+        AttributeDecodeError::TooLong("attr".to_string(), 33)
+    )]
+    #[case::short_address(
+        AttributeType::Address,
+        Bytes::from(vec![1u8; 19]),
+        AttributeDecodeError::InvalidAddress("attr".to_string(), 20, 19)
+    )]
+    #[case::invalid_utf8(
+        AttributeType::String,
+        Bytes::from(vec![0xffu8]),
+        AttributeDecodeError::InvalidUtf8("attr".to_string())
+    )]
+    #[case::invalid_bool(
+        AttributeType::Bool,
+        Bytes::from(vec![2u8]),
+        AttributeDecodeError::InvalidBool("attr".to_string(), Bytes::from(vec![2u8]))
+    )]
+    fn test_typed_attributes_invalid(
+        #[case] attr_type: AttributeType,
+        #[case] value: Bytes,
+        #[case] expected: AttributeDecodeError,
+    ) {
+        let component = typed_component(vec![("attr", value)]);
+        let schema = AttributeSchema::from([("attr".to_string(), attr_type)]);
+
+        let res = component.typed_attributes(&schema);
+
+        assert_eq!(res, Err(expected));
+    }
+
+    #[rstest]
+    #[case::u64(Bytes::from(1000u64.to_be_bytes().to_vec()), Ok(Some(U256::from(1000u64))))]
+    #[case::address_width(
+        Bytes::from("0x00000000000000000000000000000000000003e8"),
+        Ok(Some(U256::from(1000u64)))
+    )]
+    #[case::too_long(
+        Bytes::from(vec![1u8; 33]),
+        Err(AttributeDecodeError::TooLong("reserve".to_string(), 33))
+    )]
+    fn test_get_u256_be(
+        #[case] value: Bytes,
+        #[case] expected: Result<Option<U256>, AttributeDecodeError>,
+    ) {
+        let state = ProtocolComponentState::new(
+            "pc_1",
+            HashMap::from([("reserve".to_string(), value)]),
+            HashMap::new(),
+        );
+
+        assert_eq!(state.get_u256_be("reserve"), expected);
+        assert_eq!(state.get_u256_be("missing"), Ok(None));
+    }
+
     fn create_state(id: String) -> ProtocolComponentStateDelta {
         let attributes1: HashMap<String, Bytes> = vec![
             ("reserve1".to_owned(), Bytes::from(1000u64).lpad(32, 0)),
@@ -314,6 +838,197 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_protocol_state_delta_is_empty() {
+        let empty = ProtocolComponentStateDelta::new("pool", HashMap::new(), HashSet::new());
+        let deletion = ProtocolComponentStateDelta::new(
+            "pool",
+            HashMap::new(),
+            HashSet::from(["reserve1".to_owned()]),
+        );
+
+        assert!(empty.is_empty());
+        assert!(!deletion.is_empty());
+        assert!(!create_state("pool".to_owned()).is_empty());
+    }
+
+    #[rstest]
+    #[case::raw_bytes(
+        Bytes::from("0x5c69bee701ef814a2b6a3edd4b1652cb9cc5aa6f"),
+        Some(Bytes::from("0x5c69bee701ef814a2b6a3edd4b1652cb9cc5aa6f"))
+    )]
+    #[case::hex_string(
+        Bytes::from(b"0x5c69bee701ef814a2b6a3edd4b1652cb9cc5aa6f".to_vec()),
+        Some(Bytes::from("0x5c69bee701ef814a2b6a3edd4b1652cb9cc5aa6f"))
+    )]
+    #[case::invalid(Bytes::from(b"0x0fwe0g240g20".to_vec()), None)]
+    fn test_protocol_component_factory_address(
+        #[case] attribute: Bytes,
+        #[case] expected: Option<Bytes>,
+    ) {
+        let component = ProtocolComponent {
+            static_attributes: HashMap::from([("factory_address".to_string(), attribute)]),
+            ..Default::default()
+        };
+
+        assert_eq!(component.factory_address(), expected);
+    }
+
+    #[test]
+    fn test_protocol_component_factory_address_missing() {
+        assert_eq!(ProtocolComponent::default().factory_address(), None);
+    }
+
+    #[test]
+    fn test_protocol_state_tracker() {
+        let block_changes =
+            |number: u64, deltas: Vec<ProtocolComponentStateDelta>| BlockAggregatedChanges {
+                block: Block { number, ..Default::default() },
+                state_deltas: deltas
+                    .into_iter()
+                    .map(|delta| (delta.component_id.clone(), delta))
+                    .collect(),
+                ..Default::default()
+            };
+        let mut tracker = ProtocolStateTracker::new();
+
+        let first = tracker.update(&block_changes(
+            1,
+            vec![ProtocolComponentStateDelta::new(
+                "pool",
+                HashMap::from([
+                    ("reserve".to_owned(), Bytes::from(100u64)),
+                    ("fee".to_owned(), Bytes::from(3u64)),
+                ]),
+                HashSet::new(),
+            )],
+        ));
+        let second = tracker.update(&block_changes(
+            2,
+            vec![ProtocolComponentStateDelta::new(
+                "pool",
+                HashMap::from([
+                    ("reserve".to_owned(), Bytes::from(200u64)),
+                    ("fee".to_owned(), Bytes::from(3u64)),
+                ]),
+                HashSet::new(),
+            )],
+        ));
+        let third = tracker.update(&block_changes(
+            3,
+            vec![ProtocolComponentStateDelta::new(
+                "pool",
+                HashMap::new(),
+                HashSet::from(["fee".to_owned(), "unknown".to_owned()]),
+            )],
+        ));
+
+        assert_eq!(
+            first
+                .iter()
+                .map(|c| (c.attribute.as_str(), c.old.clone(), c.new.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("fee", None, Some(Bytes::from(3u64))),
+                ("reserve", None, Some(Bytes::from(100u64))),
+            ]
+        );
+        assert_eq!(
+            second,
+            vec![AttributeChange {
+                block: Block { number: 2, ..Default::default() },
+                component_id: "pool".to_owned(),
+                attribute: "reserve".to_owned(),
+                old: Some(Bytes::from(100u64)),
+                new: Some(Bytes::from(200u64)),
+            }]
+        );
+        assert_eq!(
+            third,
+            vec![AttributeChange {
+                block: Block { number: 3, ..Default::default() },
+                component_id: "pool".to_owned(),
+                attribute: "fee".to_owned(),
+                old: Some(Bytes::from(3u64)),
+                new: None,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_protocol_state_tracker_on_component_deleted() {
+        let block_changes = |number: u64| BlockAggregatedChanges {
+            block: Block { number, ..Default::default() },
+            state_deltas: HashMap::from([(
+                "pool".to_owned(),
+                ProtocolComponentStateDelta::new(
+                    "pool",
+                    HashMap::from([("reserve".to_owned(), Bytes::from(100u64))]),
+                    HashSet::new(),
+                ),
+            )]),
+            ..Default::default()
+        };
+        let mut tracker = ProtocolStateTracker::new();
+        tracker.update(&block_changes(1));
+
+        tracker.on_component_deleted("pool");
+        tracker.on_component_deleted("pool");
+        let res = tracker.update(&block_changes(2));
+
+        assert_eq!(
+            res.iter()
+                .map(|c| (c.attribute.as_str(), c.old.clone(), c.new.clone()))
+                .collect::<Vec<_>>(),
+            vec![("reserve", None, Some(Bytes::from(100u64)))]
+        );
+    }
+
+    #[rstest]
+    #[case::eighteen_decimals(1_500_000_000_000_000_123u64, 18, 4, "1.5000")]
+    #[case::eighteen_decimals_rounded(999_960_000_000_000_000u64, 18, 4, "1.0000")]
+    #[case::six_decimals(1_234_567u64, 6, 2, "1.23")]
+    #[case::six_decimals_half_up(1_235_000u64, 6, 2, "1.24")]
+    #[case::six_decimals_padded(1_234_567u64, 6, 8, "1.23456700")]
+    #[case::sub_unit(42u64, 6, 6, "0.000042")]
+    #[case::no_fraction(2_500_000u64, 6, 0, "3")]
+    fn test_component_balance_formatted_balance(
+        #[case] raw: u64,
+        #[case] decimals: u32,
+        #[case] precision: u32,
+        #[case] expected: &str,
+    ) {
+        let balance = ComponentBalance::new(
+            Bytes::zero(20),
+            Bytes::from(raw.to_be_bytes().to_vec()),
+            raw as f64,
+            Bytes::zero(32),
+            "pool",
+        );
+
+        assert_eq!(balance.formatted_balance(decimals, precision), expected);
+    }
+
+    #[test]
+    fn test_protocol_component_builder() {
+        let token = Bytes::from("0x6B175474E89094C44Da98b954EedeAC495271d0F");
+        let component = ProtocolComponent::builder("pool", "ambient", "pool", Chain::Ethereum)
+            .tokens(vec![token.clone()])
+            .build()
+            .unwrap();
+
+        assert_eq!(component.id, "pool");
+        assert_eq!(component.tokens, vec![token]);
+        assert_eq!(component.change, ChangeType::default());
+    }
+
+    #[test]
+    fn test_protocol_component_builder_rejects_empty_tokens() {
+        let res = ProtocolComponent::builder("pool", "ambient", "pool", Chain::Ethereum).build();
+
+        assert_eq!(res, Err("ProtocolComponent pool has no tokens".to_string()));
+    }
+
     #[test]
     fn test_merge_protocol_state_updates() {
         let mut state_1 = create_state("State1".to_owned());
@@ -461,4 +1176,47 @@ mod test {
             )
         );
     }
+
+    #[test]
+    fn test_merge_protocol_state_delete_then_readd() {
+        let attr = "reserve".to_string();
+        let mut state = ProtocolComponentStateDelta::new(
+            "pool",
+            HashMap::from([(attr.clone(), Bytes::from(1u64))]),
+            HashSet::new(),
+        );
+        let merges = [
+            ProtocolComponentStateDelta::new("pool", HashMap::new(), HashSet::from([attr.clone()])),
+            ProtocolComponentStateDelta::new(
+                "pool",
+                HashMap::from([(attr.clone(), Bytes::from(2u64))]),
+                HashSet::new(),
+            ),
+            ProtocolComponentStateDelta::new(
+                "pool",
+                HashMap::from([(attr.clone(), Bytes::from(3u64))]),
+                HashSet::from([attr.clone()]),
+            ),
+        ];
+
+        for other in merges {
+            state.merge(other).unwrap();
+
+            assert!(
+                !(state
+                    .updated_attributes
+                    .contains_key(&attr) &&
+                    state.deleted_attributes.contains(&attr))
+            );
+        }
+
+        assert_eq!(
+            state,
+            ProtocolComponentStateDelta::new(
+                "pool",
+                HashMap::from([(attr, Bytes::from(3u64))]),
+                HashSet::new(),
+            )
+        );
+    }
 }
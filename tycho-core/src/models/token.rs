@@ -11,10 +11,13 @@ pub type TransferCost = u64;
 /// Tax related to a token transfer. Should be given in Basis Points (1/100th of a percent)
 pub type TransferTax = u64;
 
-#[derive(PartialEq, Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct CurrencyToken {
     pub address: Bytes,
     pub symbol: String,
+    /// Human-readable name, e.g. "Wrapped Ether". Falls back to the symbol if unavailable.
+    #[serde(default)]
+    pub name: String,
     pub decimals: u32,
     pub tax: TransferTax,
     pub gas: Vec<Option<TransferCost>>,
@@ -23,10 +26,41 @@ pub struct CurrencyToken {
     ///  - 100: Normal token
     ///  - 75: Rebase token
     ///  - 50: Fee token
+    ///  - 30: Token without decimals, e.g. NFT-like or point tokens
     ///  - 10: Token analysis failed at creation
     ///  - 9-5: Token analysis failed on cronjob (after creation).
-    ///  - 0: Failed to extract decimals onchain
+    ///  - 0: Failed to extract decimals onchain or token is not transferable
     pub quality: u32,
+    /// Whether this is a plain token or a vault share token.
+    #[serde(default)]
+    pub kind: TokenKind,
+    /// The asset backing a vault token, `None` for standard tokens.
+    #[serde(default)]
+    pub underlying_asset: Option<Address>,
+    /// Why the quality is below 100, e.g. "Fee token". `None` for normal tokens.
+    ///
+    /// Informational only, it's ignored when comparing tokens.
+    #[serde(default)]
+    pub quality_reason: Option<String>,
+    /// Whether balances change without transfers, e.g. stETH or AMPL.
+    #[serde(default)]
+    pub rebasing: bool,
+}
+
+impl PartialEq for CurrencyToken {
+    fn eq(&self, other: &Self) -> bool {
+        self.address == other.address &&
+            self.symbol == other.symbol &&
+            self.name == other.name &&
+            self.decimals == other.decimals &&
+            self.tax == other.tax &&
+            self.gas == other.gas &&
+            self.chain == other.chain &&
+            self.quality == other.quality &&
+            self.kind == other.kind &&
+            self.underlying_asset == other.underlying_asset &&
+            self.rebasing == other.rebasing
+    }
 }
 
 impl CurrencyToken {
@@ -42,20 +76,38 @@ impl CurrencyToken {
         Self {
             address: address.clone(),
             symbol: symbol.to_string(),
+            name: symbol.to_string(),
             decimals,
             tax,
             gas: gas.to_owned(),
             chain,
             quality,
+            kind: TokenKind::Standard,
+            underlying_asset: None,
+            quality_reason: None,
+            rebasing: false,
         }
     }
 }
 
+/// The kind of a token.
+///
+/// * `Standard`: A plain ERC20 token.
+/// * `Vault`: An ERC-4626 vault share token, its underlying asset is stored separately.
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default, Deserialize, Serialize)]
+pub enum TokenKind {
+    #[default]
+    Standard,
+    Vault,
+}
+
 /// Represents the quality of a token.
 ///
 /// * `Good`: Indicates that the token has successfully passed the analysis process.
 /// * `Bad`: Indicates that the token has failed the analysis process. In this case, a detailed
 ///   reason for the failure is provided.
+/// * `NonTransferable`: Indicates that transfers of the token revert even for a funded holder, e.g.
+///   because the token is paused or soulbound. A detailed reason is provided.
 ///
 /// Note: Transfer taxes do not impact the token's quality.
 /// Even if a token has transfer taxes, as long as it successfully passes the analysis,
@@ -64,6 +116,7 @@ impl CurrencyToken {
 pub enum TokenQuality {
     Good,
     Bad { reason: String },
+    NonTransferable { reason: String },
 }
 
 impl TokenQuality {
@@ -74,6 +127,61 @@ impl TokenQuality {
     pub fn bad(reason: impl ToString) -> Self {
         Self::Bad { reason: reason.to_string() }
     }
+
+    pub fn non_transferable(reason: impl ToString) -> Self {
+        Self::NonTransferable { reason: reason.to_string() }
+    }
+
+    /// Returns why the token failed the analysis, `None` if it's good.
+    pub fn reason(&self) -> Option<&str> {
+        match self {
+            Self::Good => None,
+            Self::Bad { reason } | Self::NonTransferable { reason } => Some(reason),
+        }
+    }
+}
+
+/// Outcome of analyzing a token's transfer behaviour.
+///
+/// Measurements are `None` if the analysis stopped before taking them, e.g. because a transfer
+/// reverted.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct DetectionResult {
+    pub quality: TokenQuality,
+    /// Average cost of a transfer.
+    pub transfer_gas: Option<TransferCost>,
+    /// Cost of approving the maximum amount.
+    pub approve_gas: Option<TransferCost>,
+    /// The higher of the taxes applied when transferring in and out of a contract.
+    pub tax: Option<TransferTax>,
+    /// Whether balances change without transfers, e.g. because the token rebases.
+    pub rebasing: bool,
+}
+
+impl DetectionResult {
+    pub fn new(quality: TokenQuality) -> Self {
+        Self { quality, transfer_gas: None, approve_gas: None, tax: None, rebasing: false }
+    }
+
+    pub fn with_transfer_gas(mut self, transfer_gas: Option<TransferCost>) -> Self {
+        self.transfer_gas = transfer_gas;
+        self
+    }
+
+    pub fn with_approve_gas(mut self, approve_gas: Option<TransferCost>) -> Self {
+        self.approve_gas = approve_gas;
+        self
+    }
+
+    pub fn with_tax(mut self, tax: Option<TransferTax>) -> Self {
+        self.tax = tax;
+        self
+    }
+
+    pub fn with_rebasing(mut self, rebasing: bool) -> Self {
+        self.rebasing = rebasing;
+        self
+    }
 }
 
 /// A store for tracking token owners and their balances.
@@ -7,7 +7,7 @@ use crate::{
     models::{
         blockchain::{Block, BlockTag},
         contract::AccountDelta,
-        token::{CurrencyToken, TokenQuality, TransferCost, TransferTax},
+        token::{CurrencyToken, DetectionResult},
         Address, Balance,
     },
     Bytes,
@@ -36,17 +36,11 @@ pub trait TokenAnalyzer: Send + Sync {
     /// * `block` - The block tag at which the analysis should be performed.
     ///
     /// # Returns
-    /// A result containing:
-    /// * `TokenQuality` - The quality assessment of the token (either `Good` or `Bad`).
-    /// * `Option<TransferCost>` - The average cost per transfer, if available.
-    /// * `Option<TransferTax>` - The transfer tax, if applicable.
+    /// A `DetectionResult` holding the quality assessment of the token (`Good`, `Bad` or
+    /// `NonTransferable`) and the transfer costs and tax measured during the analysis.
     ///
     /// On failure, returns `Self::Error`.
-    async fn analyze(
-        &self,
-        token: Bytes,
-        block: BlockTag,
-    ) -> Result<(TokenQuality, Option<TransferCost>, Option<TransferTax>), Self::Error>;
+    async fn analyze(&self, token: Bytes, block: BlockTag) -> Result<DetectionResult, Self::Error>;
 }
 
 /// Trait for finding an address that owns a specific token. This is useful for detecting
@@ -36,6 +36,9 @@ async fn main() -> Result<(), ()> {
         web3: w3,
         finder: Arc::new(tf),
         settlement_contract: H160::from_str("0xc9f2e6ea1637E499406986ac50ddC92401ce1f58").unwrap(),
+        rate_limiter: None,
+        pinned_detections: Default::default(),
+        rebase_check: None,
     };
 
     let quality = trace_call
@@ -65,6 +65,7 @@ impl AccountExtractor for EVMAccountExtractor {
                     slots,
                     balance: balance.map(BytesCodec::to_bytes),
                     code,
+                    proxy_impl: None,
                     change: ChangeType::Creation,
                 },
             );
@@ -134,6 +135,7 @@ impl EVMAccountExtractor {
             chain: Chain::Ethereum,
             ts: NaiveDateTime::from_timestamp_opt(block.timestamp.as_u64() as i64, 0)
                 .expect("Failed to convert timestamp"),
+            base_fee_per_gas: block.base_fee_per_gas,
         })
     }
 }
@@ -5,6 +5,8 @@ pub mod token_analyzer;
 #[cfg(feature = "onchain_data")]
 pub mod token_pre_processor;
 
+pub mod rate_limiter;
+
 use ethers::{
     providers::ProviderError,
     types::{H160, H256, U256},
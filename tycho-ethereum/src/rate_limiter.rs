@@ -0,0 +1,157 @@
+use std::{
+    num::NonZeroU32,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use tokio::time::{sleep, Instant};
+
+/// A token bucket rate limiter shared across RPC call sites.
+///
+/// The bucket holds up to `requests_per_second` permits and refills continuously at the same
+/// rate, so short bursts up to the configured rate are allowed while the sustained rate is capped.
+/// Clones share the same bucket, which makes it possible to enforce a single limit across
+/// concurrent callers.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    bucket: Arc<Mutex<Bucket>>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    capacity: f64,
+    permits: f64,
+    refill_per_sec: f64,
+    last_refill: Instant,
+}
+
+impl Bucket {
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now
+            .duration_since(self.last_refill)
+            .as_secs_f64();
+        self.permits = (self.permits + elapsed * self.refill_per_sec).min(self.capacity);
+        self.last_refill = now;
+    }
+}
+
+impl RateLimiter {
+    pub fn new(requests_per_second: NonZeroU32) -> Self {
+        let rate = f64::from(requests_per_second.get());
+        Self {
+            bucket: Arc::new(Mutex::new(Bucket {
+                capacity: rate,
+                permits: rate,
+                refill_per_sec: rate,
+                last_refill: Instant::now(),
+            })),
+        }
+    }
+
+    /// Waits until a permit is available and consumes it.
+    ///
+    /// The permit is reserved right away, possibly before it is refilled, so callers are served
+    /// in the order they called this and the bucket is not locked while waiting.
+    pub async fn acquire(&self) {
+        let wait = {
+            let mut bucket = self
+                .bucket
+                .lock()
+                .expect("rate limiter lock poisoned");
+            bucket.refill();
+            bucket.permits -= 1.0;
+            (bucket.permits < 0.0)
+                .then(|| Duration::from_secs_f64(-bucket.permits / bucket.refill_per_sec))
+        };
+        if let Some(wait) = wait {
+            let reservation = Reservation { bucket: &self.bucket };
+            sleep(wait).await;
+            // The wait completed, so the reserved permit is consumed.
+            std::mem::forget(reservation);
+        }
+    }
+}
+
+/// A permit reserved ahead of its refill.
+///
+/// Dropping it gives the permit back to the bucket, so callers that are cancelled or time out
+/// while waiting don't lower the throughput available to everyone else.
+struct Reservation<'a> {
+    bucket: &'a Mutex<Bucket>,
+}
+
+impl Drop for Reservation<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut bucket) = self.bucket.lock() {
+            bucket.refill();
+            bucket.permits = (bucket.permits + 1.0).min(bucket.capacity);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_acquire_allows_initial_burst() {
+        let limiter = RateLimiter::new(NonZeroU32::new(5).unwrap());
+        let start = Instant::now();
+
+        for _ in 0..5 {
+            limiter.acquire().await;
+        }
+
+        assert_eq!(start.elapsed(), Duration::ZERO);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_acquire_paces_calls_below_rate() {
+        let limiter = RateLimiter::new(NonZeroU32::new(2).unwrap());
+        let start = Instant::now();
+
+        let handles: Vec<_> = (0..6)
+            .map(|_| {
+                let limiter = limiter.clone();
+                tokio::spawn(async move { limiter.acquire().await })
+            })
+            .collect();
+        for handle in handles {
+            handle.await.unwrap();
+        }
+
+        // 2 permits are available immediately, the remaining 4 are refilled at 2 per second.
+        assert!(start.elapsed() >= Duration::from_secs(2));
+        assert!(start.elapsed() < Duration::from_millis(2100));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_acquire_does_not_block_while_waiting() {
+        let limiter = RateLimiter::new(NonZeroU32::new(1).unwrap());
+        limiter.acquire().await;
+        let waiting = tokio::spawn({
+            let limiter = limiter.clone();
+            async move { limiter.acquire().await }
+        });
+        tokio::task::yield_now().await;
+
+        // The waiting caller holds a reservation, not the lock, so the bucket stays accessible.
+        assert!(limiter.bucket.try_lock().is_ok());
+        waiting.await.unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_cancelled_acquire_returns_permit() {
+        let limiter = RateLimiter::new(NonZeroU32::new(1).unwrap());
+        let start = Instant::now();
+        limiter.acquire().await;
+
+        let cancelled = tokio::time::timeout(Duration::from_millis(100), limiter.acquire()).await;
+        assert!(cancelled.is_err());
+        limiter.acquire().await;
+
+        // Only the next refill is waited for, not the one reserved by the cancelled caller.
+        assert_eq!(start.elapsed(), Duration::from_secs(1));
+    }
+}
@@ -1,38 +1,212 @@
 use anyhow::{bail, ensure, Context, Result};
 use contracts::ERC20;
 use ethcontract::{dyns::DynTransport, transaction::TransactionBuilder, PrivateKey};
-use ethers::types::{H160, U256};
+use ethers::types::{H160, H256, U256};
 use ethrpc::{http::HttpTransport, Web3, Web3Transport};
 use reqwest::Client;
-use std::{cmp, str::FromStr, sync::Arc};
+use std::{
+    cmp,
+    collections::{HashMap, VecDeque},
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
 use url::Url;
 use web3::{
     signing::keccak256,
-    types::{BlockNumber, BlockTrace, CallRequest, Res},
+    types::{BlockId, BlockNumber, BlockTrace, CallRequest, FilterBuilder, Res},
 };
 
 use tycho_core::{
     models::{
         blockchain::BlockTag,
-        token::{TokenQuality, TransferCost, TransferTax},
+        token::{DetectionResult, TokenQuality, TransferCost, TransferTax},
     },
     traits::{TokenAnalyzer, TokenOwnerFinding},
     Bytes,
 };
 
-use crate::{token_analyzer::trace_many, BlockTagWrapper, BytesCodec};
+use crate::{
+    rate_limiter::RateLimiter, token_analyzer::trace_many, token_pre_processor::retry,
+    BlockTagWrapper, BytesCodec,
+};
 
 /// Detects whether a token is "bad" (works in unexpected ways that are
 /// problematic for solving) by simulating several transfers of a token. To find
 /// an initial address to transfer from we use the amm pair providers.
 /// Tokens are bad if:
 /// - we cannot find an amm pool of the token to one of the base tokens
-/// - transfer into the settlement contract or back out fails
 /// - a transfer loses total balance
+///
+/// Tokens are non-transferable if the transfer into the settlement contract or back out reverts,
+/// even though the sender holds enough balance, e.g. because the token is paused.
+///
+/// If a [`RebaseCheck`] is configured, tokens are rebasing if the balance of the token owner
+/// changed over its window by more than its transfers account for, see
+/// [`TraceCallDetector::is_rebasing`].
 pub struct TraceCallDetector {
     pub web3: Web3,
     pub finder: Arc<dyn TokenOwnerFinding>,
     pub settlement_contract: H160,
+    /// Optional limiter awaited before each RPC call.
+    pub rate_limiter: Option<RateLimiter>,
+    /// Results of detections pinned to a block, may be shared by several detectors.
+    pub pinned_detections: PinnedDetections,
+    /// Parameters of the rebase check, tokens aren't checked for rebasing if unset.
+    pub rebase_check: Option<RebaseCheck>,
+}
+
+/// Error of a token detection.
+#[derive(Debug, thiserror::Error)]
+pub enum DetectionError {
+    /// Finding a holder of the token failed.
+    #[error("Failed to find token owner: {0}")]
+    Owner(String),
+    /// A call to the node failed.
+    #[error("RPC call failed: {0:#}")]
+    Rpc(anyhow::Error),
+    /// The node's response couldn't be interpreted, e.g. because a trace is missing.
+    #[error("Unexpected response: {0:#}")]
+    Response(anyhow::Error),
+}
+
+impl DetectionError {
+    /// Returns whether the detection may succeed if retried.
+    ///
+    /// Failed RPC calls are transient unless the node reported a revert, see
+    /// [`retry::is_transient_web3`]. Responses that couldn't be interpreted are final.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            DetectionError::Owner(_) => true,
+            DetectionError::Rpc(err) => err
+                .downcast_ref::<web3::Error>()
+                .is_some_and(retry::is_transient_web3),
+            DetectionError::Response(_) => false,
+        }
+    }
+}
+
+/// Number of pinned detection results kept by default.
+const DEFAULT_PINNED_DETECTIONS_CAPACITY: usize = 10_000;
+
+/// Results of detections pinned to a block, keyed by token and block number.
+///
+/// Holds at most `capacity` results, evicting the oldest result first. Clones share the same
+/// entries.
+#[derive(Debug, Clone)]
+pub struct PinnedDetections {
+    inner: Arc<Mutex<PinnedDetectionsInner>>,
+}
+
+#[derive(Debug)]
+struct PinnedDetectionsInner {
+    capacity: usize,
+    results: HashMap<(H160, u64), DetectionResult>,
+    insertion_order: VecDeque<(H160, u64)>,
+}
+
+impl Default for PinnedDetections {
+    fn default() -> Self {
+        Self::new(DEFAULT_PINNED_DETECTIONS_CAPACITY)
+    }
+}
+
+impl PinnedDetections {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(PinnedDetectionsInner {
+                capacity,
+                results: HashMap::new(),
+                insertion_order: VecDeque::new(),
+            })),
+        }
+    }
+
+    /// Returns the result of the detection of `token` at `block`, if cached.
+    pub fn get(&self, token: H160, block: u64) -> Option<DetectionResult> {
+        self.inner
+            .lock()
+            .expect("detection cache lock poisoned")
+            .results
+            .get(&(token, block))
+            .cloned()
+    }
+
+    /// Caches the result of the detection of `token` at `block`, evicting the oldest result if
+    /// the cache is full.
+    pub fn insert(&self, token: H160, block: u64, detection: DetectionResult) {
+        let mut inner = self
+            .inner
+            .lock()
+            .expect("detection cache lock poisoned");
+        if inner.capacity == 0 {
+            return;
+        }
+        if inner
+            .results
+            .insert((token, block), detection)
+            .is_some()
+        {
+            return;
+        }
+        inner
+            .insertion_order
+            .push_back((token, block));
+        if inner.insertion_order.len() > inner.capacity {
+            if let Some(oldest) = inner.insertion_order.pop_front() {
+                inner.results.remove(&oldest);
+            }
+        }
+    }
+}
+
+/// Arbitrary amount that is large enough that small relative fees should be visible.
+const MIN_AMOUNT: u64 = 100_000;
+
+/// Number of whole token units transferred for tokens with too few decimals to reach
+/// [`MIN_AMOUNT`].
+const MIN_WHOLE_UNITS: u64 = 10;
+
+/// Number of blocks between the two balance reads of the rebase check, about a day on Ethereum.
+/// Tokens like stETH rebase once a day.
+const DEFAULT_REBASE_WINDOW: u64 = 7_200;
+
+/// Balance change not accounted for by transfers, in millionths of the balance, that is
+/// tolerated by default. Absorbs the rounding of share based tokens like stETH or aTokens.
+const DEFAULT_REBASE_TOLERANCE_PPM: u64 = 1_000;
+
+/// Parameters of the check whether a token rebases, see [`TraceCallDetector::is_rebasing`].
+///
+/// The check reads historical balances, so it requires an archive node, and queries the
+/// `Transfer` logs over the whole window, which some providers limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RebaseCheck {
+    /// Number of blocks between the two balance reads.
+    pub window: u64,
+    /// Balance change not accounted for by transfers, in millionths of the balance, that is
+    /// tolerated before a token is considered rebasing.
+    pub tolerance_ppm: u64,
+}
+
+impl Default for RebaseCheck {
+    fn default() -> Self {
+        Self { window: DEFAULT_REBASE_WINDOW, tolerance_ppm: DEFAULT_REBASE_TOLERANCE_PPM }
+    }
+}
+
+/// Returns the minimum balance a token owner needs to fund the detection transfers.
+///
+/// Tokens with few or no decimals, e.g. NFT-like or point tokens, are rarely held in amounts of
+/// [`MIN_AMOUNT`] raw units, so they are funded with a handful of whole units instead. The
+/// amount is never zero and falls back to [`MIN_AMOUNT`] if the decimals are unknown.
+pub fn min_funding_amount(decimals: Option<u8>) -> U256 {
+    let min_amount = U256::from(MIN_AMOUNT);
+    match decimals {
+        Some(decimals) => U256::from(10)
+            .checked_pow(decimals.into())
+            .and_then(|unit| unit.checked_mul(MIN_WHOLE_UNITS.into()))
+            .map_or(min_amount, |amount| cmp::min(amount, min_amount)),
+        None => min_amount,
+    }
 }
 
 #[async_trait::async_trait]
@@ -43,18 +217,13 @@ impl TokenAnalyzer for TraceCallDetector {
         &self,
         token: Bytes,
         block: BlockTag,
-    ) -> std::result::Result<(TokenQuality, Option<TransferCost>, Option<TransferTax>), String>
-    {
-        let (quality, transfer_cost, tax) = self
-            .detect_impl(H160::from_bytes(&token), BlockTagWrapper(block).into())
+    ) -> std::result::Result<DetectionResult, String> {
+        let detection = self
+            .detect(H160::from_bytes(&token), block, None)
             .await
             .map_err(|e| e.to_string())?;
-        tracing::debug!(?token, ?quality, "determined token quality");
-        Ok((
-            quality,
-            transfer_cost.map(|cost| cost.try_into().unwrap_or(8_000_000)),
-            tax.map(|cost| cost.try_into().unwrap_or(10_000)),
-        ))
+        tracing::debug!(?token, quality = ?detection.quality, "determined token quality");
+        Ok(detection)
     }
 }
 
@@ -75,21 +244,71 @@ impl TraceCallDetector {
             // middle contract used to check for fees, set to cowswap settlement
             settlement_contract: H160::from_str("0xc9f2e6ea1637E499406986ac50ddC92401ce1f58")
                 .unwrap(),
+            rate_limiter: None,
+            pinned_detections: PinnedDetections::default(),
+            rebase_check: None,
         }
     }
+
+    pub fn with_rate_limiter(mut self, rate_limiter: RateLimiter) -> Self {
+        self.rate_limiter = Some(rate_limiter);
+        self
+    }
+
+    /// Checks tokens for rebasing, disabled by default.
+    pub fn with_rebase_check(mut self, rebase_check: RebaseCheck) -> Self {
+        self.rebase_check = Some(rebase_check);
+        self
+    }
+
+    async fn wait_for_rate_limit(&self) {
+        if let Some(limiter) = &self.rate_limiter {
+            limiter.acquire().await;
+        }
+    }
+
+    /// Detects the quality of `token` at `block`.
+    ///
+    /// Detections at a block tag like latest drift as the chain state advances. Detections at a
+    /// block number are pinned to that block and cached by `(token, block)`, making repeated
+    /// runs, e.g. backfills, reproducible. Failed detections are not cached.
+    ///
+    /// `decimals` of the token, if known, size the amount transferred during detection, see
+    /// [`min_funding_amount`].
+    pub async fn detect(
+        &self,
+        token: H160,
+        block: BlockTag,
+        decimals: Option<u8>,
+    ) -> Result<DetectionResult, DetectionError> {
+        let BlockTag::Number(block) = block else {
+            return self
+                .detect_impl(token, BlockTagWrapper(block).into(), decimals)
+                .await;
+        };
+        if let Some(detection) = self.pinned_detections.get(token, block) {
+            return Ok(detection);
+        }
+        let detection = self
+            .detect_impl(token, BlockNumber::Number(block.into()), decimals)
+            .await?;
+        self.pinned_detections
+            .insert(token, block, detection.clone());
+        Ok(detection)
+    }
+
     pub async fn detect_impl(
         &self,
         token: H160,
         block: BlockNumber,
-    ) -> Result<(TokenQuality, Option<U256>, Option<U256>), String> {
-        // Arbitrary amount that is large enough that small relative fees should be
-        // visible.
-        const MIN_AMOUNT: u64 = 100_000;
+        decimals: Option<u8>,
+    ) -> Result<DetectionResult, DetectionError> {
+        let min_amount = min_funding_amount(decimals);
         let (take_from, amount) = match self
             .finder
-            .find_owner(token.to_bytes(), MIN_AMOUNT.into())
+            .find_owner(token.to_bytes(), min_amount.to_bytes())
             .await
-            .map_err(|e| e.to_string())?
+            .map_err(DetectionError::Owner)?
         {
             Some((address, balance)) => {
                 // Don't use the full balance, but instead a portion of it. This
@@ -100,20 +319,16 @@ impl TraceCallDetector {
                 //   the past
                 // - New block observed - the trace_callMany is executed on a block that came in
                 //   since we read the balance
-                let amount = cmp::max(U256::from_bytes(&balance) / 2, MIN_AMOUNT.into());
+                let amount = cmp::max(U256::from_bytes(&balance) / 2, min_amount);
 
                 tracing::debug!(?token, ?address, ?amount, "found owner");
                 (H160::from_bytes(&address), amount)
             }
             None => {
-                return Ok((
-                    TokenQuality::bad(format!(
-                        "Could not find on chain source of the token with at least {MIN_AMOUNT} \
+                return Ok(DetectionResult::new(TokenQuality::bad(format!(
+                    "Could not find on chain source of the token with at least {min_amount} \
                      balance.",
-                    )),
-                    None,
-                    None,
-                ))
+                ))))
             }
         };
 
@@ -124,9 +339,10 @@ impl TraceCallDetector {
         // yet (implicitly 0) causes an allocation.
         let request =
             self.create_trace_request(token, amount, take_from, TraceRequestType::SimpleTransfer);
+        self.wait_for_rate_limit().await;
         let traces = trace_many::trace_many(request, &self.web3, block)
             .await
-            .map_err(|e| e.to_string())?;
+            .map_err(DetectionError::Rpc)?;
 
         let message = "\
         Failed to decode the token's balanceOf response because it did not \
@@ -138,7 +354,7 @@ impl TraceCallDetector {
         let bad = TokenQuality::Bad { reason: message.to_string() };
         let middle_balance = match decode_u256(&traces[2]) {
             Some(balance) => balance,
-            None => return Ok((bad, None, None)),
+            None => return Ok(DetectionResult::new(bad)),
         };
 
         let request = self.create_trace_request(
@@ -147,10 +363,116 @@ impl TraceCallDetector {
             take_from,
             TraceRequestType::DoubleTransfer(middle_balance),
         );
+        self.wait_for_rate_limit().await;
         let traces = trace_many::trace_many(request, &self.web3, block)
             .await
-            .map_err(|e| e.to_string())?;
-        Self::handle_response(&traces, amount, middle_balance, take_from).map_err(|e| e.to_string())
+            .map_err(DetectionError::Rpc)?;
+        let detection = Self::handle_response(&traces, amount, middle_balance, take_from)
+            .map_err(DetectionError::Response)?;
+
+        let Some(rebase_check) = self.rebase_check else {
+            return Ok(detection);
+        };
+        // The check is best effort: if it can't be completed, e.g. because the provider rejects
+        // the log range or isn't an archive node, the outcome is unknown and the token is
+        // treated as non-rebasing instead of failing the detection.
+        let rebasing = self
+            .is_rebasing(token, take_from, block, rebase_check)
+            .await
+            .unwrap_or_else(|err| {
+                tracing::debug!(
+                    ?token,
+                    ?err,
+                    "rebase check inconclusive, assuming a non-rebasing token"
+                );
+                false
+            });
+        Ok(detection.with_rebasing(rebasing))
+    }
+
+    /// Returns whether the balance of `holder` changed without transfers, i.e. `token` rebases.
+    ///
+    /// Reads the holder's balance at `block` and `check.window` blocks earlier and replays the
+    /// holder's `Transfer` events in between. Rebasing tokens like stETH, AMPL or aTokens end up
+    /// with a balance that differs from the replayed one by more than `check.tolerance_ppm`.
+    async fn is_rebasing(
+        &self,
+        token: H160,
+        holder: H160,
+        block: BlockNumber,
+        check: RebaseCheck,
+    ) -> Result<bool> {
+        let to_block = match block {
+            BlockNumber::Number(number) => number.as_u64(),
+            _ => {
+                self.wait_for_rate_limit().await;
+                self.web3
+                    .eth()
+                    .block_number()
+                    .await?
+                    .as_u64()
+            }
+        };
+        let from_block = to_block.saturating_sub(check.window);
+
+        let instance = ERC20::at(&self.web3, token);
+        let mut balances = Vec::with_capacity(2);
+        for number in [from_block, to_block] {
+            self.wait_for_rate_limit().await;
+            balances.push(
+                instance
+                    .balance_of(holder)
+                    .block(BlockId::Number(number.into()))
+                    .call()
+                    .await?,
+            );
+        }
+        let received = self
+            .transferred(token, None, Some(holder), from_block, to_block)
+            .await?;
+        let sent = self
+            .transferred(token, Some(holder), None, from_block, to_block)
+            .await?;
+
+        // Compare `balance_after + sent` to `balance_before + received` to avoid underflows.
+        let actual = balances[1].saturating_add(sent);
+        let replayed = balances[0].saturating_add(received);
+        let deviation = cmp::max(actual, replayed) - cmp::min(actual, replayed);
+        tracing::debug!(?token, ?holder, %actual, %replayed, "rebase check");
+        Ok(deviation.saturating_mul(1_000_000.into()) >
+            cmp::max(actual, replayed).saturating_mul(check.tolerance_ppm.into()))
+    }
+
+    /// Returns the total amount of `token` transferred from `from` to `to` after `from_block`
+    /// up to and including `to_block`. `None` matches any address.
+    async fn transferred(
+        &self,
+        token: H160,
+        from: Option<H160>,
+        to: Option<H160>,
+        from_block: u64,
+        to_block: u64,
+    ) -> Result<U256> {
+        let transfer_topic = H256(keccak256(b"Transfer(address,address,uint256)"));
+        let filter = FilterBuilder::default()
+            .address(vec![token])
+            .from_block(BlockNumber::Number((from_block + 1).into()))
+            .to_block(BlockNumber::Number(to_block.into()))
+            .topics(
+                Some(vec![transfer_topic]),
+                from.map(|from| vec![from.into()]),
+                to.map(|to| vec![to.into()]),
+                None,
+            )
+            .build();
+        self.wait_for_rate_limit().await;
+        let logs = self.web3.eth().logs(filter).await?;
+        Ok(logs
+            .iter()
+            .filter(|log| log.data.0.len() == 32)
+            .fold(U256::zero(), |total, log| {
+                total.saturating_add(U256::from_big_endian(&log.data.0))
+            }))
     }
 
     // For the out transfer we use an arbitrary address without balance to detect
@@ -229,38 +551,30 @@ impl TraceCallDetector {
         amount: U256,
         middle_amount: U256,
         take_from: H160,
-    ) -> Result<(TokenQuality, Option<U256>, Option<U256>)> {
+    ) -> Result<DetectionResult> {
         ensure!(traces.len() == 8, "unexpected number of traces");
 
         let gas_in = match ensure_transaction_ok_and_get_gas(&traces[1])? {
             Ok(gas) => gas,
             Err(reason) => {
-                return Ok((
-                    TokenQuality::bad(format!(
-                        "Transfer of token from on chain source {take_from:?} into settlement \
+                return Ok(DetectionResult::new(TokenQuality::non_transferable(format!(
+                    "Transfer of token from on chain source {take_from:?} into settlement \
                      contract failed: {reason}"
-                    )),
-                    None,
-                    None,
-                ))
+                ))))
             }
         };
         let arbitrary = Self::arbitrary_recipient();
         let gas_out = match ensure_transaction_ok_and_get_gas(&traces[4])? {
             Ok(gas) => gas,
             Err(reason) => {
-                return Ok((
-                    TokenQuality::bad(format!(
-                        "Transfer token out of settlement contract to arbitrary recipient \
+                return Ok(DetectionResult::new(TokenQuality::non_transferable(format!(
+                    "Transfer token out of settlement contract to arbitrary recipient \
                      {arbitrary:?} failed: {reason}",
-                    )),
-                    None,
-                    None,
-                ))
+                ))))
             }
         };
 
-        let gas_per_transfer = (gas_in + gas_out) / 2;
+        let gas_per_transfer = to_transfer_cost((gas_in + gas_out) / 2);
 
         let message = "\
             Failed to decode the token's balanceOf response because it did not \
@@ -272,23 +586,23 @@ impl TraceCallDetector {
         let bad = TokenQuality::Bad { reason: message.to_string() };
         let balance_before_in = match decode_u256(&traces[0]) {
             Some(balance) => balance,
-            None => return Ok((bad, Some(gas_per_transfer), None)),
+            None => return Ok(DetectionResult::new(bad).with_transfer_gas(Some(gas_per_transfer))),
         };
         let balance_after_in = match decode_u256(&traces[2]) {
             Some(balance) => balance,
-            None => return Ok((bad, Some(gas_per_transfer), None)),
+            None => return Ok(DetectionResult::new(bad).with_transfer_gas(Some(gas_per_transfer))),
         };
         let balance_after_out = match decode_u256(&traces[5]) {
             Some(balance) => balance,
-            None => return Ok((bad, Some(gas_per_transfer), None)),
+            None => return Ok(DetectionResult::new(bad).with_transfer_gas(Some(gas_per_transfer))),
         };
         let balance_recipient_before = match decode_u256(&traces[3]) {
             Some(balance) => balance,
-            None => return Ok((bad, Some(gas_per_transfer), None)),
+            None => return Ok(DetectionResult::new(bad).with_transfer_gas(Some(gas_per_transfer))),
         };
         let balance_recipient_after = match decode_u256(&traces[6]) {
             Some(balance) => balance,
-            None => return Ok((bad, Some(gas_per_transfer), None)),
+            None => return Ok(DetectionResult::new(bad).with_transfer_gas(Some(gas_per_transfer))),
         };
 
         let fees = Self::calculate_fee(
@@ -306,89 +620,74 @@ impl TraceCallDetector {
         // user more than an amount transferred like an anti fee.
 
         let fees = match fees {
-            Ok(f) => f,
+            Ok(f) => to_transfer_tax(f),
             Err(e) => {
-                return Ok((
-                    TokenQuality::bad(format!(
-                        "Failed to calculate fees for token transfer: {}",
-                        e
-                    )),
-                    None,
-                    None,
-                ))
+                return Ok(DetectionResult::new(TokenQuality::bad(format!(
+                    "Failed to calculate fees for token transfer: {}",
+                    e
+                ))))
             }
         };
 
+        let measured = |quality| DetectionResult {
+            quality,
+            transfer_gas: Some(gas_per_transfer),
+            approve_gas: None,
+            tax: Some(fees),
+            rebasing: false,
+        };
+
         let computed_balance_after_in = match balance_before_in.checked_add(amount) {
             Some(amount) => amount,
             None => {
-                return Ok((
-                    TokenQuality::bad(format!(
+                return Ok(measured(TokenQuality::bad(format!(
                     "Transferring {amount} into settlement contract would overflow its balance."
-                )),
-                    Some(gas_per_transfer),
-                    Some(fees),
-                ))
+                ))))
             }
         };
         if balance_after_in != computed_balance_after_in {
-            return Ok((
-                TokenQuality::bad(format!(
-                    "Transferring {amount} into settlement contract was expected to result in a \
+            return Ok(measured(TokenQuality::bad(format!(
+                "Transferring {amount} into settlement contract was expected to result in a \
                  balance of {computed_balance_after_in} but actually resulted in \
                  {balance_after_in}. A common cause for this is that the token takes a fee on \
                  transfer."
-                )),
-                Some(gas_per_transfer),
-                Some(fees),
-            ));
+            ))));
         }
         if balance_after_out != balance_before_in {
-            return Ok((
-                TokenQuality::bad(format!(
+            return Ok(measured(TokenQuality::bad(format!(
                 "Transferring {amount} out of settlement contract was expected to result in the \
                  original balance of {balance_before_in} but actually resulted in \
                  {balance_after_out}."
-            )),
-                Some(gas_per_transfer),
-                Some(fees),
-            ));
+            ))));
         }
         let computed_balance_recipient_after = match balance_recipient_before.checked_add(amount) {
             Some(amount) => amount,
             None => {
-                return Ok((
-                    TokenQuality::bad(format!(
+                return Ok(measured(TokenQuality::bad(format!(
                     "Transferring {amount} into arbitrary recipient {arbitrary:?} would overflow \
                      its balance."
-                )),
-                    Some(gas_per_transfer),
-                    Some(fees),
-                ))
+                ))))
             }
         };
         if computed_balance_recipient_after != balance_recipient_after {
-            return Ok((
-                TokenQuality::bad(format!(
-                    "Transferring {amount} into arbitrary recipient {arbitrary:?} was expected to \
+            return Ok(measured(TokenQuality::bad(format!(
+                "Transferring {amount} into arbitrary recipient {arbitrary:?} was expected to \
                  result in a balance of {computed_balance_recipient_after} but actually resulted \
                  in {balance_recipient_after}. A common cause for this is that the token takes a \
                  fee on transfer."
-                )),
-                Some(gas_per_transfer),
-                Some(fees),
-            ));
+            ))));
         }
 
-        if let Err(err) = ensure_transaction_ok_and_get_gas(&traces[7])? {
-            return Ok((
-                TokenQuality::bad(format!("Approval of U256::MAX failed: {err}")),
-                Some(gas_per_transfer),
-                Some(fees),
-            ));
-        }
+        let approve_gas = match ensure_transaction_ok_and_get_gas(&traces[7])? {
+            Ok(gas) => to_transfer_cost(gas),
+            Err(err) => {
+                return Ok(measured(TokenQuality::bad(format!(
+                    "Approval of U256::MAX failed: {err}"
+                ))))
+            }
+        };
 
-        Ok((TokenQuality::Good, Some(gas_per_transfer), Some(fees)))
+        Ok(DetectionResult { approve_gas: Some(approve_gas), ..measured(TokenQuality::Good) })
     }
 
     fn calculate_fee(
@@ -451,6 +750,16 @@ impl TraceCallDetector {
     }
 }
 
+/// Converts measured gas, falling back to 8M gas if it doesn't fit into a `TransferCost`.
+fn to_transfer_cost(gas: U256) -> TransferCost {
+    gas.try_into().unwrap_or(8_000_000)
+}
+
+/// Converts a tax in basis points, falling back to 100% if it doesn't fit into a `TransferTax`.
+fn to_transfer_tax(tax: U256) -> TransferTax {
+    tax.try_into().unwrap_or(10_000)
+}
+
 fn error_add(a: U256, b: U256) -> Result<U256, anyhow::Error> {
     a.checked_add(b)
         .ok_or_else(|| anyhow::format_err!("overflow"))
@@ -508,3 +817,211 @@ fn ensure_transaction_ok_and_get_gas(trace: &BlockTrace) -> Result<Result<U256,
     };
     Ok(Ok(call_result.gas_used))
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use tycho_core::models::{Address, Balance};
+
+    use super::*;
+
+    #[derive(Debug, Default)]
+    struct CountingOwnerFinder {
+        calls: AtomicUsize,
+    }
+
+    #[async_trait::async_trait]
+    impl TokenOwnerFinding for CountingOwnerFinder {
+        async fn find_owner(
+            &self,
+            _token: Address,
+            _min_balance: Balance,
+        ) -> std::result::Result<Option<(Address, Balance)>, String> {
+            self.calls
+                .fetch_add(1, Ordering::SeqCst);
+            Ok(None)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pinned_detection_is_cached() {
+        let finder = Arc::new(CountingOwnerFinder::default());
+        let detector = TraceCallDetector::new("http://localhost:8545", finder.clone());
+        let token = H160::from_low_u64_be(1);
+
+        let first = detector
+            .detect(token, BlockTag::Number(100), None)
+            .await
+            .unwrap();
+        let second = detector
+            .detect(token, BlockTag::Number(100), None)
+            .await
+            .unwrap();
+        detector
+            .detect(token, BlockTag::Number(101), None)
+            .await
+            .unwrap();
+
+        assert_eq!(first, second);
+        assert_eq!(finder.calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_detection_error_is_transient() {
+        let rpc_error = |code: i64, message: &str| {
+            DetectionError::Rpc(
+                anyhow::Error::new(web3::Error::Rpc(
+                    serde_json::from_value(serde_json::json!({"code": code, "message": message}))
+                        .unwrap(),
+                ))
+                .context("trace_callMany failed"),
+            )
+        };
+
+        assert!(rpc_error(-32005, "rate limited").is_transient());
+        assert!(DetectionError::Rpc(web3::Error::Unreachable.into()).is_transient());
+        assert!(!rpc_error(3, "execution reverted").is_transient());
+        assert!(!DetectionError::Response(anyhow::anyhow!("trace not set")).is_transient());
+    }
+
+    #[test]
+    fn test_pinned_detections_evict_oldest() {
+        let cache = PinnedDetections::new(2);
+        let token = H160::from_low_u64_be(1);
+        let good = DetectionResult::new(TokenQuality::Good);
+
+        for block in 1..=3 {
+            cache.insert(token, block, good.clone());
+        }
+
+        assert_eq!(cache.get(token, 1), None);
+        assert_eq!(cache.get(token, 2), Some(good.clone()));
+        assert_eq!(cache.get(token, 3), Some(good));
+    }
+
+    #[derive(Debug, Default)]
+    struct RecordingOwnerFinder {
+        min_balances: Mutex<Vec<Balance>>,
+    }
+
+    #[async_trait::async_trait]
+    impl TokenOwnerFinding for RecordingOwnerFinder {
+        async fn find_owner(
+            &self,
+            _token: Address,
+            min_balance: Balance,
+        ) -> std::result::Result<Option<(Address, Balance)>, String> {
+            self.min_balances
+                .lock()
+                .unwrap()
+                .push(min_balance);
+            Ok(None)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_zero_decimals_token_funding_amount() {
+        let finder = Arc::new(RecordingOwnerFinder::default());
+        let detector = TraceCallDetector::new("http://localhost:8545", finder.clone());
+
+        let quality = detector
+            .detect(H160::from_low_u64_be(1), BlockTag::Latest, Some(0))
+            .await
+            .unwrap()
+            .quality;
+
+        // Previously a fixed 100_000 whole units were required for tokens without decimals.
+        assert_eq!(*finder.min_balances.lock().unwrap(), vec![U256::from(10).to_bytes()]);
+        assert!(matches!(quality, TokenQuality::Bad { reason } if reason.contains("at least 10 ")));
+    }
+
+    fn call_trace(error: Option<&str>) -> BlockTrace {
+        balance_trace(error, None)
+    }
+
+    fn balance_trace(error: Option<&str>, balance: Option<u64>) -> BlockTrace {
+        let output = balance.map_or_else(
+            || "0x".to_string(),
+            |balance| {
+                U256::from(balance)
+                    .to_bytes()
+                    .to_string()
+            },
+        );
+        let mut trace = serde_json::json!({
+            "output": output,
+            "trace": [{
+              "traceAddress": [],
+              "subtraces": 0,
+              "action": {
+                "callType": "call",
+                "from": "0x0000000000000000000000000000000000000000",
+                "gas": "0x00",
+                "input": "0x",
+                "to": "0x0000000000000000000000000000000000000000",
+                "value": "0x00"
+              },
+              "result": { "gasUsed": "0x5208", "output": "0x" },
+              "type": "call"
+            }],
+        });
+        if let Some(error) = error {
+            trace["trace"][0]["error"] = error.into();
+            trace["trace"][0]
+                .as_object_mut()
+                .unwrap()
+                .remove("result");
+        }
+        serde_json::from_value(trace).unwrap()
+    }
+
+    #[test]
+    fn test_paused_token_is_non_transferable() {
+        let mut traces: Vec<_> = (0..8)
+            .map(|_| call_trace(None))
+            .collect();
+        traces[1] = call_trace(Some("Reverted"));
+
+        let res = TraceCallDetector::handle_response(
+            &traces,
+            U256::from(100_000),
+            U256::from(100_000),
+            H160::from_low_u64_be(1),
+        )
+        .unwrap();
+
+        assert!(matches!(res.quality, TokenQuality::NonTransferable { .. }));
+        assert_eq!(res.transfer_gas, None);
+        assert_eq!(res.tax, None);
+    }
+
+    #[test]
+    fn test_fee_token_detection_result() {
+        // A token taking a 1% fee on each transfer: 100_000 are sent into the settlement
+        // contract, which receives 99_000 and forwards them to a recipient receiving 98_010.
+        let traces = vec![
+            balance_trace(None, Some(0)),
+            call_trace(None),
+            balance_trace(None, Some(99_000)),
+            balance_trace(None, Some(0)),
+            call_trace(None),
+            balance_trace(None, Some(0)),
+            balance_trace(None, Some(98_010)),
+            call_trace(None),
+        ];
+
+        let res = TraceCallDetector::handle_response(
+            &traces,
+            U256::from(100_000),
+            U256::from(99_000),
+            H160::from_low_u64_be(1),
+        )
+        .unwrap();
+
+        assert!(matches!(res.quality, TokenQuality::Bad { ref reason } if reason.contains("fee")));
+        assert_eq!(res.transfer_gas, Some(21_000));
+        assert_eq!(res.tax, Some(100));
+        assert_eq!(res.approve_gas, None);
+    }
+}
@@ -0,0 +1,122 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use tycho_core::Bytes;
+
+const DEFAULT_BASE_DELAY: Duration = Duration::from_secs(60);
+const DEFAULT_MAX_DELAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Debug, Clone, Copy)]
+struct FailureState {
+    consecutive_failures: u32,
+    next_retry: Instant,
+}
+
+/// Tracks consecutive token detection failures and delays further attempts exponentially.
+///
+/// After the n-th consecutive failure, a token is not analyzed again for `base_delay * 2^(n-1)`,
+/// capped at `max_delay`. A successful detection resets the token's state. Clones share the
+/// same state.
+#[derive(Debug, Clone)]
+pub struct DetectionBackoff {
+    base_delay: Duration,
+    max_delay: Duration,
+    failures: Arc<Mutex<HashMap<Bytes, FailureState>>>,
+}
+
+impl Default for DetectionBackoff {
+    fn default() -> Self {
+        Self::new(DEFAULT_BASE_DELAY, DEFAULT_MAX_DELAY)
+    }
+}
+
+impl DetectionBackoff {
+    pub fn new(base_delay: Duration, max_delay: Duration) -> Self {
+        Self { base_delay, max_delay, failures: Arc::new(Mutex::new(HashMap::new())) }
+    }
+
+    /// Returns whether detection may be attempted for `address` at `now`.
+    pub fn should_attempt(&self, address: &Bytes, now: Instant) -> bool {
+        let failures = self
+            .failures
+            .lock()
+            .expect("backoff lock poisoned");
+        !matches!(failures.get(address), Some(state) if now < state.next_retry)
+    }
+
+    /// Records a failed detection and returns the earliest time of the next attempt.
+    pub fn record_failure(&self, address: &Bytes, now: Instant) -> Instant {
+        let mut failures = self
+            .failures
+            .lock()
+            .expect("backoff lock poisoned");
+        let consecutive_failures = failures
+            .get(address)
+            .map_or(1, |state| {
+                state
+                    .consecutive_failures
+                    .saturating_add(1)
+            });
+        let delay = self
+            .base_delay
+            .checked_mul(2u32.saturating_pow(consecutive_failures - 1))
+            .map_or(self.max_delay, |delay| delay.min(self.max_delay));
+        let next_retry = now + delay;
+        failures.insert(address.clone(), FailureState { consecutive_failures, next_retry });
+        next_retry
+    }
+
+    /// Records a successful detection, resetting the backoff for `address`.
+    pub fn record_success(&self, address: &Bytes) {
+        self.failures
+            .lock()
+            .expect("backoff lock poisoned")
+            .remove(address);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_delay_grows_until_capped() {
+        let backoff = DetectionBackoff::new(Duration::from_secs(10), Duration::from_secs(60));
+        let address = Bytes::from("0x01");
+        let now = Instant::now();
+
+        let delays: Vec<_> = (0..5)
+            .map(|_| {
+                backoff
+                    .record_failure(&address, now)
+                    .duration_since(now)
+            })
+            .collect();
+
+        assert_eq!(
+            delays,
+            [10, 20, 40, 60, 60]
+                .map(Duration::from_secs)
+                .to_vec()
+        );
+        assert!(!backoff.should_attempt(&address, now + Duration::from_secs(59)));
+        assert!(backoff.should_attempt(&address, now + Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn test_success_resets_backoff() {
+        let backoff = DetectionBackoff::new(Duration::from_secs(10), Duration::from_secs(60));
+        let address = Bytes::from("0x01");
+        let now = Instant::now();
+        backoff.record_failure(&address, now);
+        backoff.record_failure(&address, now);
+
+        backoff.record_success(&address);
+
+        assert!(backoff.should_attempt(&address, now));
+        assert_eq!(backoff.record_failure(&address, now), now + Duration::from_secs(10));
+    }
+}
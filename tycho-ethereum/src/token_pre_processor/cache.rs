@@ -0,0 +1,84 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use tycho_core::{
+    models::{token::CurrencyToken, Address, Chain},
+    Bytes,
+};
+
+/// Tokens whose analysis failed score below this quality, they are not cached so that they are
+/// analyzed again the next time they are requested.
+const MIN_CACHED_QUALITY: u32 = 30;
+
+/// Caches analyzed tokens, so tokens that appear in many components are only fetched once.
+///
+/// Clones share the same entries.
+#[derive(Debug, Clone, Default)]
+pub struct TokenCache {
+    tokens: Arc<RwLock<HashMap<(Chain, Address), CurrencyToken>>>,
+}
+
+impl TokenCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the cached token at `address` on `chain`.
+    pub fn get(&self, chain: Chain, address: &Bytes) -> Option<CurrencyToken> {
+        self.tokens
+            .read()
+            .expect("token cache lock poisoned")
+            .get(&(chain, address.clone()))
+            .cloned()
+    }
+
+    /// Caches `tokens`, replacing previous entries. Tokens whose analysis failed are skipped.
+    pub fn insert(&self, tokens: impl IntoIterator<Item = CurrencyToken>) {
+        let mut cache = self
+            .tokens
+            .write()
+            .expect("token cache lock poisoned");
+        for token in tokens {
+            if token.quality >= MIN_CACHED_QUALITY {
+                cache.insert((token.chain, token.address.clone()), token);
+            }
+        }
+    }
+
+    /// Removes the token at `address` on `chain`, so it's fetched again on its next request.
+    pub fn invalidate(&self, chain: Chain, address: &Bytes) {
+        self.tokens
+            .write()
+            .expect("token cache lock poisoned")
+            .remove(&(chain, address.clone()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_cache() {
+        let cache = TokenCache::new();
+        let usdc = Bytes::from("0x01");
+        let failed = Bytes::from("0x02");
+        cache.insert([
+            CurrencyToken::new(&usdc, "USDC", 6, 0, &[], Chain::Ethereum, 100),
+            CurrencyToken::new(&failed, "FAIL", 18, 0, &[], Chain::Ethereum, 10),
+        ]);
+
+        assert_eq!(
+            cache.get(Chain::Ethereum, &usdc),
+            Some(CurrencyToken::new(&usdc, "USDC", 6, 0, &[], Chain::Ethereum, 100))
+        );
+        assert_eq!(cache.get(Chain::Arbitrum, &usdc), None);
+        assert_eq!(cache.get(Chain::Ethereum, &failed), None);
+
+        cache.invalidate(Chain::Ethereum, &usdc);
+
+        assert_eq!(cache.get(Chain::Ethereum, &usdc), None);
+    }
+}
@@ -0,0 +1,75 @@
+use std::collections::HashMap;
+
+use tycho_core::{models::Chain, Bytes};
+
+const DEFAULT_DECIMALS: u8 = 18;
+
+/// Decimals assumed for tokens whose `decimals()` call fails.
+///
+/// Defaults can be set per token and per chain. A lookup returns the most specific match: the
+/// token's default, then its chain's default, then the global fallback of 18 decimals.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecimalsDefaults {
+    fallback: u8,
+    defaults: HashMap<(Chain, Option<Bytes>), u8>,
+}
+
+impl Default for DecimalsDefaults {
+    fn default() -> Self {
+        Self::new(DEFAULT_DECIMALS)
+    }
+}
+
+impl DecimalsDefaults {
+    pub fn new(fallback: u8) -> Self {
+        Self { fallback, defaults: HashMap::new() }
+    }
+
+    /// Sets the default of all tokens on `chain` without a token specific default.
+    pub fn with_chain_default(mut self, chain: Chain, decimals: u8) -> Self {
+        self.defaults
+            .insert((chain, None), decimals);
+        self
+    }
+
+    /// Sets the default of the token at `address` on `chain`.
+    pub fn with_token_default(mut self, chain: Chain, address: Bytes, decimals: u8) -> Self {
+        self.defaults
+            .insert((chain, Some(address)), decimals);
+        self
+    }
+
+    /// Returns the decimals to assume for the token at `address` on `chain`.
+    pub fn get(&self, chain: Chain, address: &Bytes) -> u8 {
+        self.defaults
+            .get(&(chain, Some(address.clone())))
+            .or_else(|| self.defaults.get(&(chain, None)))
+            .copied()
+            .unwrap_or(self.fallback)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_default_overrides_chain_default() {
+        let token = Bytes::from("0x01");
+        let defaults = DecimalsDefaults::default()
+            .with_chain_default(Chain::Starknet, 6)
+            .with_token_default(Chain::Starknet, token.clone(), 8);
+
+        assert_eq!(defaults.get(Chain::Starknet, &token), 8);
+        assert_eq!(defaults.get(Chain::Starknet, &Bytes::from("0x02")), 6);
+        assert_eq!(defaults.get(Chain::Ethereum, &token), 18);
+    }
+
+    #[test]
+    fn test_chain_default() {
+        let defaults = DecimalsDefaults::new(12).with_chain_default(Chain::ZkSync, 6);
+
+        assert_eq!(defaults.get(Chain::ZkSync, &Bytes::from("0x01")), 6);
+        assert_eq!(defaults.get(Chain::Arbitrum, &Bytes::from("0x01")), 12);
+    }
+}
@@ -1,43 +1,123 @@
 use async_trait::async_trait;
-use ethers::{abi::Abi, contract::Contract, prelude::Provider, providers::Http, types::H160};
+use ethers::{
+    abi::{parse_abi, Abi, Function, Token},
+    contract::{
+        multicall_contract::{Call3, Multicall3},
+        ContractError, MULTICALL_ADDRESS, MULTICALL_SUPPORTED_CHAIN_IDS,
+    },
+    prelude::Provider,
+    providers::{Http, Middleware},
+    types::{transaction::eip2718::TypedTransaction, TransactionRequest, H160, U256},
+};
 use ethrpc::{http::HttpTransport, Web3, Web3Transport};
+use futures03::{stream, Future, StreamExt};
 use reqwest::Client;
 use serde_json::from_str;
-use std::{str::FromStr, sync::Arc};
-use tracing::{instrument, warn};
+use std::{
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tracing::{debug, instrument, warn};
 use unicode_segmentation::UnicodeSegmentation;
 use url::Url;
 
 use tycho_core::{
     models::{
         blockchain::BlockTag,
-        token::{CurrencyToken, TokenQuality},
+        token::{CurrencyToken, DetectionResult, TokenKind, TokenQuality},
         Chain,
     },
-    traits::{TokenAnalyzer, TokenOwnerFinding, TokenPreProcessor},
+    traits::{TokenOwnerFinding, TokenPreProcessor},
     Bytes,
 };
 
-use crate::{token_analyzer::trace_call::TraceCallDetector, BytesCodec};
+use crate::{
+    rate_limiter::RateLimiter,
+    token_analyzer::trace_call::{
+        DetectionError, PinnedDetections, RebaseCheck, TraceCallDetector,
+    },
+    BytesCodec,
+};
+
+pub mod backoff;
+pub mod cache;
+pub mod decimals;
+pub mod quality;
+pub mod retry;
+
+use backoff::DetectionBackoff;
+use cache::TokenCache;
+use decimals::DecimalsDefaults;
+use quality::QualityConfig;
+use retry::{is_transient, with_timeout, CallError, RetryPolicy};
 
 #[derive(Debug, Clone)]
 pub struct EthereumTokenPreProcessor {
     ethers_client: Arc<Provider<Http>>,
     erc20_abi: Abi,
+    vault_abi: Abi,
     web3_client: Web3,
     chain: Chain,
+    /// Optional limiter shared by all RPC calls issued while fetching token information.
+    rate_limiter: Option<RateLimiter>,
+    /// Delays detection of tokens that repeatedly failed to be analyzed.
+    detection_backoff: DetectionBackoff,
+    /// Decimals assumed for tokens whose decimals can't be fetched.
+    decimals_defaults: DecimalsDefaults,
+    /// Maximum number of tokens analyzed concurrently.
+    concurrency: usize,
+    /// Retries RPC calls failing with transient errors.
+    retry: RetryPolicy,
+    /// Serves previously analyzed tokens without fetching them again.
+    token_cache: Option<TokenCache>,
+    /// Maximum duration of a single RPC interaction, e.g. one call or one token detection.
+    call_timeout: Duration,
+    /// Qualities assigned to tokens that were analyzed successfully.
+    quality: QualityConfig,
+    /// Results of detections pinned to a block, shared by the detectors of all requests.
+    pinned_detections: PinnedDetections,
+    /// Parameters of the rebase check of token analysis, disabled if unset.
+    rebase_check: Option<RebaseCheck>,
 }
 
 const ABI_STR: &str = include_str!("./abi/erc20.json");
 
+const DEFAULT_CONCURRENCY: usize = 8;
+const DEFAULT_CALL_TIMEOUT: Duration = Duration::from_secs(30);
+/// Maximum number of tokens whose metadata is fetched in a single Multicall3 call.
+const METADATA_BATCH_SIZE: usize = 200;
+const VAULT_ABI: &[&str] = &["function asset() external view returns (address)"];
+
+/// Metadata of a token, fields are `None` if the respective call failed.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct TokenMetadata {
+    symbol: Option<String>,
+    decimals: Option<u8>,
+    name: Option<String>,
+    /// The underlying asset reported by `asset()`, set only for ERC-4626 vault tokens.
+    asset: Option<H160>,
+}
+
 impl EthereumTokenPreProcessor {
     pub fn new(ethers_client: Provider<Http>, web3_client: Web3, chain: Chain) -> Self {
         let abi = from_str::<Abi>(ABI_STR).expect("Unable to parse ABI");
         EthereumTokenPreProcessor {
             ethers_client: Arc::new(ethers_client),
             erc20_abi: abi,
+            vault_abi: parse_abi(VAULT_ABI).expect("Unable to parse vault ABI"),
             web3_client,
             chain,
+            rate_limiter: None,
+            detection_backoff: DetectionBackoff::default(),
+            decimals_defaults: DecimalsDefaults::default(),
+            concurrency: DEFAULT_CONCURRENCY,
+            retry: RetryPolicy::default(),
+            token_cache: None,
+            call_timeout: DEFAULT_CALL_TIMEOUT,
+            quality: QualityConfig::default(),
+            pinned_detections: PinnedDetections::default(),
+            rebase_check: None,
         }
     }
 
@@ -55,12 +135,472 @@ impl EthereumTokenPreProcessor {
         EthereumTokenPreProcessor {
             ethers_client: Arc::new(ethers_client),
             erc20_abi: abi,
+            vault_abi: parse_abi(VAULT_ABI).expect("Unable to parse vault ABI"),
             web3_client,
             chain,
+            rate_limiter: None,
+            detection_backoff: DetectionBackoff::default(),
+            decimals_defaults: DecimalsDefaults::default(),
+            concurrency: DEFAULT_CONCURRENCY,
+            retry: RetryPolicy::default(),
+            token_cache: None,
+            call_timeout: DEFAULT_CALL_TIMEOUT,
+            quality: QualityConfig::default(),
+            pinned_detections: PinnedDetections::default(),
+            rebase_check: None,
+        }
+    }
+
+    /// Paces all RPC calls made by this processor, including token analysis, using the given
+    /// limiter.
+    pub fn with_rate_limiter(mut self, rate_limiter: RateLimiter) -> Self {
+        self.rate_limiter = Some(rate_limiter);
+        self
+    }
+
+    /// Checks analyzed tokens for rebasing, which requires an archive node. Disabled by default.
+    pub fn with_rebase_check(mut self, rebase_check: RebaseCheck) -> Self {
+        self.rebase_check = Some(rebase_check);
+        self
+    }
+
+    /// Replaces the default backoff applied to tokens whose detection keeps failing.
+    pub fn with_detection_backoff(mut self, detection_backoff: DetectionBackoff) -> Self {
+        self.detection_backoff = detection_backoff;
+        self
+    }
+
+    /// Replaces the default of 18 decimals assumed for tokens whose decimals can't be fetched.
+    pub fn with_decimals_defaults(mut self, decimals_defaults: DecimalsDefaults) -> Self {
+        self.decimals_defaults = decimals_defaults;
+        self
+    }
+
+    /// Sets how many tokens are analyzed concurrently, defaults to 8. Values below 1 are treated
+    /// as 1.
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Replaces the default policy of retrying transient RPC failures up to 3 times.
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Serves tokens from `cache` if possible and caches newly analyzed tokens. Pass a clone of
+    /// the cache to pre-warm or invalidate entries later. Disabled by default.
+    pub fn with_token_cache(mut self, cache: TokenCache) -> Self {
+        self.token_cache = Some(cache);
+        self
+    }
+
+    /// Bounds the duration of each RPC interaction, defaults to 30 seconds. Interactions that
+    /// time out are treated as failed and not retried.
+    pub fn with_call_timeout(mut self, call_timeout: Duration) -> Self {
+        self.call_timeout = call_timeout;
+        self
+    }
+
+    /// Replaces the default qualities of normal tokens (100) and fee tokens (50).
+    pub fn with_quality_config(mut self, quality: QualityConfig) -> Self {
+        self.quality = quality;
+        self
+    }
+
+    async fn wait_for_rate_limit(&self) {
+        if let Some(limiter) = &self.rate_limiter {
+            limiter.acquire().await;
+        }
+    }
+
+    /// Fetches symbol, decimals, name and vault asset of the tokens at `addresses`, in the same
+    /// order.
+    ///
+    /// Calls are batched into one Multicall3 call per [`METADATA_BATCH_SIZE`] tokens. If
+    /// Multicall3 isn't available or a batch fails, each token of that batch is queried
+    /// separately instead.
+    async fn fetch_metadata(&self, addresses: &[Bytes]) -> Vec<TokenMetadata> {
+        let mut metadata = Vec::with_capacity(addresses.len());
+        for batch in addresses.chunks(METADATA_BATCH_SIZE) {
+            if let Some(batch_metadata) = self.fetch_metadata_batched(batch).await {
+                metadata.extend(batch_metadata);
+                continue;
+            }
+            for address in batch {
+                metadata.push(
+                    fetch_metadata_serial(
+                        self.ethers_client.as_ref(),
+                        &self.erc20_abi,
+                        &self.vault_abi,
+                        address,
+                        &self.retry,
+                        self.call_timeout,
+                        self.rate_limiter.as_ref(),
+                    )
+                    .await,
+                );
+            }
+        }
+        metadata
+    }
+
+    /// Fetches the metadata of the tokens through a single Multicall3 call. Returns `None` if
+    /// Multicall3 isn't deployed on this chain or the aggregate call fails.
+    async fn fetch_metadata_batched(&self, addresses: &[Bytes]) -> Option<Vec<TokenMetadata>> {
+        if !MULTICALL_SUPPORTED_CHAIN_IDS.contains(&self.chain.id()) {
+            return None;
+        }
+        let symbol = self
+            .erc20_abi
+            .function("symbol")
+            .expect("Unable to find symbol in ABI");
+        let decimals = self
+            .erc20_abi
+            .function("decimals")
+            .expect("Unable to find decimals in ABI");
+        let name = self
+            .erc20_abi
+            .function("name")
+            .expect("Unable to find name in ABI");
+        let asset = self
+            .vault_abi
+            .function("asset")
+            .expect("Unable to find asset in ABI");
+        let calls = addresses
+            .iter()
+            .flat_map(|address| {
+                [symbol, decimals, name, asset].map(|function| Call3 {
+                    target: H160::from_bytes(address),
+                    allow_failure: true,
+                    call_data: function
+                        .encode_input(&[])
+                        .expect("Error preparing request")
+                        .into(),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let multicall = Multicall3::new(MULTICALL_ADDRESS, self.ethers_client.clone());
+        let aggregate = &multicall.aggregate_3(calls);
+        let results = self
+            .retry
+            .retry(
+                |e: &CallError<ContractError<Provider<Http>>>| {
+                    e.is_transient(|e| {
+                        e.as_middleware_error()
+                            .is_some_and(is_transient)
+                    })
+                },
+                || async move {
+                    self.wait_for_rate_limit().await;
+                    with_timeout(self.call_timeout, aggregate.call()).await
+                },
+            )
+            .await
+            .map_err(|e| warn!(error=?e, "MulticallFailure, falling back to serial calls"))
+            .ok()?;
+
+        Some(
+            results
+                .chunks(4)
+                .map(|res| TokenMetadata {
+                    symbol: decode_string(symbol, res[0].success, &res[0].return_data),
+                    decimals: decode_decimals(decimals, res[1].success, &res[1].return_data),
+                    name: decode_string(name, res[2].success, &res[2].return_data),
+                    asset: decode_asset(asset, res[3].success, &res[3].return_data),
+                })
+                .collect(),
+        )
+    }
+
+    /// Fetches and analyzes the tokens at `addresses`, bypassing the cache.
+    async fn fetch_tokens(
+        &self,
+        addresses: Vec<Bytes>,
+        token_finder: Arc<dyn TokenOwnerFinding>,
+        block: BlockTag,
+    ) -> Vec<CurrencyToken> {
+        if addresses.is_empty() {
+            return Vec::new();
+        }
+        let metadata = self.fetch_metadata(&addresses).await;
+        let tokens = addresses.into_iter().zip(metadata);
+        let detector = TraceCallDetector {
+            web3: self.web3_client.clone(),
+            finder: token_finder,
+            settlement_contract: H160::from_str("0xc9f2e6ea1637E499406986ac50ddC92401ce1f58") // middle contract used to check for fees, set to cowswap settlement
+                .unwrap(),
+            rate_limiter: self.rate_limiter.clone(),
+            pinned_detections: self.pinned_detections.clone(),
+            rebase_check: self.rebase_check,
+        };
+
+        map_bounded(tokens, self.concurrency, |(address, metadata)| {
+            self.token_info(address, metadata, &detector, block)
+        })
+        .await
+    }
+
+    /// Fetches everything except the metadata of the token at `address` and scores its quality.
+    async fn token_info(
+        &self,
+        address: Bytes,
+        TokenMetadata { symbol, decimals, name, asset }: TokenMetadata,
+        detector: &TraceCallDetector,
+        block: BlockTag,
+    ) -> CurrencyToken {
+        // Detection reports problems of the token itself in the result, errors are caused by
+        // failing RPC calls or unexpected responses. Only transient failures are retried.
+        let detection = if self
+            .detection_backoff
+            .should_attempt(&address, Instant::now())
+        {
+            match self
+                .retry
+                .retry(
+                    |e: &CallError<DetectionError>| e.is_transient(DetectionError::is_transient),
+                    || {
+                        with_timeout(
+                            self.call_timeout,
+                            detector.detect(H160::from_bytes(&address), block, decimals),
+                        )
+                    },
+                )
+                .await
+            {
+                Ok(res) => {
+                    self.detection_backoff
+                        .record_success(&address);
+                    res
+                }
+                Err(e) => {
+                    let next_retry = self
+                        .detection_backoff
+                        .record_failure(&address, Instant::now());
+                    warn!(error=?e, ?next_retry, "TokenDetectionFailure");
+                    let reason = match e {
+                        CallError::Timeout(_) => "Detection timed out",
+                        CallError::Failed(_) => "Detection failed",
+                    };
+                    DetectionResult::new(TokenQuality::bad(reason))
+                }
+            }
+        } else {
+            debug!(?address, "TokenDetectionBackoff");
+            DetectionResult::new(TokenQuality::bad("Detection backed off after repeated failures"))
+        };
+
+        let default_decimals = self
+            .decimals_defaults
+            .get(self.chain, &address);
+        let (symbol, decimals, metadata_error) = match (symbol, decimals) {
+            (Some(symbol), Some(decimals)) => (symbol, decimals, None),
+            (Some(symbol), None) => (symbol, default_decimals, Some("Failed to fetch decimals")),
+            (None, Some(decimals)) => {
+                (address.to_string(), decimals, Some("Failed to fetch symbol"))
+            }
+            (None, None) => {
+                (address.to_string(), default_decimals, Some("Failed to fetch symbol and decimals"))
+            }
+        };
+
+        let (quality, quality_reason) =
+            score_token(&address, metadata_error, decimals, &detection, &self.quality);
+
+        let symbol = sanitize(&symbol);
+        CurrencyToken {
+            address,
+            name: name
+                .map(|name| sanitize(&name))
+                .filter(|name| !name.is_empty())
+                .unwrap_or_else(|| symbol.clone()),
+            symbol,
+            decimals: decimals.into(),
+            tax: detection.tax.unwrap_or(0),
+            gas: detection
+                .transfer_gas
+                .map(|g| vec![Some(g)])
+                .unwrap_or_else(Vec::new),
+            chain: self.chain,
+            quality,
+            kind: if asset.is_some() { TokenKind::Vault } else { TokenKind::Standard },
+            underlying_asset: asset.map(BytesCodec::to_bytes),
+            quality_reason,
+            rebasing: detection.rebasing,
         }
     }
 }
 
+/// Applies `f` to all `items`, with at most `limit` of the returned futures in flight at once.
+/// Results are returned in the order of `items`.
+async fn map_bounded<T, R, F, Fut>(items: impl IntoIterator<Item = T>, limit: usize, f: F) -> Vec<R>
+where
+    F: FnMut(T) -> Fut,
+    Fut: Future<Output = R>,
+{
+    stream::iter(items)
+        .map(f)
+        .buffered(limit)
+        .collect()
+        .await
+}
+
+/// Removes null characters and truncates to 255 characters.
+fn sanitize(value: &str) -> String {
+    value
+        .replace('\0', "")
+        .graphemes(true)
+        .take(255)
+        .collect()
+}
+
+/// Fetches the metadata of a single token with one call per field.
+///
+/// Calls are retried according to `retry`, each attempt waits for `rate_limiter` first and may
+/// take at most `timeout`.
+async fn fetch_metadata_serial<'a, M: Middleware>(
+    client: &M,
+    erc20_abi: &'a Abi,
+    vault_abi: &'a Abi,
+    address: &Bytes,
+    retry: &RetryPolicy,
+    timeout: Duration,
+    rate_limiter: Option<&RateLimiter>,
+) -> TokenMetadata {
+    let call = |abi: &'a Abi, name: &str| {
+        let function = abi
+            .function(name)
+            .expect("Unable to find function in ABI");
+        let tx: TypedTransaction = TransactionRequest::new()
+            .to(H160::from_bytes(address))
+            .data(
+                function
+                    .encode_input(&[])
+                    .expect("Error preparing request"),
+            )
+            .into();
+        async move {
+            let tx = &tx;
+            let data = retry
+                .retry(
+                    |e: &CallError<M::Error>| e.is_transient(is_transient),
+                    || async move {
+                        if let Some(limiter) = rate_limiter {
+                            limiter.acquire().await;
+                        }
+                        with_timeout(timeout, client.call(tx, None)).await
+                    },
+                )
+                .await
+                .map_err(|e| debug!(error=?e, ?address, function=%function.name, "CallFailure"))
+                .ok()?;
+            Some((function, data))
+        }
+    };
+
+    // Called raw, so that symbols and names returned as `bytes32` can be decoded too.
+    let symbol = call(erc20_abi, "symbol")
+        .await
+        .and_then(|(function, data)| decode_string(function, true, &data));
+    let decimals = call(erc20_abi, "decimals")
+        .await
+        .and_then(|(function, data)| decode_decimals(function, true, &data));
+    let name = call(erc20_abi, "name")
+        .await
+        .and_then(|(function, data)| decode_string(function, true, &data));
+    // Reverts for tokens that aren't ERC-4626 vaults.
+    let asset = call(vault_abi, "asset")
+        .await
+        .and_then(|(function, data)| decode_asset(function, true, &data));
+    TokenMetadata { symbol, decimals, name, asset }
+}
+
+/// Decodes the return value of a call to `decimals()`, `None` if the call failed or the value
+/// doesn't fit into a `u8`.
+fn decode_decimals(function: &Function, success: bool, data: &[u8]) -> Option<u8> {
+    decode_output(function, success, data)
+        .and_then(Token::into_uint)
+        .filter(|decimals| *decimals <= U256::from(u8::MAX))
+        .map(|decimals| decimals.low_u32() as u8)
+}
+
+/// Decodes the return value of a call to `function` returning a `string`, `None` if the call
+/// failed or returned unexpected data.
+///
+/// Some legacy tokens, e.g. MKR, return a `bytes32` padded with zeros instead. A string is
+/// ABI-encoded in at least 64 bytes, so 32 bytes of return data are decoded as such a `bytes32`.
+fn decode_string(function: &Function, success: bool, data: &[u8]) -> Option<String> {
+    if success && data.len() == 32 {
+        let end = data
+            .iter()
+            .rposition(|b| *b != 0)
+            .map_or(0, |i| i + 1);
+        return String::from_utf8(data[..end].to_vec())
+            .ok()
+            .filter(|s| !s.is_empty());
+    }
+    decode_output(function, success, data).and_then(Token::into_string)
+}
+
+/// Decodes the single return value of a call to `function`, `None` if the call failed or returned
+/// unexpected data.
+fn decode_output(function: &Function, success: bool, data: &[u8]) -> Option<Token> {
+    if !success {
+        return None;
+    }
+    function.decode_output(data).ok()?.pop()
+}
+
+/// Decodes the return value of a call to `asset()`, `None` if the call failed or returned the zero
+/// address, i.e. the token isn't an ERC-4626 vault.
+fn decode_asset(function: &Function, success: bool, data: &[u8]) -> Option<H160> {
+    decode_output(function, success, data)
+        .and_then(Token::into_address)
+        .filter(|asset| !asset.is_zero())
+}
+
+/// Scores the quality of a token, see `CurrencyToken::quality` for the meaning of the scores.
+///
+/// `metadata_error` describes why the token's symbol or decimals couldn't be fetched, if they
+/// couldn't. Tokens that were analyzed successfully are scored according to `config`. Returns the
+/// score along with the reason it is below the good tier.
+fn score_token(
+    address: &Bytes,
+    metadata_error: Option<&str>,
+    decimals: u8,
+    detection: &DetectionResult,
+    config: &QualityConfig,
+) -> (u32, Option<String>) {
+    match &detection.quality {
+        TokenQuality::Bad { reason } => {
+            warn!(address=?address, ?reason, "BadToken");
+            // Flag this token as bad using quality, an external script is responsible for
+            // analyzing these tokens again.
+            return (10, Some(reason.clone()));
+        }
+        TokenQuality::NonTransferable { reason } => {
+            warn!(address=?address, ?reason, "NonTransferableToken");
+            return (0, Some(format!("Non-transferable: {reason}")));
+        }
+        TokenQuality::Good => {}
+    }
+
+    if let Some(error) = metadata_error {
+        (0, Some(error.to_string()))
+    } else if detection
+        .tax
+        .is_some_and(|tax| config.is_fee_token(tax))
+    {
+        (config.fee_token_quality(), Some("Fee token".to_string()))
+    } else if decimals == 0 {
+        (config.zero_decimals, Some("Token without decimals".to_string()))
+    } else {
+        (config.good, None)
+    }
+}
+
 /// Map a protocol system into its vault
 /// TODO: This is a hack until we can use the `balance_owner` attribute. Needs to be fixed once we
 /// emit this attribute for every protocol in Substreams
@@ -87,87 +627,398 @@ impl TokenPreProcessor for EthereumTokenPreProcessor {
         token_finder: Arc<dyn TokenOwnerFinding>,
         block: BlockTag,
     ) -> Vec<CurrencyToken> {
-        let mut tokens_info = Vec::new();
+        let Some(cache) = &self.token_cache else {
+            return self
+                .fetch_tokens(addresses, token_finder, block)
+                .await;
+        };
+
+        let cached: Vec<_> = addresses
+            .iter()
+            .map(|address| cache.get(self.chain, address))
+            .collect();
+        let misses = addresses
+            .into_iter()
+            .zip(cached.iter())
+            .filter(|(_, token)| token.is_none())
+            .map(|(address, _)| address)
+            .collect::<Vec<_>>();
+        debug!(n_cached = cached.len() - misses.len(), n_misses = misses.len(), "TokenCacheLookup");
 
-        for address in addresses {
-            let contract = Contract::new(
-                H160::from_bytes(&address),
-                self.erc20_abi.clone(),
-                self.ethers_client.clone(),
+        let fetched = self
+            .fetch_tokens(misses, token_finder, block)
+            .await;
+        cache.insert(fetched.iter().cloned());
+
+        let mut fetched = fetched.into_iter();
+        cached
+            .into_iter()
+            .map(|token| {
+                token.unwrap_or_else(|| {
+                    fetched
+                        .next()
+                        .expect("a token was fetched for each miss")
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::{
+        abi::{encode, Token},
+        providers::{JsonRpcClient, JsonRpcError, MockError, MockResponse},
+    };
+    use std::{
+        collections::HashMap,
+        env,
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+    use tycho_core::models::token::TokenOwnerStore;
+
+    #[test]
+    fn test_score_token() {
+        let good = || DetectionResult::new(TokenQuality::Good);
+        let cases = [
+            (None, 18, good(), 100, None),
+            (None, 18, good().with_tax(Some(100)), 50, Some("Fee token")),
+            (None, 0, good(), 30, Some("Token without decimals")),
+            (
+                None,
+                18,
+                DetectionResult::new(TokenQuality::bad("Detection failed")),
+                10,
+                Some("Detection failed"),
+            ),
+            (
+                None,
+                18,
+                DetectionResult::new(TokenQuality::non_transferable("transfer reverted")),
+                0,
+                Some("Non-transferable: transfer reverted"),
+            ),
+            (Some("Failed to fetch decimals"), 18, good(), 0, Some("Failed to fetch decimals")),
+        ];
+
+        for (metadata_error, decimals, detection, expected_quality, expected_reason) in cases {
+            let (quality, reason) = score_token(
+                &Bytes::from("0x01"),
+                metadata_error,
+                decimals,
+                &detection,
+                &QualityConfig::default(),
             );
 
-            let symbol = contract
-                .method("symbol", ())
-                .expect("Error preparing request")
-                .call()
-                .await;
+            assert_eq!(quality, expected_quality, "{expected_reason:?}");
+            assert_eq!(reason.as_deref(), expected_reason);
+        }
+    }
 
-            let decimals: Result<u8, _> = contract
-                .method("decimals", ())
-                .expect("Error preparing request")
-                .call()
-                .await;
+    #[test]
+    fn test_score_token_custom_tax_threshold() {
+        let detection = DetectionResult::new(TokenQuality::Good).with_tax(Some(5));
+        let score = |config| score_token(&Bytes::from("0x01"), None, 18, &detection, &config);
 
-            let trace_call = TraceCallDetector {
-                web3: self.web3_client.clone(),
-                finder: token_finder.clone(),
-                settlement_contract: H160::from_str("0xc9f2e6ea1637E499406986ac50ddC92401ce1f58") // middle contract used to check for fees, set to cowswap settlement
-                    .unwrap(),
-            };
+        assert_eq!(score(QualityConfig::default()), (50, Some("Fee token".to_string())));
+        assert_eq!(score(QualityConfig::new(100, 50, 10)), (100, None));
+        assert_eq!(score(QualityConfig::new(90, 20, 1)), (70, Some("Fee token".to_string())));
+    }
 
-            let (token_quality, gas, tax) = trace_call
-                .analyze(address.clone(), block)
-                .await
-                .unwrap_or_else(|e| {
-                    warn!(error=?e, "TokenDetectionFailure");
-                    (TokenQuality::bad("Detection failed"), None, None)
-                });
-
-            let (symbol, decimals, mut quality) = match (symbol, decimals) {
-                (Ok(symbol), Ok(decimals)) => (symbol, decimals, 100),
-                (Ok(symbol), Err(_)) => (symbol, 18, 0),
-                (Err(_), Ok(decimals)) => (address.to_string(), decimals, 0),
-                (Err(_), Err(_)) => (address.to_string(), 18, 0),
-            };
-
-            if let TokenQuality::Bad { reason } = token_quality {
-                warn!(address=?address, ?reason, "BadToken");
-                // Flag this token as bad using quality, an external script is responsible for
-                // analyzing these tokens again.
-                quality = 10;
-            };
-
-            // If quality is 100 but it's a fee token, set quality to 50
-            if quality == 100 && tax.map_or(false, |tax_value| tax_value > 0) {
-                quality = 50;
+    #[test]
+    fn test_score_token_custom_zero_decimals_quality() {
+        let detection = DetectionResult::new(TokenQuality::Good);
+        let score = |config| score_token(&Bytes::from("0x01"), None, 0, &detection, &config);
+
+        let reason = Some("Token without decimals".to_string());
+        assert_eq!(score(QualityConfig::default()), (30, reason.clone()));
+        assert_eq!(score(QualityConfig::default().with_zero_decimals_quality(60)), (60, reason));
+    }
+
+    #[tokio::test]
+    async fn test_map_bounded_preserves_order() {
+        let in_flight = AtomicUsize::new(0);
+        let max_in_flight = AtomicUsize::new(0);
+
+        let res = map_bounded(0..10u64, 3, |i| {
+            let (in_flight, max_in_flight) = (&in_flight, &max_in_flight);
+            async move {
+                let current = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                max_in_flight.fetch_max(current, Ordering::SeqCst);
+                // Later items finish first.
+                tokio::time::sleep(Duration::from_millis(10 * (10 - i))).await;
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+                i
             }
+        })
+        .await;
 
-            tokens_info.push(CurrencyToken {
-                address,
-                symbol: symbol
-                    .replace('\0', "")
-                    .graphemes(true)
-                    .take(255)
-                    .collect::<String>(),
-                decimals: decimals.into(),
-                tax: tax.unwrap_or(0),
-                gas: gas
-                    .map(|g| vec![Some(g)])
-                    .unwrap_or_else(Vec::new),
-                chain: self.chain,
-                quality,
-            });
+        assert_eq!(res, (0..10).collect::<Vec<_>>());
+        assert_eq!(max_in_flight.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_metadata_serial_retries_transient_errors() {
+        let (provider, mock) = Provider::mocked();
+        let abi = from_str::<Abi>(ABI_STR).unwrap();
+        let rate_limited = || {
+            MockResponse::Error(JsonRpcError {
+                code: -32005,
+                message: "rate limit exceeded".to_string(),
+                data: None,
+            })
+        };
+        // The mock returns responses in reverse order: the symbol call fails twice, then all
+        // calls succeed.
+        let decimals: ethers::types::Bytes = encode(&[Token::Uint(U256::from(18))]).into();
+        let symbol: ethers::types::Bytes = encode(&[Token::String("USDC".to_string())]).into();
+        let name: ethers::types::Bytes = encode(&[Token::String("USD Coin".to_string())]).into();
+        let asset = H160::from_str("0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2").unwrap();
+        let vault_asset: ethers::types::Bytes = encode(&[Token::Address(asset)]).into();
+        mock.push::<ethers::types::Bytes, _>(vault_asset)
+            .unwrap();
+        mock.push::<ethers::types::Bytes, _>(name)
+            .unwrap();
+        mock.push::<ethers::types::Bytes, _>(decimals)
+            .unwrap();
+        mock.push::<ethers::types::Bytes, _>(symbol)
+            .unwrap();
+        mock.push_response(rate_limited());
+        mock.push_response(rate_limited());
+        let address = Bytes::from("0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48");
+
+        let metadata = fetch_metadata_serial(
+            &provider,
+            &abi,
+            &parse_abi(VAULT_ABI).unwrap(),
+            &address,
+            &RetryPolicy::new(3, Duration::ZERO),
+            Duration::from_secs(1),
+            None,
+        )
+        .await;
+
+        assert_eq!(
+            metadata,
+            TokenMetadata {
+                symbol: Some("USDC".to_string()),
+                decimals: Some(18),
+                name: Some("USD Coin".to_string()),
+                asset: Some(asset),
+            }
+        );
+        let (quality, reason) = score_token(
+            &address,
+            None,
+            18,
+            &DetectionResult::new(TokenQuality::Good),
+            &QualityConfig::default(),
+        );
+        assert_eq!((quality, reason), (100, None));
+    }
+
+    /// A node that never answers.
+    #[derive(Debug)]
+    struct HangingNode;
+
+    #[async_trait]
+    impl JsonRpcClient for HangingNode {
+        type Error = MockError;
+
+        async fn request<T, R>(&self, _method: &str, _params: T) -> Result<R, MockError>
+        where
+            T: std::fmt::Debug + serde::Serialize + Send + Sync,
+            R: serde::de::DeserializeOwned + Send,
+        {
+            std::future::pending().await
         }
+    }
+
+    #[tokio::test]
+    async fn test_fetch_metadata_serial_times_out() {
+        let provider = Provider::new(HangingNode);
+        let abi = from_str::<Abi>(ABI_STR).unwrap();
 
-        tokens_info
+        let metadata = fetch_metadata_serial(
+            &provider,
+            &abi,
+            &parse_abi(VAULT_ABI).unwrap(),
+            &Bytes::from("0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48"),
+            &RetryPolicy::new(3, Duration::ZERO),
+            Duration::from_millis(10),
+            None,
+        )
+        .await;
+
+        assert_eq!(metadata, TokenMetadata::default());
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::{collections::HashMap, env};
-    use tycho_core::models::token::TokenOwnerStore;
+    #[tokio::test]
+    async fn test_fetch_metadata_serial_does_not_retry_reverts() {
+        let (provider, mock) = Provider::mocked();
+        let abi = from_str::<Abi>(ABI_STR).unwrap();
+        let decimals: ethers::types::Bytes = encode(&[Token::Uint(U256::from(18))]).into();
+        let name: ethers::types::Bytes = encode(&[Token::String("Maker".to_string())]).into();
+        let reverted = || {
+            MockResponse::Error(JsonRpcError {
+                code: 3,
+                message: "execution reverted".to_string(),
+                data: None,
+            })
+        };
+        mock.push_response(reverted());
+        mock.push::<ethers::types::Bytes, _>(name)
+            .unwrap();
+        mock.push::<ethers::types::Bytes, _>(decimals)
+            .unwrap();
+        mock.push_response(reverted());
+
+        let metadata = fetch_metadata_serial(
+            &provider,
+            &abi,
+            &parse_abi(VAULT_ABI).unwrap(),
+            &Bytes::from("0x9f8f72aa9304c8b593d555f12ef6589cc3a579a2"),
+            &RetryPolicy::new(3, Duration::ZERO),
+            Duration::from_secs(1),
+            None,
+        )
+        .await;
+
+        assert_eq!(
+            metadata,
+            TokenMetadata {
+                symbol: None,
+                decimals: Some(18),
+                name: Some("Maker".to_string()),
+                asset: None
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_tokens_cached() {
+        let cache = TokenCache::new();
+        let usdc = Bytes::from("0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48");
+        let token = CurrencyToken::new(&usdc, "USDC", 6, 0, &[], Chain::Ethereum, 100);
+        cache.insert([token.clone()]);
+        // Nothing listens on this port, any RPC call would fail and degrade the token.
+        let processor =
+            EthereumTokenPreProcessor::new_from_url("http://127.0.0.1:1", Chain::Ethereum)
+                .with_retry_policy(RetryPolicy::new(1, Duration::ZERO))
+                .with_token_cache(cache);
+
+        let res = processor
+            .get_tokens(
+                vec![usdc],
+                Arc::new(TokenOwnerStore::new(HashMap::new())),
+                BlockTag::Latest,
+            )
+            .await;
+
+        assert_eq!(res, vec![token]);
+    }
+
+    #[test]
+    fn test_decode_string() {
+        let abi = from_str::<Abi>(ABI_STR).unwrap();
+        let symbol = abi.function("symbol").unwrap();
+        let mut mkr = [0u8; 32];
+        mkr[..3].copy_from_slice(b"MKR");
+
+        assert_eq!(
+            decode_string(symbol, true, &encode(&[Token::String("WETH".to_string())])),
+            Some("WETH".to_string())
+        );
+        assert_eq!(decode_string(symbol, true, &mkr), Some("MKR".to_string()));
+        assert_eq!(decode_string(symbol, true, &[0u8; 32]), None);
+        assert_eq!(decode_string(symbol, false, &mkr), None);
+        assert_eq!(decode_string(symbol, true, &[]), None);
+    }
+
+    #[test]
+    fn test_decode_asset() {
+        let vault_abi = parse_abi(VAULT_ABI).unwrap();
+        let function = vault_abi.function("asset").unwrap();
+        let asset = H160::from_str("0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2").unwrap();
+
+        assert_eq!(decode_asset(function, true, &encode(&[Token::Address(asset)])), Some(asset));
+        assert_eq!(decode_asset(function, true, &encode(&[Token::Address(H160::zero())])), None);
+        assert_eq!(decode_asset(function, false, &encode(&[Token::Address(asset)])), None);
+        assert_eq!(decode_asset(function, true, &[]), None);
+    }
+
+    #[test]
+    fn test_quality_reason_ignored_in_equality() {
+        let token =
+            CurrencyToken::new(&Bytes::from("0x01"), "FEE", 18, 100, &[], Chain::Ethereum, 50);
+        let with_reason =
+            CurrencyToken { quality_reason: Some("Fee token".to_string()), ..token.clone() };
+
+        assert_eq!(token, with_reason);
+    }
+
+    #[tokio::test]
+    #[ignore]
+    // This test requires a real RPC URL
+    async fn test_fetch_metadata_batched_matches_serial() {
+        let archive_rpc = env::var("ARCHIVE_ETH_RPC_URL").expect("ARCHIVE_ETH_RPC_URL is not set");
+        let processor = EthereumTokenPreProcessor::new_from_url(&archive_rpc, Chain::Ethereum);
+        let addresses: Vec<Bytes> = [
+            "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2",
+            "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48",
+            "0xdAC17F958D2ee523a2206206994597C13D831ec7",
+            "0x6B175474E89094C44Da98b954EedeAC495271d0F",
+            "0x2260FAC5E5542a773Aa44fBCfeDf7C193bc2C599",
+            "0xA0b86991c7456b36c1d19D4a2e9Eb0cE3606eB48",
+            "0x9f8F72aA9304c8B593d555F12eF6589cC3A579A2",
+        ]
+        .iter()
+        .map(|address| Bytes::from_str(address).unwrap())
+        .collect();
+
+        let batched = processor
+            .fetch_metadata_batched(&addresses)
+            .await
+            .expect("Multicall3 is deployed on Ethereum");
+        let mut serial = Vec::new();
+        for address in addresses.iter() {
+            serial.push(
+                fetch_metadata_serial(
+                    processor.ethers_client.as_ref(),
+                    &processor.erc20_abi,
+                    &processor.vault_abi,
+                    address,
+                    &processor.retry,
+                    processor.call_timeout,
+                    None,
+                )
+                .await,
+            );
+        }
+
+        assert_eq!(batched, serial);
+        assert_eq!(
+            batched[0],
+            TokenMetadata {
+                symbol: Some("WETH".to_string()),
+                decimals: Some(18),
+                name: Some("Wrapped Ether".to_string()),
+                asset: None
+            }
+        );
+        assert_eq!(batched[5], TokenMetadata::default());
+        assert_eq!(
+            batched[6],
+            TokenMetadata {
+                symbol: Some("MKR".to_string()),
+                decimals: Some(18),
+                name: Some("Maker".to_string()),
+                asset: None
+            }
+        );
+    }
 
     #[tokio::test]
     #[ignore]
@@ -191,17 +1042,24 @@ mod tests {
         let weth_address: &str = "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2";
         let usdc_address: &str = "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48";
         let fake_address: &str = "0xA0b86991c7456b36c1d19D4a2e9Eb0cE3606eB48";
+        let mkr_address: &str = "0x9f8F72aA9304c8B593d555F12eF6589cC3A579A2";
         let addresses = vec![
             Bytes::from_str(weth_address).unwrap(),
             Bytes::from_str(usdc_address).unwrap(),
             Bytes::from_str(fake_address).unwrap(),
+            Bytes::from_str(mkr_address).unwrap(),
         ];
 
         let results = processor
             .get_tokens(addresses, Arc::new(tf), BlockTag::Number(1))
             .await;
-        assert_eq!(results.len(), 3);
-        let relevant_attrs: Vec<(String, u32, u32)> = results
+        assert_eq!(results.len(), 4);
+        // MKR returns its symbol and name as bytes32.
+        assert_eq!((results[3].symbol.as_str(), results[3].decimals), ("MKR", 18));
+        assert_eq!(results[3].name, "Maker");
+        assert_eq!(results[0].name, "Wrapped Ether");
+        assert_eq!(results[1].name, "USD Coin");
+        let relevant_attrs: Vec<(String, u32, u32)> = results[..3]
             .iter()
             .map(|t| (t.symbol.clone(), t.decimals, t.quality))
             .collect();
@@ -214,4 +1072,37 @@ mod tests {
             ]
         );
     }
+    #[tokio::test]
+    #[ignore]
+    // This test requires a real RPC URL
+    async fn test_get_tokens_rebasing() {
+        let archive_rpc = env::var("ARCHIVE_ETH_RPC_URL").expect("ARCHIVE_ETH_RPC_URL is not set");
+        let processor = EthereumTokenPreProcessor::new_from_url(&archive_rpc, Chain::Ethereum);
+        let steth = Bytes::from_str("0xae7ab96520DE3A18E5e111B5EaAb095312D7fE84").unwrap();
+        let weth = Bytes::from_str("0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2").unwrap();
+        // Curve stETH/ETH pool and Uniswap V2 USDC/WETH pair.
+        let owners = TokenOwnerStore::new(HashMap::from([
+            (
+                steth.clone(),
+                (
+                    Bytes::from_str("0xDC24316b9AE028F1497c275EB9192a3Ea0f67022").unwrap(),
+                    U256::exp10(18).to_bytes(),
+                ),
+            ),
+            (
+                weth.clone(),
+                (
+                    Bytes::from_str("0xB4e16d0168e52d35CaCD2c6185b44281Ec28C9Dc").unwrap(),
+                    U256::exp10(18).to_bytes(),
+                ),
+            ),
+        ]));
+
+        let results = processor
+            .get_tokens(vec![steth, weth], Arc::new(owners), BlockTag::Number(19_000_000))
+            .await;
+
+        assert!(results[0].rebasing);
+        assert!(!results[1].rebasing);
+    }
 }
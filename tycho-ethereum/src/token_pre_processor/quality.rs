@@ -0,0 +1,49 @@
+use tycho_core::models::token::TransferTax;
+
+const DEFAULT_GOOD_QUALITY: u32 = 100;
+const DEFAULT_FEE_PENALTY: u32 = 50;
+const DEFAULT_TAX_THRESHOLD: TransferTax = 0;
+const DEFAULT_ZERO_DECIMALS_QUALITY: u32 = 30;
+
+/// Tunes the quality assigned to tokens that were analyzed successfully.
+///
+/// Normal tokens score `good`. Tokens whose transfer tax exceeds `tax_threshold`, given in basis
+/// points, are fee tokens and score `good - fee_penalty`. Tokens without decimals score
+/// `zero_decimals`. The defaults score normal tokens 100, any taxed token 50 and tokens without
+/// decimals 30.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QualityConfig {
+    pub good: u32,
+    pub fee_penalty: u32,
+    pub tax_threshold: TransferTax,
+    pub zero_decimals: u32,
+}
+
+impl Default for QualityConfig {
+    fn default() -> Self {
+        Self::new(DEFAULT_GOOD_QUALITY, DEFAULT_FEE_PENALTY, DEFAULT_TAX_THRESHOLD)
+    }
+}
+
+impl QualityConfig {
+    pub fn new(good: u32, fee_penalty: u32, tax_threshold: TransferTax) -> Self {
+        Self { good, fee_penalty, tax_threshold, zero_decimals: DEFAULT_ZERO_DECIMALS_QUALITY }
+    }
+
+    /// Sets the quality of tokens without decimals.
+    pub fn with_zero_decimals_quality(mut self, quality: u32) -> Self {
+        self.zero_decimals = quality;
+        self
+    }
+
+    /// Returns whether a token with transfer tax `tax` is a fee token.
+    pub fn is_fee_token(&self, tax: TransferTax) -> bool {
+        tax > self.tax_threshold
+    }
+
+    /// Returns the quality of fee tokens.
+    pub fn fee_token_quality(&self) -> u32 {
+        self.good
+            .saturating_sub(self.fee_penalty)
+    }
+}
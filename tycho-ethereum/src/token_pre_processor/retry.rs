@@ -0,0 +1,197 @@
+use std::{fmt::Debug, future::Future, time::Duration};
+
+use ethers::providers::MiddlewareError;
+use tracing::{debug, warn};
+
+const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+const DEFAULT_BASE_DELAY: Duration = Duration::from_millis(250);
+
+/// JSON-RPC error code nodes use for reverted calls.
+const REVERT_ERROR_CODE: i64 = 3;
+
+/// Retries RPC calls that fail with transient errors, e.g. rate limits or timeouts.
+///
+/// A call is attempted at most `max_attempts` times. The n-th retry waits `base_delay * 2^(n-1)`.
+/// Errors that aren't transient are returned right away.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    base_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_ATTEMPTS, DEFAULT_BASE_DELAY)
+    }
+}
+
+impl RetryPolicy {
+    /// Creates a new policy, `max_attempts` below 1 are treated as 1.
+    pub fn new(max_attempts: u32, base_delay: Duration) -> Self {
+        Self { max_attempts: max_attempts.max(1), base_delay }
+    }
+
+    /// Runs `call` until it succeeds, fails with an error that isn't transient or runs out of
+    /// attempts. Returns the result of the last attempt.
+    pub async fn retry<T, E, F, Fut>(
+        &self,
+        is_transient: impl Fn(&E) -> bool,
+        mut call: F,
+    ) -> Result<T, E>
+    where
+        E: Debug,
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let mut attempt = 1;
+        loop {
+            match call().await {
+                Err(e) if attempt < self.max_attempts && is_transient(&e) => {
+                    let delay = self.base_delay * 2u32.saturating_pow(attempt - 1);
+                    debug!(error=?e, attempt, ?delay, "RetryingRpcCall");
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                res => return res,
+            }
+        }
+    }
+}
+
+/// Error of an RPC interaction that is bounded by a timeout.
+#[derive(Debug)]
+pub enum CallError<E> {
+    /// The interaction didn't complete within the given duration.
+    Timeout(Duration),
+    /// The interaction failed.
+    Failed(E),
+}
+
+impl<E> CallError<E> {
+    /// Returns whether the interaction may succeed if retried. Timeouts are final, a contract that
+    /// hangs once is likely to hang again.
+    pub fn is_transient(&self, is_transient: impl Fn(&E) -> bool) -> bool {
+        match self {
+            CallError::Timeout(_) => false,
+            CallError::Failed(e) => is_transient(e),
+        }
+    }
+}
+
+/// Awaits `call` for at most `timeout`.
+pub async fn with_timeout<T, E>(
+    timeout: Duration,
+    call: impl Future<Output = Result<T, E>>,
+) -> Result<T, CallError<E>> {
+    match tokio::time::timeout(timeout, call).await {
+        Ok(res) => res.map_err(CallError::Failed),
+        Err(_) => {
+            warn!(?timeout, "RpcCallTimeout");
+            Err(CallError::Timeout(timeout))
+        }
+    }
+}
+
+/// Returns whether a failed RPC call may succeed if retried.
+///
+/// Reverts are reported as JSON-RPC error responses with code 3 or a message mentioning the
+/// revert, they are final. Any other failure, e.g. a transport error, an HTTP 429 or a node side
+/// timeout, is considered transient.
+pub fn is_transient<E: MiddlewareError>(err: &E) -> bool {
+    match err.as_error_response() {
+        Some(response) => !is_revert(response.code, &response.message),
+        None => true,
+    }
+}
+
+/// Returns whether a failed web3 call may succeed if retried, see [`is_transient`].
+///
+/// Responses that can't be decoded are final as well.
+pub fn is_transient_web3(err: &web3::Error) -> bool {
+    match err {
+        web3::Error::Rpc(response) => !is_revert(response.code.code(), &response.message),
+        web3::Error::Unreachable | web3::Error::Transport(_) | web3::Error::Io(_) => true,
+        _ => false,
+    }
+}
+
+fn is_revert(code: i64, message: &str) -> bool {
+    code == REVERT_ERROR_CODE || message.contains("revert")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_retry_transient_errors() {
+        let attempts = &AtomicU32::new(0);
+        let policy = RetryPolicy::new(3, Duration::ZERO);
+
+        let res = policy
+            .retry(
+                |e: &&str| *e == "timeout",
+                || async move {
+                    match attempts.fetch_add(1, Ordering::SeqCst) {
+                        0 | 1 => Err("timeout"),
+                        _ => Ok(42),
+                    }
+                },
+            )
+            .await;
+
+        assert_eq!(res, Ok(42));
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_retry_stops_on_final_error() {
+        let attempts = &AtomicU32::new(0);
+        let policy = RetryPolicy::new(5, Duration::ZERO);
+
+        let res: Result<(), _> = policy
+            .retry(
+                |e: &&str| *e == "timeout",
+                || async move {
+                    attempts.fetch_add(1, Ordering::SeqCst);
+                    Err("execution reverted")
+                },
+            )
+            .await;
+
+        assert_eq!(res, Err("execution reverted"));
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_retry_gives_up_after_max_attempts() {
+        let attempts = &AtomicU32::new(0);
+        let policy = RetryPolicy::new(2, Duration::ZERO);
+
+        let res: Result<(), _> = policy
+            .retry(
+                |_: &&str| true,
+                || async move {
+                    attempts.fetch_add(1, Ordering::SeqCst);
+                    Err("timeout")
+                },
+            )
+            .await;
+
+        assert_eq!(res, Err("timeout"));
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_with_timeout() {
+        let timeout = Duration::from_millis(10);
+
+        let hanging = with_timeout(timeout, std::future::pending::<Result<(), ()>>()).await;
+        let ready = with_timeout(timeout, async { Ok::<_, ()>(1) }).await;
+
+        assert!(matches!(hanging, Err(CallError::Timeout(t)) if t == timeout));
+        assert!(matches!(ready, Ok(1)));
+    }
+}
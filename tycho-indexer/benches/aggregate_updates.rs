@@ -0,0 +1,79 @@
+use std::collections::{HashMap, HashSet};
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use tycho_core::{
+    models::{
+        blockchain::{Block, Transaction, TxWithChanges},
+        contract::AccountDelta,
+        protocol::ProtocolComponentStateDelta,
+        Chain, ChangeType,
+    },
+    Bytes,
+};
+use tycho_indexer::extractor::models::BlockChanges;
+
+/// Builds a block of 1000 transactions, each updating 10 of 500 accounts and components, so
+/// 10k account and 10k state updates are aggregated in total.
+fn large_block() -> BlockChanges {
+    let block = Block::default();
+    let tx_update = |index: u64| {
+        let updates = (0..10u64).map(|i| (index * 7 + i) % 500);
+        let account_deltas = updates
+            .clone()
+            .map(|a| {
+                let address = Bytes::from(a).lpad(20, 0);
+                let slot = Bytes::from((a + index) % 16).lpad(32, 0);
+                let delta = AccountDelta::new(
+                    Chain::Ethereum,
+                    address.clone(),
+                    HashMap::from([(slot, Some(Bytes::from(index).lpad(32, 0)))]),
+                    Some(Bytes::from(index)),
+                    None,
+                    ChangeType::Update,
+                );
+                (address, delta)
+            })
+            .collect();
+        let state_updates = updates
+            .map(|c| {
+                let id = format!("pc_{c}");
+                let delta = ProtocolComponentStateDelta::new(
+                    &id,
+                    HashMap::from([(format!("attr_{}", index % 3), Bytes::from(index))]),
+                    HashSet::new(),
+                );
+                (id, delta)
+            })
+            .collect();
+        let tx = Transaction::new(
+            Bytes::from(index + 1).lpad(32, 0),
+            block.hash.clone(),
+            Bytes::zero(20),
+            Some(Bytes::zero(20)),
+            index,
+        );
+        TxWithChanges::new(HashMap::new(), account_deltas, state_updates, HashMap::new(), tx)
+    };
+    BlockChanges::new(
+        "bench".to_string(),
+        Chain::Ethereum,
+        block.clone(),
+        0,
+        false,
+        (0..1000).map(tx_update).collect(),
+    )
+}
+
+fn aggregate_updates(c: &mut Criterion) {
+    let block = large_block();
+    c.bench_function("aggregate_updates_10k", |b| {
+        b.iter_batched(
+            || block.clone(),
+            |block| block.aggregate_updates(false).unwrap(),
+            BatchSize::LargeInput,
+        )
+    });
+}
+
+criterion_group!(benches, aggregate_updates);
+criterion_main!(benches);
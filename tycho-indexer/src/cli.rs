@@ -1,3 +1,5 @@
+use std::num::NonZeroU32;
+
 use clap::{Args, Parser, Subcommand};
 
 use tycho_core::{models::Chain, Bytes};
@@ -77,6 +79,11 @@ pub struct SubstreamsArgs {
     #[clap(env, long)]
     pub rpc_url: String,
 
+    /// Maximum number of rpc requests per second made while fetching token information,
+    /// unlimited if unset
+    #[clap(env, long)]
+    pub rpc_requests_per_second: Option<NonZeroU32>,
+
     /// Substreams API token
     #[clap(long, env, hide_env_values = true, alias = "api_token")]
     pub substreams_api_token: String,
@@ -229,6 +236,7 @@ mod cli_tests {
                 stop_block: None,
                 substreams_args: SubstreamsArgs {
                     rpc_url: "http://example.com".to_string(),
+                    rpc_requests_per_second: None,
                     substreams_api_token: "your_api_token".to_string(),
                 },
                 initialized_accounts: vec![],
@@ -269,6 +277,7 @@ mod cli_tests {
             command: Command::Index(IndexArgs {
                 substreams_args: SubstreamsArgs {
                     rpc_url: "http://example.com".to_string(),
+                    rpc_requests_per_second: None,
                     substreams_api_token: "your_api_token".to_string(),
                 },
                 chains: vec!["ethereum".to_string()],
@@ -0,0 +1,78 @@
+use crate::pb::sf::substreams::rpc::v2::BlockScopedData;
+
+/// Restricts extraction to an inclusive range of block numbers.
+///
+/// Useful to process only the blocks around an incident while backfilling. A missing bound
+/// leaves the range open on that side, so the default filter accepts all blocks. Blocks outside
+/// the range are skipped before they are decoded.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct BlockRangeFilter {
+    pub from: Option<u64>,
+    pub to: Option<u64>,
+}
+
+impl BlockRangeFilter {
+    pub fn new(from: Option<u64>, to: Option<u64>) -> Self {
+        Self { from, to }
+    }
+
+    /// Returns whether `number` lies within the range, bounds included.
+    pub fn contains(&self, number: u64) -> bool {
+        self.from
+            .is_none_or(|from| number >= from) &&
+            self.to.is_none_or(|to| number <= to)
+    }
+
+    /// Returns whether the block of `inp` should be processed.
+    ///
+    /// Messages without a clock are always accepted, decoding reports them as malformed.
+    pub fn accepts(&self, inp: &BlockScopedData) -> bool {
+        inp.clock
+            .as_ref()
+            .is_none_or(|clock| self.contains(clock.number))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rstest::rstest;
+
+    use crate::pb::sf::substreams::v1::Clock;
+
+    use super::*;
+
+    #[rstest]
+    #[case::below(14_999_999, false)]
+    #[case::lower_bound(15_000_000, true)]
+    #[case::inside(15_000_500, true)]
+    #[case::upper_bound(15_001_000, true)]
+    #[case::above(15_001_001, false)]
+    fn test_contains_inclusive_bounds(#[case] number: u64, #[case] expected: bool) {
+        let filter = BlockRangeFilter::new(Some(15_000_000), Some(15_001_000));
+
+        assert_eq!(filter.contains(number), expected);
+    }
+
+    #[rstest]
+    #[case::below(14_999_999, false)]
+    #[case::lower_bound(15_000_000, true)]
+    #[case::far_above(u64::MAX, true)]
+    fn test_contains_open_ended(#[case] number: u64, #[case] expected: bool) {
+        let filter = BlockRangeFilter::new(Some(15_000_000), None);
+
+        assert_eq!(filter.contains(number), expected);
+    }
+
+    #[test]
+    fn test_accepts() {
+        let filter = BlockRangeFilter::new(None, Some(10));
+        let data = |number| BlockScopedData {
+            clock: Some(Clock { number, ..Default::default() }),
+            ..Default::default()
+        };
+
+        assert!(filter.accepts(&data(10)));
+        assert!(!filter.accepts(&data(11)));
+        assert!(filter.accepts(&BlockScopedData::default()));
+    }
+}
@@ -0,0 +1,153 @@
+use std::collections::{HashMap, VecDeque};
+
+use tycho_core::models::{protocol::ProtocolComponent, ChangeType, ComponentId};
+
+use crate::extractor::models::BlockChanges;
+
+/// Suppresses component creations that were already emitted recently.
+///
+/// Some substreams re-report the creation of components in later blocks. Creations are
+/// remembered by component id and compared by content, so a re-reported creation is dropped while
+/// a creation of the same component with different content passes. Only the last `capacity`
+/// distinct components are remembered.
+#[derive(Debug)]
+pub struct ComponentDedupeCache {
+    capacity: usize,
+    order: VecDeque<ComponentId>,
+    seen: HashMap<ComponentId, ProtocolComponent>,
+}
+
+impl ComponentDedupeCache {
+    pub fn new(capacity: usize) -> Self {
+        Self { capacity, order: VecDeque::with_capacity(capacity), seen: HashMap::new() }
+    }
+
+    /// Records `component` as seen and returns whether it wasn't seen before with the same
+    /// content.
+    pub fn insert(&mut self, component: &ProtocolComponent) -> bool {
+        let content = creation_content(component);
+        if self.seen.get(&component.id) == Some(&content) {
+            return false;
+        }
+        if self
+            .seen
+            .insert(component.id.clone(), content)
+            .is_none()
+        {
+            self.order
+                .push_back(component.id.clone());
+        }
+        if self.order.len() > self.capacity {
+            if let Some(evicted) = self.order.pop_front() {
+                self.seen.remove(&evicted);
+            }
+        }
+        true
+    }
+
+    /// Removes creations from `changes` that were already seen, either in a previous block or in
+    /// an earlier transaction of the same block.
+    pub fn filter(&mut self, changes: &mut BlockChanges) {
+        for tx in changes.txs_with_update.iter_mut() {
+            tx.protocol_components
+                .retain(|_, component| {
+                    component.change != ChangeType::Creation || self.insert(component)
+                });
+        }
+    }
+
+    /// Forgets all seen creations, e.g. after a revert undid some of them.
+    pub fn clear(&mut self) {
+        self.order.clear();
+        self.seen.clear();
+    }
+}
+
+/// Returns the part of a component that identifies its creation.
+///
+/// The creation transaction and timestamp are excluded, as a re-reported creation carries the
+/// transaction it was re-reported in.
+fn creation_content(component: &ProtocolComponent) -> ProtocolComponent {
+    ProtocolComponent {
+        creation_tx: Default::default(),
+        created_at: Default::default(),
+        ..component.clone()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use tycho_core::{
+        models::{
+            blockchain::{Block, Transaction, TxWithChanges},
+            Chain,
+        },
+        Bytes,
+    };
+
+    use super::*;
+
+    fn block_with_component(number: u64, fee: u64) -> BlockChanges {
+        let component = ProtocolComponent::builder("pc_1", "ambient", "pool", Chain::Ethereum)
+            .tokens(vec![Bytes::from("0x01"), Bytes::from("0x02")])
+            .static_attributes(HashMap::from([("fee".to_string(), Bytes::from(fee))]))
+            .change(ChangeType::Creation)
+            .creation_tx(Bytes::from(number))
+            .build()
+            .unwrap();
+        BlockChanges::new(
+            "test".to_string(),
+            Chain::Ethereum,
+            Block { number, ..Default::default() },
+            0,
+            false,
+            vec![TxWithChanges::new(
+                HashMap::from([(component.id.clone(), component)]),
+                HashMap::new(),
+                HashMap::new(),
+                HashMap::new(),
+                Transaction::default(),
+            )],
+        )
+    }
+
+    #[test]
+    fn test_filter_duplicate_creations() {
+        let mut cache = ComponentDedupeCache::new(10);
+        let mut first = block_with_component(1, 3000);
+        let mut duplicate = block_with_component(2, 3000);
+        let mut changed = block_with_component(3, 500);
+
+        cache.filter(&mut first);
+        cache.filter(&mut duplicate);
+        cache.filter(&mut changed);
+
+        assert_eq!(first.protocol_components().len(), 1);
+        assert!(duplicate
+            .protocol_components()
+            .is_empty());
+        assert_eq!(changed.protocol_components().len(), 1);
+    }
+
+    #[test]
+    fn test_filter_duplicate_creations_within_block() {
+        let mut cache = ComponentDedupeCache::new(10);
+        let mut block = block_with_component(1, 3000);
+        let tx = block.txs_with_update[0].clone();
+        block.txs_with_update.push(tx);
+
+        cache.filter(&mut block);
+
+        assert_eq!(
+            block.txs_with_update[0]
+                .protocol_components
+                .len(),
+            1
+        );
+        assert!(block.txs_with_update[1]
+            .protocol_components
+            .is_empty());
+    }
+}
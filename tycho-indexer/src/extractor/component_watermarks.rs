@@ -0,0 +1,154 @@
+use std::collections::{HashMap, HashSet};
+
+use tycho_core::models::{Address, ComponentId};
+
+use crate::extractor::models::BlockChanges;
+
+/// Tracks the last block in which each component changed, to spot stale components.
+///
+/// A component changes with its creation and with any state, balance or account update.
+/// Account updates are attributed to the components whose contracts they touch. Contracts are
+/// learned from component creations, so account updates of components created before tracking
+/// started are not attributed.
+#[derive(Debug, Default)]
+pub struct ComponentWatermarks {
+    last_update: HashMap<ComponentId, u64>,
+    contracts: HashMap<Address, HashSet<ComponentId>>,
+}
+
+impl ComponentWatermarks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the components changed in `changes` as updated at its block.
+    pub fn update(&mut self, changes: &BlockChanges) {
+        let number = changes.block.number;
+        for tx in changes.txs_with_update.iter() {
+            for component in tx.protocol_components.values() {
+                for address in component.contract_addresses.iter() {
+                    self.contracts
+                        .entry(address.clone())
+                        .or_default()
+                        .insert(component.id.clone());
+                }
+            }
+            let updated = tx
+                .protocol_components
+                .keys()
+                .chain(tx.state_updates.keys())
+                .chain(tx.balance_changes.keys())
+                .chain(
+                    tx.account_deltas
+                        .keys()
+                        .filter_map(|address| self.contracts.get(address))
+                        .flatten(),
+                )
+                .cloned()
+                .collect::<Vec<_>>();
+            for id in updated {
+                self.last_update.insert(id, number);
+            }
+        }
+    }
+
+    /// Returns the last block in which the component `id` changed, if it was seen.
+    pub fn last_update(&self, id: &str) -> Option<u64> {
+        self.last_update.get(id).copied()
+    }
+
+    /// Returns the ids of components that didn't change in the last `threshold` blocks before
+    /// `current_block`, sorted.
+    pub fn stale_components(&self, current_block: u64, threshold: u64) -> Vec<ComponentId> {
+        let mut stale: Vec<_> = self
+            .last_update
+            .iter()
+            .filter(|(_, last)| current_block.saturating_sub(**last) > threshold)
+            .map(|(id, _)| id.clone())
+            .collect();
+        stale.sort_unstable();
+        stale
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tycho_core::{
+        models::{
+            blockchain::{Block, TxWithChanges},
+            contract::AccountDelta,
+            protocol::{ProtocolComponent, ProtocolComponentStateDelta},
+            Chain, ChangeType,
+        },
+        Bytes,
+    };
+
+    use super::*;
+
+    fn block(number: u64, txs: Vec<TxWithChanges>) -> BlockChanges {
+        let block = Block { number, ..Default::default() };
+        BlockChanges::new("test".to_string(), Chain::Ethereum, block, 0, false, txs)
+    }
+
+    #[test]
+    fn test_stale_components() {
+        let contract = Bytes::from("0x01");
+        let token = Bytes::from("0x02");
+        let vm_pool = ProtocolComponent::builder("vm_pool", "vm", "pool", Chain::Ethereum)
+            .tokens(vec![token.clone()])
+            .contract_addresses(vec![contract.clone()])
+            .change(ChangeType::Creation)
+            .build()
+            .unwrap();
+        let native_pool =
+            ProtocolComponent::builder("native_pool", "ambient", "pool", Chain::Ethereum)
+                .tokens(vec![token])
+                .change(ChangeType::Creation)
+                .build()
+                .unwrap();
+        let mut watermarks = ComponentWatermarks::new();
+
+        watermarks.update(&block(
+            1,
+            vec![TxWithChanges {
+                protocol_components: HashMap::from([
+                    ("vm_pool".to_string(), vm_pool),
+                    ("native_pool".to_string(), native_pool),
+                ]),
+                ..Default::default()
+            }],
+        ));
+        watermarks.update(&block(
+            5,
+            vec![TxWithChanges {
+                account_deltas: HashMap::from([(
+                    contract.clone(),
+                    AccountDelta::new(
+                        Chain::Ethereum,
+                        contract,
+                        HashMap::new(),
+                        Some(Bytes::from(1u64)),
+                        None,
+                        ChangeType::Update,
+                    ),
+                )]),
+                ..Default::default()
+            }],
+        ));
+        watermarks.update(&block(
+            8,
+            vec![TxWithChanges {
+                state_updates: HashMap::from([(
+                    "unknown".to_string(),
+                    ProtocolComponentStateDelta::new("unknown", HashMap::new(), HashSet::new()),
+                )]),
+                ..Default::default()
+            }],
+        ));
+
+        assert_eq!(watermarks.last_update("vm_pool"), Some(5));
+        assert_eq!(watermarks.last_update("native_pool"), Some(1));
+        assert_eq!(watermarks.stale_components(10, 5), vec!["native_pool".to_string()]);
+        assert_eq!(watermarks.stale_components(20, 5).len(), 3);
+    }
+}
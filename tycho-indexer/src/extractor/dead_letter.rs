@@ -0,0 +1,188 @@
+use std::{fs::OpenOptions, io::Write, path::PathBuf, sync::Mutex};
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use tycho_core::Bytes;
+
+use crate::{
+    extractor::{models::BlockChanges, ExtractionError},
+    pb::sf::substreams::rpc::v2::BlockScopedData,
+};
+
+/// A block that failed to decode, kept for later inspection or replay.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeadLetter {
+    pub block_number: u64,
+    pub cursor: String,
+    /// The raw map output payload of the block, empty if the block had no map output.
+    pub raw_bytes: Bytes,
+    pub error: String,
+}
+
+impl DeadLetter {
+    fn new(inp: &BlockScopedData, error: &ExtractionError) -> Self {
+        Self {
+            block_number: inp
+                .clock
+                .as_ref()
+                .map_or(0, |clock| clock.number),
+            cursor: inp.cursor.clone(),
+            raw_bytes: inp
+                .output
+                .as_ref()
+                .and_then(|output| output.map_output.as_ref())
+                .map(|data| Bytes::from(data.value.clone()))
+                .unwrap_or_default(),
+            error: error.to_string(),
+        }
+    }
+}
+
+/// Destination for blocks that failed to decode.
+pub trait DeadLetterSink: Send + Sync {
+    fn write(&self, letter: DeadLetter) -> Result<(), ExtractionError>;
+}
+
+/// Keeps dead letters in memory.
+#[derive(Debug, Default)]
+pub struct InMemoryDeadLetterSink {
+    letters: Mutex<Vec<DeadLetter>>,
+}
+
+impl InMemoryDeadLetterSink {
+    /// Returns all dead letters written so far, oldest first.
+    pub fn letters(&self) -> Vec<DeadLetter> {
+        self.letters
+            .lock()
+            .expect("dead letter lock poisoned")
+            .clone()
+    }
+}
+
+impl DeadLetterSink for InMemoryDeadLetterSink {
+    fn write(&self, letter: DeadLetter) -> Result<(), ExtractionError> {
+        self.letters
+            .lock()
+            .expect("dead letter lock poisoned")
+            .push(letter);
+        Ok(())
+    }
+}
+
+/// Appends dead letters to a file, one JSON object per line.
+#[derive(Debug)]
+pub struct FileDeadLetterSink {
+    path: PathBuf,
+}
+
+impl FileDeadLetterSink {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl DeadLetterSink for FileDeadLetterSink {
+    fn write(&self, letter: DeadLetter) -> Result<(), ExtractionError> {
+        let mut line =
+            serde_json::to_vec(&letter).map_err(|e| ExtractionError::Unknown(e.to_string()))?;
+        line.push(b'\n');
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut file| file.write_all(&line))
+            .map_err(|e| ExtractionError::Unknown(e.to_string()))
+    }
+}
+
+/// Decodes `inp`, routing it to `sink` instead of failing if it can't be parsed.
+///
+/// Returns `Ok(None)` if the block was dead lettered, so the caller can continue with the next
+/// block. Only parse failures are dead lettered, other errors such as `ExtractionError::Empty`
+/// are returned as is.
+///
+/// # Errors
+///
+/// Returns an error if decoding fails for another reason than a malformed payload, or if the
+/// dead letter can't be written.
+pub fn extract_or_dead_letter<F>(
+    inp: &BlockScopedData,
+    decode: F,
+    sink: &dyn DeadLetterSink,
+) -> Result<Option<BlockChanges>, ExtractionError>
+where
+    F: FnOnce(&BlockScopedData) -> Result<BlockChanges, ExtractionError>,
+{
+    match decode(inp) {
+        Ok(changes) => Ok(Some(changes)),
+        Err(e @ (ExtractionError::DecodeError(_) | ExtractionError::ProtobufError(_))) => {
+            let letter = DeadLetter::new(inp, &e);
+            warn!(block_number = letter.block_number, error = %e, "DeadLetteringBlock");
+            sink.write(letter)?;
+            Ok(None)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use prost::Message;
+
+    use tycho_core::models::{Chain, ProtocolType};
+
+    use super::*;
+    use crate::{
+        extractor::{
+            models::fixtures::block_state_changes,
+            protobuf_deserialisation::{decode_block_scoped, DecodeOptions},
+        },
+        pb::testing::fixtures,
+    };
+
+    fn scoped_data(value: Vec<u8>, cursor: &str) -> BlockScopedData {
+        let mut data = fixtures::pb_block_scoped_data((), Some(cursor), Some(0));
+        let output = data
+            .output
+            .as_mut()
+            .unwrap()
+            .map_output
+            .as_mut()
+            .unwrap();
+        output.type_url = "type.googleapis.com/tycho.evm.v1.BlockContractChanges".to_owned();
+        output.value = value;
+        data
+    }
+
+    #[test]
+    fn test_extract_or_dead_letter() {
+        let sink = InMemoryDeadLetterSink::default();
+        let protocol_types = HashMap::from([("WeightedPool".to_string(), ProtocolType::default())]);
+        let decode = |inp: &BlockScopedData| {
+            decode_block_scoped(
+                inp,
+                "test",
+                Chain::Ethereum,
+                "ambient",
+                &protocol_types,
+                &DecodeOptions::default(),
+            )
+        };
+        let good = scoped_data(fixtures::pb_block_contract_changes(0).encode_to_vec(), "cursor@1");
+        let bad = scoped_data(vec![0xff], "cursor@2");
+
+        let good_res = extract_or_dead_letter(&good, decode, &sink).unwrap();
+        let bad_res = extract_or_dead_letter(&bad, decode, &sink).unwrap();
+
+        assert_eq!(good_res, Some(block_state_changes().into()));
+        assert_eq!(bad_res, None);
+        let letters = sink.letters();
+        assert_eq!(letters.len(), 1);
+        assert_eq!(letters[0].block_number, 420);
+        assert_eq!(letters[0].cursor, "cursor@2");
+        assert_eq!(letters[0].raw_bytes, Bytes::from(vec![0xff]));
+    }
+}
@@ -0,0 +1,139 @@
+use std::{collections::HashMap, sync::Arc};
+
+use tracing::debug;
+
+use tycho_core::models::{blockchain::BlockAggregatedChanges, protocol::ComponentBalance, Address};
+
+/// Returns the price of one unit of a token in the reference currency, `None` if unknown.
+pub type PriceFn = dyn Fn(&Address) -> Option<f64> + Send + Sync;
+
+/// Drops newly created components whose liquidity is below a threshold.
+///
+/// Scam and dust pools often hold negligible liquidity. A created component is valued at the
+/// sum of its balances in the creation block, each priced by the price function. Balances of
+/// tokens without a price don't count towards the liquidity.
+///
+/// Only creations are filtered, so later updates of a dropped component are still emitted and
+/// have to be ignored by consumers that don't know the component.
+#[derive(Clone)]
+pub struct MinLiquidityFilter {
+    threshold: f64,
+    price: Arc<PriceFn>,
+}
+
+impl MinLiquidityFilter {
+    pub fn new<F>(threshold: f64, price: F) -> Self
+    where
+        F: Fn(&Address) -> Option<f64> + Send + Sync + 'static,
+    {
+        Self { threshold, price: Arc::new(price) }
+    }
+
+    /// Removes created components below the threshold, along with their state, balances and tvl.
+    pub fn apply(&self, changes: &mut BlockAggregatedChanges) {
+        let dust = changes
+            .new_protocol_components
+            .keys()
+            .filter(|id| {
+                let liquidity = changes
+                    .component_balances
+                    .get(*id)
+                    .map_or(0.0, |balances| self.liquidity(balances));
+                liquidity < self.threshold
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+
+        for id in dust {
+            debug!(component_id = %id, "DroppingLowLiquidityComponent");
+            changes
+                .new_protocol_components
+                .remove(&id);
+            changes.state_deltas.remove(&id);
+            changes.component_balances.remove(&id);
+            changes.component_tvl.remove(&id);
+        }
+    }
+
+    fn liquidity(&self, balances: &HashMap<Address, ComponentBalance>) -> f64 {
+        balances
+            .iter()
+            .filter_map(|(token, balance)| Some(balance.balance_float * (self.price)(token)?))
+            .sum()
+    }
+}
+
+impl std::fmt::Debug for MinLiquidityFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MinLiquidityFilter")
+            .field("threshold", &self.threshold)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tycho_core::{
+        models::{protocol::ProtocolComponent, Chain, ChangeType},
+        Bytes,
+    };
+
+    use super::*;
+
+    fn component_with_balance(id: &str, token: &Address, balance: f64) -> BlockAggregatedChanges {
+        let component = ProtocolComponent::builder(id, "ambient", "pool", Chain::Ethereum)
+            .tokens(vec![token.clone()])
+            .change(ChangeType::Creation)
+            .build()
+            .unwrap();
+        BlockAggregatedChanges {
+            new_protocol_components: HashMap::from([(id.to_string(), component)]),
+            component_balances: HashMap::from([(
+                id.to_string(),
+                HashMap::from([(
+                    token.clone(),
+                    ComponentBalance::new(
+                        token.clone(),
+                        Bytes::from(balance as u64),
+                        balance,
+                        Bytes::zero(32),
+                        id,
+                    ),
+                )]),
+            )]),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_drops_dust_component() {
+        let token = Bytes::from("0x01");
+        let priced = token.clone();
+        let filter = MinLiquidityFilter::new(1000.0, move |t| (*t == priced).then_some(2.0));
+        let mut changes = component_with_balance("dust", &token, 10.0);
+        let liquid = component_with_balance("liquid", &token, 1000.0);
+        changes
+            .new_protocol_components
+            .extend(liquid.new_protocol_components);
+        changes
+            .component_balances
+            .extend(liquid.component_balances);
+
+        filter.apply(&mut changes);
+
+        assert_eq!(
+            changes
+                .new_protocol_components
+                .keys()
+                .collect::<Vec<_>>(),
+            vec!["liquid"]
+        );
+        assert_eq!(
+            changes
+                .component_balances
+                .keys()
+                .collect::<Vec<_>>(),
+            vec!["liquid"]
+        );
+    }
+}
@@ -22,15 +22,24 @@ use crate::{
     pb::sf::substreams::rpc::v2::{BlockScopedData, BlockUndoSignal, ModulesProgress},
 };
 
+pub mod block_range;
 pub mod chain_state;
+pub mod component_dedupe;
+pub mod component_watermarks;
+pub mod dead_letter;
+pub mod liquidity_filter;
 pub mod models;
+pub mod parallel_decoder;
 pub mod post_processors;
 pub mod protobuf_deserialisation;
 pub mod protocol_cache;
 pub mod protocol_extractor;
 pub mod reorg_buffer;
+pub mod resume;
 pub mod runner;
 pub mod token_analysis_cron;
+pub mod token_pipeline;
+pub mod tvl_converter;
 mod u256_num;
 
 #[derive(Error, Debug, PartialEq)]
@@ -53,6 +62,10 @@ pub enum ExtractionError {
     ServiceError(String),
     #[error("Merge error: {0}")]
     MergeError(String),
+    #[error("Account {account} updated more than once by transaction {tx}")]
+    DuplicateAccountUpdate { account: Bytes, tx: Bytes },
+    #[error("Protocol component {0} has an unspecified change type")]
+    UnspecifiedChangeType(String),
     #[error("Reorg buffer error: {0}")]
     ReorgBufferError(String),
 }
@@ -78,11 +91,24 @@ pub trait Extractor: Send + Sync {
 
     async fn get_last_processed_block(&self) -> Option<Block>;
 
+    /// Decodes the changes of a block.
+    ///
+    /// Decoding doesn't depend on the extractor state, so blocks can be decoded ahead of being
+    /// handled, see [`parallel_decoder::ParallelDecoder`].
+    fn decode_block(&self, inp: &BlockScopedData) -> Result<models::BlockChanges, ExtractionError>;
+
     async fn handle_tick_scoped_data(
         &self,
         inp: BlockScopedData,
     ) -> Result<Option<ExtractorMsg>, ExtractionError>;
 
+    /// Handles a block whose changes were decoded ahead of time by [`Extractor::decode_block`].
+    async fn handle_decoded_tick(
+        &self,
+        inp: BlockScopedData,
+        changes: Result<models::BlockChanges, ExtractionError>,
+    ) -> Result<Option<ExtractorMsg>, ExtractionError>;
+
     async fn handle_revert(
         &self,
         inp: BlockUndoSignal,
@@ -91,22 +117,28 @@ pub trait Extractor: Send + Sync {
     async fn handle_progress(&self, inp: ModulesProgress) -> Result<(), ExtractionError>;
 }
 
-/// Wrapper to carry a cursor along with another struct.
+/// Wrapper to carry a cursor and the sequence number of the emitted message along with another
+/// struct.
 #[derive(Debug)]
 pub(crate) struct BlockUpdateWithCursor<B: std::fmt::Debug> {
     block_update: B,
     cursor: String,
+    sequence: u64,
 }
 
 impl<B: std::fmt::Debug> BlockUpdateWithCursor<B> {
-    pub(crate) fn new(block_update: B, cursor: String) -> Self {
-        Self { block_update, cursor }
+    pub(crate) fn new(block_update: B, cursor: String, sequence: u64) -> Self {
+        Self { block_update, cursor, sequence }
     }
 
     pub(crate) fn cursor(&self) -> &String {
         &self.cursor
     }
 
+    pub(crate) fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
     pub(crate) fn block_update(&self) -> &B {
         &self.block_update
     }
@@ -6,7 +6,7 @@ use tycho_core::{
         contract::TransactionVMUpdates,
         protocol::{ComponentBalance, ProtocolChangesWithTx, ProtocolComponent},
         token::CurrencyToken,
-        Address, AttrStoreKey, Chain, ComponentId,
+        Address, AttrStoreKey, Chain, ChangeType, ComponentId,
     },
     Bytes,
 };
@@ -121,6 +121,24 @@ impl BlockEntityChanges {
             })
             .collect()
     }
+
+    /// Returns the changes of the component `id` within this block as `(tx_index, change)`
+    /// pairs, in transaction order.
+    ///
+    /// A transaction that only updates the component's state counts as an update.
+    pub fn component_history(&self, id: &str) -> Vec<(u64, ChangeType)> {
+        self.txs_with_update
+            .iter()
+            .filter_map(|tx_u| {
+                let change = match tx_u.new_protocol_components.get(id) {
+                    Some(component) => component.change,
+                    None if tx_u.protocol_states.contains_key(id) => ChangeType::Update,
+                    None => return None,
+                };
+                Some((tx_u.tx.index, change))
+            })
+            .collect()
+    }
 }
 
 impl BlockScoped for BlockEntityChanges {
@@ -129,7 +147,7 @@ impl BlockScoped for BlockEntityChanges {
     }
 }
 
-#[derive(Debug, PartialEq, Default, Clone)]
+#[derive(Debug, Default, Clone)]
 pub struct BlockChanges {
     extractor: String,
     chain: Chain,
@@ -141,6 +159,22 @@ pub struct BlockChanges {
     pub new_tokens: HashMap<Address, CurrencyToken>,
     /// Vec of updates at this block, aggregated by tx and sorted by tx index in ascending order
     pub txs_with_update: Vec<TxWithChanges>,
+    /// Keccak-256 hash of the raw substreams payload this block was decoded from, if requested.
+    ///
+    /// Allows auditing decoded messages against the original payload. Not part of equality.
+    pub raw_payload_hash: Option<Bytes>,
+}
+
+impl PartialEq for BlockChanges {
+    fn eq(&self, other: &Self) -> bool {
+        self.extractor == other.extractor &&
+            self.chain == other.chain &&
+            self.block == other.block &&
+            self.finalized_block_height == other.finalized_block_height &&
+            self.revert == other.revert &&
+            self.new_tokens == other.new_tokens &&
+            self.txs_with_update == other.txs_with_update
+    }
 }
 
 impl BlockChanges {
@@ -160,6 +194,7 @@ impl BlockChanges {
             revert,
             new_tokens: HashMap::new(),
             txs_with_update,
+            raw_payload_hash: None,
         }
     }
 
@@ -173,24 +208,66 @@ impl BlockChanges {
     /// After merging all updates, a [`AggregatedBlockChanges`] object is returned
     /// which contains, amongst other data, the compacted state updates.
     ///
+    /// If `drop_empty_states` is set, state deltas that neither update nor delete any attribute
+    /// after merging are removed from the result.
+    ///
     /// # Errors
     ///
-    /// This returns an error if there was a problem during merge. The error
-    /// type is `ExtractionError`.
-    pub fn aggregate_updates(self) -> Result<BlockAggregatedChanges, ExtractionError> {
+    /// This returns an error if the same account is updated by more than one entry of the same
+    /// transaction, if a protocol component has an unspecified change type, or if there was a
+    /// problem during merge. The error type is `ExtractionError`.
+    pub fn aggregate_updates(
+        self,
+        drop_empty_states: bool,
+    ) -> Result<BlockAggregatedChanges, ExtractionError> {
+        self.validate_unique_account_updates()?;
+
         let mut iter = self.txs_with_update.into_iter();
 
-        // Use unwrap_or_else to provide a default state if iter.next() is None
-        let first_state = iter.next().unwrap_or_default();
+        // The first update is used as the base of the fold, so the transaction checks done on
+        // merge only ever compare real transactions. A default (all-zero) transaction is only
+        // used if there are no updates at all, in which case nothing is merged into it.
+        let mut aggregated_changes = iter.next().unwrap_or_default();
+
+        // Reserve room for the upper bound of merged entries, so large blocks don't rehash
+        // repeatedly while merging.
+        let remaining = iter.as_slice();
+        aggregated_changes
+            .account_deltas
+            .reserve(
+                remaining
+                    .iter()
+                    .map(|u| u.account_deltas.len())
+                    .sum(),
+            );
+        aggregated_changes
+            .state_updates
+            .reserve(
+                remaining
+                    .iter()
+                    .map(|u| u.state_updates.len())
+                    .sum(),
+            );
+        aggregated_changes
+            .balance_changes
+            .reserve(
+                remaining
+                    .iter()
+                    .map(|u| u.balance_changes.len())
+                    .sum(),
+            );
+
+        for new_state in iter {
+            aggregated_changes
+                .merge(new_state)
+                .map_err(ExtractionError::MergeError)?;
+        }
 
-        let aggregated_changes = iter
-            .try_fold(first_state, |mut acc_state, new_state| {
-                acc_state
-                    .merge(new_state.clone())
-                    .map_err(ExtractionError::MergeError)?;
-                Ok::<_, ExtractionError>(acc_state.clone())
-            })
-            .unwrap();
+        let mut state_deltas = aggregated_changes.state_updates;
+        if drop_empty_states {
+            state_deltas.retain(|_, delta| !delta.is_empty());
+        }
+        let components = route_components(aggregated_changes.protocol_components)?;
 
         Ok(BlockAggregatedChanges {
             extractor: self.extractor,
@@ -198,16 +275,37 @@ impl BlockChanges {
             block: self.block,
             finalized_block_height: self.finalized_block_height,
             revert: self.revert,
-            new_protocol_components: aggregated_changes.protocol_components,
+            new_protocol_components: components.new,
+            updated_protocol_components: components.updated,
             new_tokens: self.new_tokens,
-            deleted_protocol_components: HashMap::new(),
-            state_deltas: aggregated_changes.state_updates,
+            deleted_protocol_components: components.deleted,
+            state_deltas,
             account_deltas: aggregated_changes.account_deltas,
             component_balances: aggregated_changes.balance_changes,
             component_tvl: HashMap::new(),
+            seq: 0,
         })
     }
 
+    /// Ensures no account is updated by more than one entry sharing the same transaction hash.
+    ///
+    /// Different accounts may be updated by the same transaction, but two updates of the same
+    /// account within one transaction indicate a parsing issue.
+    fn validate_unique_account_updates(&self) -> Result<(), ExtractionError> {
+        let mut seen = HashSet::new();
+        for tx_update in &self.txs_with_update {
+            for account in tx_update.account_deltas.keys() {
+                if !seen.insert((account, &tx_update.tx.hash)) {
+                    return Err(ExtractionError::DuplicateAccountUpdate {
+                        account: account.clone(),
+                        tx: tx_update.tx.hash.clone(),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
     pub fn protocol_components(&self) -> Vec<ProtocolComponent> {
         self.txs_with_update
             .iter()
@@ -218,6 +316,117 @@ impl BlockChanges {
             })
             .collect()
     }
+
+    /// Finds token addresses that refer to the same contract but are encoded differently.
+    ///
+    /// Token addresses of all components in this block are normalised to 20 byte addresses
+    /// (stripping zero left padding or adding it where missing). Whenever differently encoded
+    /// addresses normalise to the same value, each pair of conflicting forms is returned as hex
+    /// strings, sorted, so that inconsistencies in the upstream encoding can be spotted before
+    /// they lead to double keyed balances.
+    pub fn token_address_conflicts(&self) -> Vec<(String, String)> {
+        let mut forms: HashMap<Address, HashSet<Address>> = HashMap::new();
+        for component in self.protocol_components() {
+            for token in component.tokens {
+                forms
+                    .entry(normalise_address(&token))
+                    .or_default()
+                    .insert(token);
+            }
+        }
+
+        let mut conflicts: Vec<_> = forms
+            .into_values()
+            .filter(|variants| variants.len() > 1)
+            .flat_map(|variants| {
+                let mut variants: Vec<_> = variants
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect();
+                variants.sort();
+                let first = variants.remove(0);
+                variants
+                    .into_iter()
+                    .map(move |other| (first.clone(), other))
+            })
+            .collect();
+        conflicts.sort();
+        conflicts
+    }
+
+    /// Prefixes the ids of all components in this block with `namespace`, e.g. `ambient:0xabc`.
+    ///
+    /// Keeps the ids of extractors with overlapping id spaces apart in a shared store.
+    /// Components, state deltas and balances are prefixed alike, so they still refer to each
+    /// other.
+    pub fn namespace_component_ids(&mut self, namespace: &str) {
+        let prefixed = |id: &str| format!("{namespace}:{id}");
+        for tx in self.txs_with_update.iter_mut() {
+            tx.protocol_components = std::mem::take(&mut tx.protocol_components)
+                .into_values()
+                .map(|mut component| {
+                    component.id = prefixed(&component.id);
+                    (component.id.clone(), component)
+                })
+                .collect();
+            tx.state_updates = std::mem::take(&mut tx.state_updates)
+                .into_values()
+                .map(|mut delta| {
+                    delta.component_id = prefixed(&delta.component_id);
+                    (delta.component_id.clone(), delta)
+                })
+                .collect();
+            tx.balance_changes = std::mem::take(&mut tx.balance_changes)
+                .into_iter()
+                .map(|(id, mut balances)| {
+                    for balance in balances.values_mut() {
+                        balance.component_id = prefixed(&balance.component_id);
+                    }
+                    (prefixed(&id), balances)
+                })
+                .collect();
+        }
+    }
+}
+
+/// Returns the addresses of all accounts whose state may differ between two chain segments.
+///
+/// After a reorg, `reverted` are the blocks removed from the chain and `applied` the blocks
+/// replacing them. Every account touched in either segment is included, as well as the contracts
+/// of components created in them, so consumers only need to re-fetch these accounts.
+pub fn reorg_affected_accounts(
+    reverted: &[BlockChanges],
+    applied: &[BlockChanges],
+) -> HashSet<Address> {
+    reverted
+        .iter()
+        .chain(applied)
+        .flat_map(|block| block.txs_with_update.iter())
+        .flat_map(|tx| {
+            tx.account_deltas.keys().chain(
+                tx.protocol_components
+                    .values()
+                    .flat_map(|component| component.contract_addresses.iter()),
+            )
+        })
+        .cloned()
+        .collect()
+}
+
+/// Normalises an address to its 20 byte form, if it only differs by zero padding.
+fn normalise_address(address: &Address) -> Address {
+    const ADDRESS_LEN: usize = 20;
+    let padding = address
+        .len()
+        .saturating_sub(ADDRESS_LEN);
+    if address[..padding]
+        .iter()
+        .all(|b| *b == 0)
+    {
+        Address::from(address[padding..].to_vec()).lpad(ADDRESS_LEN, 0)
+    } else {
+        address.clone()
+    }
 }
 
 impl StateUpdateBufferEntry for BlockChanges {
@@ -295,6 +504,40 @@ impl StateUpdateBufferEntry for BlockChanges {
     }
 }
 
+/// New, updated and deleted protocol components, keyed by component id.
+struct RoutedComponents {
+    new: HashMap<ComponentId, ProtocolComponent>,
+    updated: HashMap<ComponentId, ProtocolComponent>,
+    deleted: HashMap<ComponentId, ProtocolComponent>,
+}
+
+/// Routes protocol components into new, updated and deleted components by their change type.
+///
+/// Updates of components created in the same block were already merged onto the creation, so
+/// remaining updates belong to components created in earlier blocks. Components with an
+/// unspecified change type are rejected.
+fn route_components(
+    components: HashMap<ComponentId, ProtocolComponent>,
+) -> Result<RoutedComponents, ExtractionError> {
+    let mut routed =
+        RoutedComponents { new: HashMap::new(), updated: HashMap::new(), deleted: HashMap::new() };
+    for (id, component) in components {
+        match component.change {
+            ChangeType::Creation => {
+                routed.new.insert(id, component);
+            }
+            ChangeType::Update => {
+                routed.updated.insert(id, component);
+            }
+            ChangeType::Deletion => {
+                routed.deleted.insert(id, component);
+            }
+            ChangeType::Unspecified => return Err(ExtractionError::UnspecifiedChangeType(id)),
+        }
+    }
+    Ok(routed)
+}
+
 impl BlockScoped for BlockChanges {
     fn block(&self) -> tycho_core::models::blockchain::Block {
         self.block.clone()
@@ -315,6 +558,7 @@ impl From<BlockContractChanges> for BlockChanges {
                 .into_iter()
                 .map(Into::into)
                 .collect(),
+            raw_payload_hash: None,
         }
     }
 }
@@ -333,6 +577,7 @@ impl From<BlockEntityChanges> for BlockChanges {
                 .into_iter()
                 .map(Into::into)
                 .collect(),
+            raw_payload_hash: None,
         }
     }
 }
@@ -374,6 +619,7 @@ pub mod fixtures {
                 revert,
                 new_tokens,
                 txs_with_update,
+                raw_payload_hash: None,
             }
         }
     }
@@ -431,27 +677,29 @@ pub mod fixtures {
     }
 
     fn create_protocol_component(tx_hash: Bytes) -> ProtocolComponent {
-        ProtocolComponent {
-            id: "d417ff54652c09bd9f31f216b1a2e5d1e28c1dce1ba840c40d16f2b4d09b5902".to_owned(),
-            protocol_system: "ambient".to_string(),
-            protocol_type_name: String::from("WeightedPool"),
-            chain: Chain::Ethereum,
-            tokens: vec![
-                Bytes::from_str("0x6B175474E89094C44Da98b954EedeAC495271d0F").unwrap(),
-                Bytes::from_str("0x6B175474E89094C44Da98b954EedeAC495271d0F").unwrap(),
-            ],
-            contract_addresses: vec![
-                Bytes::from_str("0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2").unwrap(),
-                Bytes::from_str("0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2").unwrap(),
-            ],
-            static_attributes: HashMap::from([
-                ("key1".to_string(), Bytes::from(b"value1".to_vec())),
-                ("key2".to_string(), Bytes::from(b"value2".to_vec())),
-            ]),
-            change: ChangeType::Creation,
-            creation_tx: tx_hash,
-            created_at: NaiveDateTime::from_timestamp_opt(1000, 0).unwrap(),
-        }
+        ProtocolComponent::builder(
+            "d417ff54652c09bd9f31f216b1a2e5d1e28c1dce1ba840c40d16f2b4d09b5902",
+            "ambient",
+            "WeightedPool",
+            Chain::Ethereum,
+        )
+        .tokens(vec![
+            Bytes::from_str("0x6B175474E89094C44Da98b954EedeAC495271d0F").unwrap(),
+            Bytes::from_str("0x6B175474E89094C44Da98b954EedeAC495271d0F").unwrap(),
+        ])
+        .contract_addresses(vec![
+            Bytes::from_str("0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2").unwrap(),
+            Bytes::from_str("0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2").unwrap(),
+        ])
+        .static_attributes(HashMap::from([
+            ("key1".to_string(), Bytes::from(b"value1".to_vec())),
+            ("key2".to_string(), Bytes::from(b"value2".to_vec())),
+        ]))
+        .change(ChangeType::Creation)
+        .creation_tx(tx_hash)
+        .created_at(NaiveDateTime::from_timestamp_opt(1000, 0).unwrap())
+        .build()
+        .unwrap()
     }
 
     pub fn block_state_changes() -> BlockContractChanges {
@@ -623,6 +871,7 @@ pub mod fixtures {
                 id: "Pool".to_owned(),
                 protocol_system: "ambient".to_string(),
                 protocol_type_name: "WeightedPool".to_owned(),
+                additional_protocol_types: Vec::new(),
                 chain: Chain::Ethereum,
                 tokens: vec![
                     Bytes::from_str("0x6B175474E89094C44Da98b954EedeAC495271d0F").unwrap(),
@@ -689,8 +938,11 @@ pub mod fixtures {
 #[cfg(test)]
 mod test {
     use prost::Message;
+    use rstest::rstest;
     use std::str::FromStr;
 
+    use tycho_core::models::{contract::AccountDelta, protocol::ProtocolComponentStateDelta};
+
     use super::*;
 
     #[test]
@@ -819,4 +1071,545 @@ mod test {
             )])
         )
     }
+
+    #[rstest]
+    #[case::keep_empty(false, 2)]
+    #[case::drop_empty(true, 1)]
+    fn test_aggregate_updates_empty_states(#[case] drop_empty: bool, #[case] expected: usize) {
+        let tx = fixtures::create_transaction("0x01", "0x02", 1);
+        let block = BlockChanges::new(
+            "test".to_string(),
+            Chain::Ethereum,
+            Block::default(),
+            0,
+            false,
+            vec![TxWithChanges::new(
+                HashMap::new(),
+                HashMap::new(),
+                HashMap::from([
+                    (
+                        "pc_1".to_string(),
+                        ProtocolComponentStateDelta::new("pc_1", HashMap::new(), HashSet::new()),
+                    ),
+                    (
+                        "pc_2".to_string(),
+                        ProtocolComponentStateDelta::new(
+                            "pc_2",
+                            HashMap::from([("reserve".to_string(), Bytes::from(1u64))]),
+                            HashSet::new(),
+                        ),
+                    ),
+                ]),
+                HashMap::new(),
+                tx,
+            )],
+        );
+
+        let res = block
+            .aggregate_updates(drop_empty)
+            .unwrap();
+
+        assert_eq!(res.state_deltas.len(), expected);
+        assert!(res.state_deltas.contains_key("pc_2"));
+    }
+
+    #[test]
+    fn test_aggregate_updates_zero_hash_first_tx() {
+        let block_hash = "0x0000000000000000000000000000000000000000000000000000000000000001";
+        let first_tx = fixtures::create_transaction(
+            "0x0000000000000000000000000000000000000000000000000000000000000000",
+            block_hash,
+            0,
+        );
+        let second_tx = fixtures::create_transaction(
+            "0x0000000000000000000000000000000000000000000000000000000000000002",
+            block_hash,
+            1,
+        );
+        let state_update = |tx, reserve: u64| {
+            TxWithChanges::new(
+                HashMap::new(),
+                HashMap::new(),
+                HashMap::from([(
+                    "pc_1".to_string(),
+                    ProtocolComponentStateDelta::new(
+                        "pc_1",
+                        HashMap::from([("reserve".to_string(), Bytes::from(reserve))]),
+                        HashSet::new(),
+                    ),
+                )]),
+                HashMap::new(),
+                tx,
+            )
+        };
+        let block = BlockChanges::new(
+            "test".to_string(),
+            Chain::Ethereum,
+            Block::default(),
+            0,
+            false,
+            vec![state_update(first_tx, 1), state_update(second_tx, 2)],
+        );
+
+        let res = block.aggregate_updates(false).unwrap();
+
+        assert_eq!(
+            res.state_deltas["pc_1"].updated_attributes,
+            HashMap::from([("reserve".to_string(), Bytes::from(2u64))])
+        );
+    }
+
+    #[test]
+    fn test_aggregate_updates_deleted_attribute() {
+        let block_hash = "0x0000000000000000000000000000000000000000000000000000000000000001";
+        let state_update = |tx, updated: HashMap<String, Bytes>, deleted: HashSet<String>| {
+            TxWithChanges::new(
+                HashMap::new(),
+                HashMap::new(),
+                HashMap::from([(
+                    "pc_1".to_string(),
+                    ProtocolComponentStateDelta::new("pc_1", updated, deleted),
+                )]),
+                HashMap::new(),
+                tx,
+            )
+        };
+        let block = BlockChanges::new(
+            "test".to_string(),
+            Chain::Ethereum,
+            Block::default(),
+            0,
+            false,
+            vec![
+                state_update(
+                    fixtures::create_transaction(fixtures::HASH_256_0, block_hash, 1),
+                    HashMap::from([
+                        ("reserve".to_string(), Bytes::from(1u64)),
+                        ("tick".to_string(), Bytes::from(2u64)),
+                    ]),
+                    HashSet::new(),
+                ),
+                state_update(
+                    fixtures::create_transaction(
+                        "0x0000000000000000000000000000000000000000000000000000000000000002",
+                        block_hash,
+                        2,
+                    ),
+                    HashMap::new(),
+                    HashSet::from(["tick".to_string()]),
+                ),
+            ],
+        );
+
+        let res = block.aggregate_updates(false).unwrap();
+
+        let delta = &res.state_deltas["pc_1"];
+        assert_eq!(
+            delta.updated_attributes,
+            HashMap::from([("reserve".to_string(), Bytes::from(1u64))])
+        );
+        assert_eq!(delta.deleted_attributes, HashSet::from(["tick".to_string()]));
+    }
+
+    #[test]
+    fn test_aggregate_updates_large_block() {
+        let block_hash = "0x0000000000000000000000000000000000000000000000000000000000000001";
+        let tx_update = |index: u64| {
+            let updates = (0..10u64).map(|i| (index * 7 + i) % 500);
+            TxWithChanges::new(
+                HashMap::new(),
+                updates
+                    .clone()
+                    .map(|a| {
+                        let address = Bytes::from(a).lpad(20, 0);
+                        let delta = AccountDelta::new(
+                            Chain::Ethereum,
+                            address.clone(),
+                            fixtures::optional_slots([((a + index) % 16, index)]),
+                            Some(Bytes::from(index)),
+                            None,
+                            ChangeType::Update,
+                        );
+                        (address, delta)
+                    })
+                    .collect(),
+                updates
+                    .map(|c| {
+                        let id = format!("pc_{c}");
+                        let delta = ProtocolComponentStateDelta::new(
+                            &id,
+                            HashMap::from([(format!("attr_{}", index % 3), Bytes::from(index))]),
+                            HashSet::new(),
+                        );
+                        (id, delta)
+                    })
+                    .collect(),
+                HashMap::new(),
+                fixtures::create_transaction(&format!("{:#066x}", index + 1), block_hash, index),
+            )
+        };
+        let block = BlockChanges::new(
+            "test".to_string(),
+            Chain::Ethereum,
+            Block::default(),
+            0,
+            false,
+            (0..1000).map(tx_update).collect(),
+        );
+        // Aggregation as it was done before the maps were pre-sized and merged in place.
+        let mut iter = block
+            .txs_with_update
+            .clone()
+            .into_iter();
+        let first_state = iter.next().unwrap_or_default();
+        let reference = iter
+            .try_fold(first_state, |mut acc_state, new_state| {
+                acc_state.merge(new_state.clone())?;
+                Ok::<_, String>(acc_state.clone())
+            })
+            .unwrap();
+
+        let res = block.aggregate_updates(false).unwrap();
+
+        assert_eq!(res.account_deltas, reference.account_deltas);
+        assert_eq!(res.state_deltas, reference.state_updates);
+        assert_eq!(res.component_balances, reference.balance_changes);
+    }
+
+    #[test]
+    fn test_aggregate_updates_duplicate_account_update() {
+        let tx = fixtures::create_transaction("0x01", "0x02", 1);
+        let address = Bytes::from("0x0000000000000000000000000000000000000001");
+        let account_update = |slot: u64| {
+            TxWithChanges::new(
+                HashMap::new(),
+                HashMap::from([(
+                    address.clone(),
+                    AccountDelta::new(
+                        Chain::Ethereum,
+                        address.clone(),
+                        HashMap::from([(Bytes::from(slot), Some(Bytes::from(1u64)))]),
+                        None,
+                        None,
+                        ChangeType::Update,
+                    ),
+                )]),
+                HashMap::new(),
+                HashMap::new(),
+                tx.clone(),
+            )
+        };
+        let block = BlockChanges::new(
+            "test".to_string(),
+            Chain::Ethereum,
+            Block::default(),
+            0,
+            false,
+            vec![account_update(1), account_update(2)],
+        );
+
+        let res = block.aggregate_updates(false);
+
+        assert_eq!(
+            res,
+            Err(ExtractionError::DuplicateAccountUpdate { account: address, tx: tx.hash })
+        );
+    }
+
+    #[test]
+    fn test_aggregate_updates_rejects_unspecified_change() {
+        let component = ProtocolComponent { id: "pc_1".to_string(), ..Default::default() };
+        let block = BlockChanges::new(
+            "test".to_string(),
+            Chain::Ethereum,
+            Block::default(),
+            0,
+            false,
+            vec![TxWithChanges::new(
+                HashMap::from([(component.id.clone(), component)]),
+                HashMap::new(),
+                HashMap::new(),
+                HashMap::new(),
+                fixtures::create_transaction("0x01", "0x02", 1),
+            )],
+        );
+
+        let res = block.aggregate_updates(false);
+
+        assert_eq!(res, Err(ExtractionError::UnspecifiedChangeType("pc_1".to_string())));
+    }
+
+    #[test]
+    fn test_aggregate_updates_routes_updates_of_unknown_components() {
+        let component = |id: &str, change| ProtocolComponent {
+            id: id.to_string(),
+            change,
+            ..Default::default()
+        };
+        let changes = |component: ProtocolComponent, hash, index| {
+            TxWithChanges::new(
+                HashMap::from([(component.id.clone(), component)]),
+                HashMap::new(),
+                HashMap::new(),
+                HashMap::new(),
+                fixtures::create_transaction(hash, "0x02", index),
+            )
+        };
+        let created = component("pc_1", ChangeType::Creation);
+        let updated = component("pc_2", ChangeType::Update);
+        let block = BlockChanges::new(
+            "test".to_string(),
+            Chain::Ethereum,
+            Block::default(),
+            0,
+            false,
+            vec![changes(created.clone(), "0x01", 1), changes(updated.clone(), "0x03", 2)],
+        );
+
+        let res = block.aggregate_updates(false).unwrap();
+
+        assert_eq!(res.new_protocol_components, HashMap::from([("pc_1".to_string(), created)]));
+        assert_eq!(res.updated_protocol_components, HashMap::from([("pc_2".to_string(), updated)]));
+    }
+
+    #[rstest]
+    #[case::differently_cased(
+        "0x6B175474E89094C44Da98b954EedeAC495271d0F",
+        "0x6b175474e89094c44da98b954eedeac495271d0f",
+        vec![]
+    )]
+    #[case::zero_padded(
+        "0x6b175474e89094c44da98b954eedeac495271d0f",
+        "0x0000000000000000000000006b175474e89094c44da98b954eedeac495271d0f",
+        vec![(
+            "0x0000000000000000000000006b175474e89094c44da98b954eedeac495271d0f".to_string(),
+            "0x6b175474e89094c44da98b954eedeac495271d0f".to_string(),
+        )]
+    )]
+    fn test_token_address_conflicts(
+        #[case] first: &str,
+        #[case] second: &str,
+        #[case] expected: Vec<(String, String)>,
+    ) {
+        let component = |id: &str, token: &str| {
+            ProtocolComponent::builder(id, "ambient", "pool", Chain::Ethereum)
+                .tokens(vec![
+                    Bytes::from_str(token).unwrap(),
+                    Bytes::from_str("0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2").unwrap(),
+                ])
+                .change(ChangeType::Creation)
+                .build()
+                .unwrap()
+        };
+        let block = BlockChanges::new(
+            "test".to_string(),
+            Chain::Ethereum,
+            Block::default(),
+            0,
+            false,
+            vec![TxWithChanges::new(
+                HashMap::from([
+                    ("pc_1".to_string(), component("pc_1", first)),
+                    ("pc_2".to_string(), component("pc_2", second)),
+                ]),
+                HashMap::new(),
+                HashMap::new(),
+                HashMap::new(),
+                fixtures::create_transaction("0x01", "0x02", 1),
+            )],
+        );
+
+        assert_eq!(block.token_address_conflicts(), expected);
+    }
+
+    #[test]
+    fn test_namespace_component_ids() {
+        let token = Bytes::from("0x01");
+        let component = ProtocolComponent::builder("0xabc", "ambient", "pool", Chain::Ethereum)
+            .tokens(vec![token.clone()])
+            .change(ChangeType::Creation)
+            .build()
+            .unwrap();
+        let tx = TxWithChanges::new(
+            HashMap::from([("0xabc".to_string(), component)]),
+            HashMap::new(),
+            HashMap::from([(
+                "0xabc".to_string(),
+                ProtocolComponentStateDelta::new("0xabc", HashMap::new(), HashSet::new()),
+            )]),
+            HashMap::from([(
+                "0xabc".to_string(),
+                HashMap::from([(
+                    token.clone(),
+                    ComponentBalance::new(
+                        token.clone(),
+                        Bytes::from(100u64),
+                        100.0,
+                        Bytes::zero(32),
+                        "0xabc",
+                    ),
+                )]),
+            )]),
+            fixtures::transaction01(),
+        );
+        let mut block = BlockChanges::new(
+            "test".to_string(),
+            Chain::Ethereum,
+            Block::default(),
+            0,
+            false,
+            vec![tx],
+        );
+
+        block.namespace_component_ids("ambient");
+
+        let tx = &block.txs_with_update[0];
+        let id = "ambient:0xabc";
+        assert_eq!(tx.protocol_components[id].id, id);
+        assert_eq!(tx.state_updates[id].component_id, id);
+        assert_eq!(tx.balance_changes[id][&token].component_id, id);
+        assert_eq!(
+            block
+                .protocol_components()
+                .iter()
+                .map(|c| c.id.as_str())
+                .collect::<Vec<_>>(),
+            vec![id]
+        );
+    }
+
+    #[test]
+    fn test_reorg_affected_accounts() {
+        let address = |a: u64| Bytes::from(a).lpad(20, 0);
+        let block = |accounts: &[u64], contracts: &[u64]| {
+            let component = ProtocolComponent::builder("pc_1", "ambient", "pool", Chain::Ethereum)
+                .tokens(vec![address(100)])
+                .contract_addresses(
+                    contracts
+                        .iter()
+                        .map(|c| address(*c))
+                        .collect(),
+                )
+                .change(ChangeType::Creation)
+                .build()
+                .unwrap();
+            let tx = TxWithChanges::new(
+                HashMap::from([("pc_1".to_string(), component)]),
+                accounts
+                    .iter()
+                    .map(|a| {
+                        let delta = AccountDelta::new(
+                            Chain::Ethereum,
+                            address(*a),
+                            HashMap::new(),
+                            None,
+                            None,
+                            ChangeType::Update,
+                        );
+                        (address(*a), delta)
+                    })
+                    .collect(),
+                HashMap::new(),
+                HashMap::new(),
+                fixtures::transaction01(),
+            );
+            BlockChanges::new(
+                "test".to_string(),
+                Chain::Ethereum,
+                Block::default(),
+                0,
+                false,
+                vec![tx],
+            )
+        };
+        let reverted = block(&[1, 2], &[5]);
+        let applied = block(&[2, 3], &[]);
+
+        let res = reorg_affected_accounts(&[reverted], &[applied]);
+
+        assert_eq!(res, HashSet::from([address(1), address(2), address(3), address(5)]));
+    }
+
+    #[test]
+    fn test_aggregate_updates_same_tx_hash() {
+        let tx_changes = |attribute: &str| ProtocolChangesWithTx {
+            new_protocol_components: HashMap::new(),
+            protocol_states: HashMap::from([(
+                "pc_1".to_string(),
+                ProtocolComponentStateDelta::new(
+                    "pc_1",
+                    HashMap::from([(attribute.to_string(), Bytes::from(1u64))]),
+                    HashSet::new(),
+                ),
+            )]),
+            balance_changes: HashMap::new(),
+            tx: fixtures::transaction01(),
+        };
+        let block: BlockChanges = BlockEntityChanges::new(
+            "test".to_string(),
+            Chain::Ethereum,
+            Block::default(),
+            0,
+            false,
+            vec![tx_changes("fee"), tx_changes("reserve")],
+        )
+        .into();
+
+        let res = block.aggregate_updates(false);
+
+        assert!(matches!(res, Err(ExtractionError::MergeError(_))));
+    }
+
+    #[test]
+    fn test_component_history() {
+        let component = ProtocolComponent::builder("pc_1", "ambient", "pool", Chain::Ethereum)
+            .tokens(vec![Bytes::from("0x01")])
+            .change(ChangeType::Creation)
+            .build()
+            .unwrap();
+        let tx_changes = |index: u64, components, states| ProtocolChangesWithTx {
+            new_protocol_components: components,
+            protocol_states: states,
+            balance_changes: HashMap::new(),
+            tx: fixtures::create_transaction("0x01", "0x02", index),
+        };
+        let block = BlockEntityChanges::new(
+            "test".to_string(),
+            Chain::Ethereum,
+            Block::default(),
+            0,
+            false,
+            vec![
+                tx_changes(
+                    1,
+                    HashMap::from([("pc_1".to_string(), component)]),
+                    HashMap::from([(
+                        "pc_1".to_string(),
+                        ProtocolComponentStateDelta::new("pc_1", HashMap::new(), HashSet::new()),
+                    )]),
+                ),
+                tx_changes(2, HashMap::new(), HashMap::new()),
+                tx_changes(
+                    3,
+                    HashMap::new(),
+                    HashMap::from([(
+                        "pc_1".to_string(),
+                        ProtocolComponentStateDelta::new(
+                            "pc_1",
+                            HashMap::from([("fee".to_string(), Bytes::from(500u64))]),
+                            HashSet::new(),
+                        ),
+                    )]),
+                ),
+            ],
+        );
+
+        assert_eq!(
+            block.component_history("pc_1"),
+            vec![(1, ChangeType::Creation), (3, ChangeType::Update)]
+        );
+        assert!(block
+            .component_history("pc_2")
+            .is_empty());
+    }
 }
@@ -0,0 +1,106 @@
+use std::{num::NonZeroUsize, sync::Arc};
+
+use futures03::{Stream, StreamExt};
+use tokio::sync::Semaphore;
+
+use crate::extractor::ExtractionError;
+
+/// Decodes stream items on the blocking thread pool while emitting them in the order they arrived.
+///
+/// Decoding large blocks is CPU bound, so during backfills blocks are decoded concurrently.
+/// Results are held in a reorder buffer until all preceding items were emitted, so as long as
+/// the input stream is ordered by block number, so is the output.
+///
+/// At most `parallelism` items are decoded at the same time, and at most `reorder_window`
+/// items are in flight or waiting to be emitted. Once the window is full, no further items are
+/// pulled from the input until the oldest item was emitted.
+pub struct ParallelDecoder<F> {
+    decode: Arc<F>,
+    parallelism: NonZeroUsize,
+    reorder_window: NonZeroUsize,
+}
+
+impl<F> ParallelDecoder<F> {
+    pub fn new(decode: F, parallelism: NonZeroUsize, reorder_window: NonZeroUsize) -> Self {
+        Self { decode: Arc::new(decode), parallelism, reorder_window }
+    }
+
+    /// Consumes a stream of items and emits their decoded form in input order.
+    pub fn run<S, I, O>(self, items: S) -> impl Stream<Item = Result<O, ExtractionError>>
+    where
+        S: Stream<Item = I>,
+        F: Fn(I) -> O + Send + Sync + 'static,
+        I: Send + 'static,
+        O: Send + 'static,
+    {
+        let semaphore = Arc::new(Semaphore::new(self.parallelism.get()));
+        let decode = self.decode;
+        items
+            .map(move |item| {
+                let semaphore = semaphore.clone();
+                let decode = decode.clone();
+                async move {
+                    let _permit = semaphore
+                        .acquire_owned()
+                        .await
+                        .map_err(|e| ExtractionError::Unknown(e.to_string()))?;
+                    tokio::task::spawn_blocking(move || decode(item))
+                        .await
+                        .map_err(|e| ExtractionError::Unknown(e.to_string()))
+                }
+            })
+            .buffered(self.reorder_window.get())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::HashMap, sync::Mutex};
+
+    use futures03::stream;
+    use tokio::sync::oneshot;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_out_of_order_completion_is_emitted_in_order() {
+        let (release_tx, release_rx): (Vec<_>, HashMap<_, _>) = (1..=4u64)
+            .map(|number| {
+                let (tx, rx) = oneshot::channel::<()>();
+                (tx, (number, rx))
+            })
+            .unzip();
+        let (done_tx, mut done_rx) = tokio::sync::mpsc::unbounded_channel();
+        let release_rx = Mutex::new(release_rx);
+        let decoder = ParallelDecoder::new(
+            move |number: u64| {
+                let release = release_rx
+                    .lock()
+                    .unwrap()
+                    .remove(&number)
+                    .unwrap();
+                release.blocking_recv().unwrap();
+                done_tx.send(number).unwrap();
+                number
+            },
+            NonZeroUsize::new(4).unwrap(),
+            NonZeroUsize::new(4).unwrap(),
+        );
+        let decoded = tokio::spawn(
+            decoder
+                .run(stream::iter(1..=4u64))
+                .map(Result::unwrap)
+                .collect::<Vec<_>>(),
+        );
+
+        // Let the blocks finish decoding in reverse order.
+        let mut completed = Vec::new();
+        for release in release_tx.into_iter().rev() {
+            release.send(()).unwrap();
+            completed.push(done_rx.recv().await.unwrap());
+        }
+
+        assert_eq!(completed, vec![4, 3, 2, 1]);
+        assert_eq!(decoded.await.unwrap(), vec![1, 2, 3, 4]);
+    }
+}
@@ -90,7 +90,7 @@ mod test {
     use tycho_core::models::{
         blockchain::{Block, Transaction, TxWithChanges},
         protocol::ProtocolComponent,
-        Chain,
+        Chain, ChangeType,
     };
 
     use super::*;
@@ -140,6 +140,7 @@ mod test {
                         id: CREATED_CONTRACT.to_string(),
                         protocol_system: "test".to_string(),
                         protocol_type_name: "Pool".to_string(),
+                        additional_protocol_types: Vec::new(),
                         chain: Chain::Ethereum,
                         tokens: vec![
                             Bytes::from_str("0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48").unwrap(),
@@ -149,7 +150,7 @@ mod test {
                         creation_tx: Default::default(),
                         static_attributes: Default::default(),
                         created_at: Default::default(),
-                        change: Default::default(),
+                        change: ChangeType::Creation,
                     },
                 )]),
                 account_deltas: Default::default(),
@@ -264,6 +265,7 @@ mod test {
                         id: CREATED_CONTRACT.to_string(),
                         protocol_system: "test".to_string(),
                         protocol_type_name: "Pool".to_string(),
+                        additional_protocol_types: Vec::new(),
                         chain: Chain::Ethereum,
                         tokens: vec![
                             Bytes::from_str("0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48").unwrap(),
@@ -278,7 +280,7 @@ mod test {
                             ("factory_name".to_string(), Bytes::from(STABLE_SWAP_FACTORY)),
                         ]),
                         created_at: Default::default(),
-                        change: Default::default(),
+                        change: ChangeType::Creation,
                     },
                 )]),
                 tx: Transaction::new(
@@ -313,6 +315,7 @@ mod test {
                         id: CREATED_CONTRACT.to_string(),
                         protocol_system: "test".to_string(),
                         protocol_type_name: "Pool".to_string(),
+                        additional_protocol_types: Vec::new(),
                         chain: Chain::Ethereum,
                         tokens: vec![
                             Bytes::from_str("0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48").unwrap(),
@@ -325,7 +328,7 @@ mod test {
                             ("factory_name".to_string(), Bytes::from(STABLE_SWAP_FACTORY)),
                         ]),
                         created_at: Default::default(),
-                        change: Default::default(),
+                        change: ChangeType::Creation,
                     },
                 )]),
                 tx: Transaction::new(
@@ -1,11 +1,14 @@
 use std::collections::{hash_map::Entry, HashMap, HashSet};
 
 use chrono::NaiveDateTime;
-use tracing::warn;
+use primitive_types::U256;
+use prost::Message;
+use tracing::{trace, warn};
 
 use tycho_core::{
+    keccak256,
     models::{
-        blockchain::{Block, Transaction, TxWithChanges},
+        blockchain::{Block, Transaction, TransactionType, TxStatus, TxWithChanges},
         contract::{AccountDelta, TransactionVMUpdates},
         protocol::{
             ComponentBalance, ProtocolChangesWithTx, ProtocolComponent, ProtocolComponentStateDelta,
@@ -21,9 +24,34 @@ use crate::{
         u256_num::bytes_to_f64,
         ExtractionError,
     },
-    pb::tycho::evm::v1 as substreams,
+    pb::{
+        sf::substreams::rpc::v2::{store_delta, BlockScopedData},
+        tycho::evm::v1 as substreams,
+    },
 };
 
+/// Default upper bound on the number of new protocol components accepted in a single block.
+pub const DEFAULT_MAX_COMPONENTS_PER_BLOCK: usize = 10_000;
+
+/// Width of the largest balance that can be decoded, a 256 bit integer.
+const MAX_BALANCE_BYTES: usize = 32;
+
+/// How missing fields of substreams messages are handled.
+///
+/// Substreams packages built against an older or newer schema than the indexer may omit fields
+/// the indexer expects, or use enum values it doesn't know, which both decode as unspecified.
+/// Fields that are optional anyway, like a contract's balance and code or a transaction's
+/// receiver, are parsed leniently in either mode.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ParseMode {
+    /// Reject messages with a missing change type.
+    #[default]
+    Strict,
+    /// Assume a default for missing change types and log a warning: contract and attribute
+    /// changes become updates, component changes become creations.
+    Tolerant,
+}
+
 pub trait TryFromMessage {
     type Args<'a>;
 
@@ -38,6 +66,12 @@ impl TryFromMessage for AccountDelta {
     fn try_from_message(args: Self::Args<'_>) -> Result<Self, ExtractionError> {
         let (msg, chain) = args;
         let change = msg.change().into();
+        if change == ChangeType::Unspecified {
+            return Err(ExtractionError::DecodeError(format!(
+                "Unspecified change type for contract {}",
+                Bytes::from(msg.address)
+            )));
+        }
         let update = AccountDelta::new(
             chain,
             msg.address.into(),
@@ -49,6 +83,9 @@ impl TryFromMessage for AccountDelta {
             if !msg.code.is_empty() { Some(msg.code.into()) } else { None },
             change,
         );
+        update
+            .validate()
+            .map_err(|e| ExtractionError::DecodeError(e.to_string()))?;
         Ok(update)
     }
 }
@@ -59,6 +96,16 @@ impl TryFromMessage for Block {
     /// Parses block from tychos protobuf block message
     fn try_from_message(args: Self::Args<'_>) -> Result<Self, ExtractionError> {
         let (msg, chain) = args;
+        let base_fee_per_gas = match msg.base_fee_per_gas {
+            Some(fee) if fee.len() > 32 => {
+                return Err(ExtractionError::DecodeError(format!(
+                    "Base fee per gas is {} bytes long, at most 32 bytes fit into a U256",
+                    fee.len()
+                )))
+            }
+            Some(fee) => Some(U256::from_big_endian(&fee)),
+            None => None,
+        };
         let ts_nano = match chain {
             // For blockchains with subsecond block times, like Arbitrum, timestamps aren't precise
             // enough to distinguish between two blocks accurately. To maintain accurate ordering,
@@ -78,6 +125,7 @@ impl TryFromMessage for Block {
                     msg.ts
                 ))
             })?,
+            base_fee_per_gas,
         })
     }
 }
@@ -88,7 +136,34 @@ impl TryFromMessage for Transaction {
     fn try_from_message(args: Self::Args<'_>) -> Result<Self, ExtractionError> {
         let (msg, block_hash) = args;
 
-        let to = if !msg.to.is_empty() { Some(msg.to.into()) } else { None };
+        let tx_type = msg.tx_type();
+        let status = msg.status();
+        let to: Option<Bytes> = if !msg.to.is_empty() { Some(msg.to.into()) } else { None };
+        let tx_type = match tx_type {
+            substreams::TransactionType::Unspecified => TransactionType::infer(to.as_ref()),
+            substreams::TransactionType::Call => TransactionType::Call,
+            substreams::TransactionType::Creation => TransactionType::Creation,
+            substreams::TransactionType::System => TransactionType::System,
+        };
+        let status = match status {
+            substreams::TransactionStatus::Unspecified | substreams::TransactionStatus::Success => {
+                TxStatus::Success
+            }
+            substreams::TransactionStatus::Reverted => TxStatus::Reverted,
+        };
+        let access_list = (!msg.access_list.is_empty()).then(|| {
+            msg.access_list
+                .into_iter()
+                .map(|entry| {
+                    let slots = entry
+                        .storage_keys
+                        .into_iter()
+                        .map(Bytes::from)
+                        .collect();
+                    (Bytes::from(entry.address), slots)
+                })
+                .collect()
+        });
 
         Ok(Self {
             hash: msg.hash.into(),
@@ -96,6 +171,10 @@ impl TryFromMessage for Transaction {
             from: msg.from.into(),
             to,
             index: msg.index,
+            gas_used: msg.gas_used,
+            tx_type,
+            status,
+            access_list,
         })
     }
 }
@@ -105,6 +184,14 @@ impl TryFromMessage for ComponentBalance {
 
     fn try_from_message(args: Self::Args<'_>) -> Result<Self, ExtractionError> {
         let (msg, tx) = args;
+        // Balances are big-endian unsigned integers of variable width, at most 32 bytes.
+        if msg.balance.len() > MAX_BALANCE_BYTES {
+            return Err(ExtractionError::DecodeError(format!(
+                "Balance of token {} has {} bytes, expected at most {MAX_BALANCE_BYTES}",
+                Bytes::from(msg.token),
+                msg.balance.len()
+            )));
+        }
         let balance_float = bytes_to_f64(&msg.balance).unwrap_or(f64::NAN);
         Ok(Self {
             token: msg.token.into(),
@@ -129,6 +216,13 @@ impl TryFromMessage for ProtocolComponent {
 
     fn try_from_message(args: Self::Args<'_>) -> Result<Self, ExtractionError> {
         let (msg, chain, protocol_system, protocol_types, tx_hash, creation_ts) = args;
+        let change = msg.change().into();
+        if change == ChangeType::Unspecified {
+            return Err(ExtractionError::DecodeError(format!(
+                "Unspecified change type for component {}",
+                msg.id
+            )));
+        }
         let tokens: Vec<Bytes> = msg
             .tokens
             .clone()
@@ -155,22 +249,39 @@ impl TryFromMessage for ProtocolComponent {
             .clone()
             .ok_or(ExtractionError::DecodeError("Missing protocol type".to_owned()))?;
 
-        if !protocol_types.contains_key(&protocol_type.name) {
-            return Err(ExtractionError::DecodeError(format!(
-                "Unknown protocol type name: {}",
-                protocol_type.name
-            )));
+        let additional_protocol_types: Vec<String> = msg
+            .additional_protocol_types
+            .iter()
+            .map(|pt| pt.name.clone())
+            .filter(|name| *name != protocol_type.name)
+            .collect();
+
+        for name in std::iter::once(&protocol_type.name).chain(additional_protocol_types.iter()) {
+            if !protocol_types.contains_key(name) {
+                return Err(ExtractionError::DecodeError(format!(
+                    "Unknown protocol type name: {}",
+                    name
+                )));
+            }
         }
 
+        trace!(
+            component_id = %msg.id,
+            ?chain,
+            protocol_type = %protocol_type.name,
+            "Parsed protocol component"
+        );
+
         Ok(Self {
             id: msg.id.clone(),
             protocol_type_name: protocol_type.name,
+            additional_protocol_types,
             protocol_system: protocol_system.to_owned(),
             tokens,
             contract_addresses: contract_ids,
             static_attributes,
             chain,
-            change: msg.change().into(),
+            change,
             creation_tx: tx_hash,
             created_at: creation_ts,
         })
@@ -180,9 +291,7 @@ impl TryFromMessage for ProtocolComponent {
 impl From<substreams::ChangeType> for ChangeType {
     fn from(value: substreams::ChangeType) -> Self {
         match value {
-            substreams::ChangeType::Unspecified => {
-                panic!("Unkown enum member encountered: {:?}", value)
-            }
+            substreams::ChangeType::Unspecified => ChangeType::Unspecified,
             substreams::ChangeType::Update => ChangeType::Update,
             substreams::ChangeType::Creation => ChangeType::Creation,
             substreams::ChangeType::Deletion => ChangeType::Deletion,
@@ -191,21 +300,38 @@ impl From<substreams::ChangeType> for ChangeType {
 }
 
 impl TryFromMessage for ProtocolComponentStateDelta {
-    type Args<'a> = substreams::EntityChanges;
+    /// The entity changes and an optional allowlist of attribute names. If an allowlist is
+    /// given, attributes not on it are dropped, otherwise all attributes are kept.
+    type Args<'a> = (substreams::EntityChanges, Option<&'a HashSet<String>>);
 
     fn try_from_message(args: Self::Args<'_>) -> Result<Self, ExtractionError> {
-        let msg = args;
+        let (msg, attribute_allowlist) = args;
 
         let (mut updates, mut deletions) = (HashMap::new(), HashSet::new());
 
-        for attribute in msg.attributes.into_iter() {
+        let attributes = msg
+            .attributes
+            .into_iter()
+            .filter(|attribute| {
+                attribute_allowlist.is_none_or(|allowlist| allowlist.contains(&attribute.name))
+            });
+        // Attributes are ordered, a later change of the same attribute overrides earlier ones.
+        for attribute in attributes {
             match attribute.change().into() {
                 ChangeType::Update | ChangeType::Creation => {
+                    deletions.remove(&attribute.name);
                     updates.insert(attribute.name, Bytes::from(attribute.value));
                 }
                 ChangeType::Deletion => {
+                    updates.remove(&attribute.name);
                     deletions.insert(attribute.name);
                 }
+                ChangeType::Unspecified => {
+                    return Err(ExtractionError::DecodeError(format!(
+                        "Unspecified change type for attribute {} of component {}",
+                        attribute.name, msg.component_id
+                    )));
+                }
             }
         }
 
@@ -223,10 +349,11 @@ impl TryFromMessage for ProtocolChangesWithTx {
         &'a Block,
         &'a str,
         &'a HashMap<String, ProtocolType>,
+        Option<&'a HashSet<String>>,
     );
 
     fn try_from_message(args: Self::Args<'_>) -> Result<Self, ExtractionError> {
-        let (msg, block, protocol_system, protocol_types) = args;
+        let (msg, block, protocol_system, protocol_types, attribute_allowlist) = args;
         let tx = Transaction::try_from_message((
             msg.tx
                 .expect("TransactionEntityChanges should have a transaction"),
@@ -253,7 +380,8 @@ impl TryFromMessage for ProtocolChangesWithTx {
 
         // Then, parse the state updates
         for state_msg in msg.entity_changes.into_iter() {
-            let state = ProtocolComponentStateDelta::try_from_message(state_msg)?;
+            let state =
+                ProtocolComponentStateDelta::try_from_message((state_msg, attribute_allowlist))?;
             // Check if a state update for the same component already exists
             // If it exists, overwrite the existing state update with the new one and log a warning
             match state_updates.entry(state.component_id.clone()) {
@@ -298,11 +426,16 @@ impl TryFromMessage for ProtocolChangesWithTx {
 }
 
 impl TryFromMessage for TxWithChanges {
-    type Args<'a> =
-        (substreams::TransactionChanges, &'a Block, &'a str, &'a HashMap<String, ProtocolType>);
+    type Args<'a> = (
+        substreams::TransactionChanges,
+        &'a Block,
+        &'a str,
+        &'a HashMap<String, ProtocolType>,
+        Option<&'a HashSet<String>>,
+    );
 
     fn try_from_message(args: Self::Args<'_>) -> Result<Self, ExtractionError> {
-        let (msg, block, protocol_system, protocol_types) = args;
+        let (msg, block, protocol_system, protocol_types, attribute_allowlist) = args;
         let tx = Transaction::try_from_message((
             msg.tx
                 .expect("TransactionChanges should have a transaction"),
@@ -335,7 +468,8 @@ impl TryFromMessage for TxWithChanges {
 
         // Then, parse the state updates
         for state_msg in msg.entity_changes.into_iter() {
-            let state = ProtocolComponentStateDelta::try_from_message(state_msg)?;
+            let state =
+                ProtocolComponentStateDelta::try_from_message((state_msg, attribute_allowlist))?;
             // Check if a state update for the same component already exists
             // If it exists, overwrite the existing state update with the new one and log a warning
             match state_updates.entry(state.component_id.clone()) {
@@ -349,17 +483,23 @@ impl TryFromMessage for TxWithChanges {
             }
         }
 
-        // Finally, parse the balance changes
+        // Finally, parse the balance changes. Balances are absolute, so if a token's balance of a
+        // component changes several times within the transaction, the last change is its net
+        // effect and overwrites the earlier ones.
         for balance_change in msg.balance_changes.into_iter() {
-            let component_id = String::from_utf8(balance_change.component_id.clone())
-                .map_err(|error| ExtractionError::DecodeError(error.to_string()))?;
-            let token_address = Bytes::from(balance_change.token.clone());
             let balance = ComponentBalance::try_from_message((balance_change, &tx))?;
 
-            balance_changes
-                .entry(component_id)
+            if let Some(existing_balance) = balance_changes
+                .entry(balance.component_id.clone())
                 .or_default()
-                .insert(token_address, balance);
+                .insert(balance.token.clone(), balance)
+            {
+                trace!(
+                    component_id = %existing_balance.component_id,
+                    token = %existing_balance.token,
+                    "Overwriting balance change within transaction"
+                );
+            }
         }
 
         Ok(Self {
@@ -380,12 +520,27 @@ impl TryFromMessage for BlockContractChanges {
         String,
         &'a HashMap<String, ProtocolType>,
         u64,
+        usize,
     );
 
     fn try_from_message(args: Self::Args<'_>) -> Result<Self, ExtractionError> {
-        let (msg, extractor, chain, protocol_system, protocol_types, finalized_block_height) = args;
+        let (
+            msg,
+            extractor,
+            chain,
+            protocol_system,
+            protocol_types,
+            finalized_block_height,
+            max_components,
+        ) = args;
 
         if let Some(block) = msg.block {
+            ensure_component_limit(
+                msg.changes
+                    .iter()
+                    .flat_map(|change| &change.component_changes),
+                max_components,
+            )?;
             let block = Block::try_from_message((block, chain))?;
             let mut tx_updates = Vec::new();
 
@@ -456,12 +611,29 @@ impl TryFromMessage for BlockEntityChanges {
         &'a str,
         &'a HashMap<String, ProtocolType>,
         u64,
+        usize,
+        Option<&'a HashSet<String>>,
     );
 
     fn try_from_message(args: Self::Args<'_>) -> Result<Self, ExtractionError> {
-        let (msg, extractor, chain, protocol_system, protocol_types, finalized_block_height) = args;
+        let (
+            msg,
+            extractor,
+            chain,
+            protocol_system,
+            protocol_types,
+            finalized_block_height,
+            max_components,
+            attribute_allowlist,
+        ) = args;
 
         if let Some(block) = msg.block {
+            ensure_component_limit(
+                msg.changes
+                    .iter()
+                    .flat_map(|change| &change.component_changes),
+                max_components,
+            )?;
             let block = Block::try_from_message((block, chain))?;
 
             let mut txs_with_update = msg
@@ -479,6 +651,7 @@ impl TryFromMessage for BlockEntityChanges {
                         &block,
                         protocol_system,
                         protocol_types,
+                        attribute_allowlist,
                     ))
                 })
                 .collect::<Result<Vec<ProtocolChangesWithTx>, ExtractionError>>()?;
@@ -501,13 +674,36 @@ impl TryFromMessage for BlockEntityChanges {
 }
 
 impl TryFromMessage for BlockChanges {
-    type Args<'a> =
-        (substreams::BlockChanges, &'a str, Chain, &'a str, &'a HashMap<String, ProtocolType>, u64);
+    type Args<'a> = (
+        substreams::BlockChanges,
+        &'a str,
+        Chain,
+        &'a str,
+        &'a HashMap<String, ProtocolType>,
+        u64,
+        usize,
+        Option<&'a HashSet<String>>,
+    );
 
     fn try_from_message(args: Self::Args<'_>) -> Result<Self, ExtractionError> {
-        let (msg, extractor, chain, protocol_system, protocol_types, finalized_block_height) = args;
+        let (
+            msg,
+            extractor,
+            chain,
+            protocol_system,
+            protocol_types,
+            finalized_block_height,
+            max_components,
+            attribute_allowlist,
+        ) = args;
 
         if let Some(block) = msg.block {
+            ensure_component_limit(
+                msg.changes
+                    .iter()
+                    .flat_map(|change| &change.component_changes),
+                max_components,
+            )?;
             let block = Block::try_from_message((block, chain))?;
 
             let txs_with_update = msg
@@ -525,6 +721,7 @@ impl TryFromMessage for BlockChanges {
                         &block,
                         protocol_system,
                         protocol_types,
+                        attribute_allowlist,
                     ))
                 })
                 .collect::<Result<Vec<TxWithChanges>, ExtractionError>>()?;
@@ -547,6 +744,288 @@ impl TryFromMessage for BlockChanges {
     }
 }
 
+/// Guards against malformed messages emitting an unreasonable amount of new components.
+///
+/// Only creations count towards the limit, updates and deletions of existing components don't.
+fn ensure_component_limit<'a>(
+    component_changes: impl IntoIterator<Item = &'a substreams::ProtocolComponent>,
+    max_components: usize,
+) -> Result<(), ExtractionError> {
+    let count = component_changes
+        .into_iter()
+        .filter(|component| component.change() == substreams::ChangeType::Creation)
+        .count();
+    if count > max_components {
+        return Err(ExtractionError::DecodeError(format!(
+            "Block contains {count} new components, exceeding the maximum of {max_components}"
+        )));
+    }
+    Ok(())
+}
+
+/// Options of [`decode_block_scoped`].
+///
+/// By default, messages of any module with at most [`DEFAULT_MAX_COMPONENTS_PER_BLOCK`] new
+/// components are decoded strictly, keeping all attributes and without hashing the payload.
+#[derive(Debug, Clone)]
+pub struct DecodeOptions<'a> {
+    /// Maximum number of new components in a single block.
+    pub max_components: usize,
+    /// Name of the module the message must be emitted by, any module if unset.
+    pub expected_module: Option<&'a str>,
+    /// Attaches the Keccak-256 hash of the raw payload to the decoded changes, so they can later
+    /// be verified against the original substreams output.
+    pub hash_payload: bool,
+    /// How missing change types are handled, see [`ParseMode`].
+    pub parse_mode: ParseMode,
+    /// Only protocol state attributes with these names are kept, if set.
+    pub attribute_allowlist: Option<&'a HashSet<String>>,
+}
+
+impl Default for DecodeOptions<'_> {
+    fn default() -> Self {
+        Self {
+            max_components: DEFAULT_MAX_COMPONENTS_PER_BLOCK,
+            expected_module: None,
+            hash_payload: false,
+            parse_mode: ParseMode::default(),
+            attribute_allowlist: None,
+        }
+    }
+}
+
+/// Decodes the map output of a `BlockScopedData` message into `BlockChanges`.
+///
+/// The payload is decoded according to its `type_url`. For backwards compatibility,
+/// `BlockContractChanges` and `BlockEntityChanges` messages are decoded as their corresponding
+/// message type and then converted into `BlockChanges`.
+///
+/// # Errors
+///
+/// Returns a `DecodeError` if the message has no map output, was emitted by another module than
+/// the expected one (if set), its `type_url` is unknown or it contains more new components than
+/// allowed by `options`, and `ExtractionError::Empty` if the decoded message does not contain a
+/// block.
+pub fn decode_block_scoped(
+    inp: &BlockScopedData,
+    extractor: &str,
+    chain: Chain,
+    protocol_system: &str,
+    protocol_types: &HashMap<String, ProtocolType>,
+    options: &DecodeOptions,
+) -> Result<BlockChanges, ExtractionError> {
+    let DecodeOptions {
+        max_components,
+        expected_module,
+        hash_payload,
+        parse_mode,
+        attribute_allowlist,
+    } = *options;
+    let output = inp
+        .output
+        .as_ref()
+        .ok_or_else(|| ExtractionError::DecodeError("Missing map output".to_owned()))?;
+    if let Some(expected) = expected_module {
+        if output.name != expected {
+            return Err(ExtractionError::DecodeError(format!(
+                "Received output of module {}, expected {}",
+                output.name, expected
+            )));
+        }
+    }
+    let data = output
+        .map_output
+        .as_ref()
+        .ok_or_else(|| ExtractionError::DecodeError("Missing map output".to_owned()))?;
+
+    let mut changes = match data.type_url.as_str() {
+        url if url.ends_with("BlockChanges") => {
+            let mut raw_msg = substreams::BlockChanges::decode(data.value.as_slice())?;
+            trace!(?raw_msg, "Received BlockChanges message");
+            if parse_mode == ParseMode::Tolerant {
+                for change in raw_msg.changes.iter_mut() {
+                    default_missing_changes(
+                        &mut change.contract_changes,
+                        &mut change.entity_changes,
+                        &mut change.component_changes,
+                    );
+                }
+            }
+            BlockChanges::try_from_message((
+                raw_msg,
+                extractor,
+                chain,
+                protocol_system,
+                protocol_types,
+                inp.final_block_height,
+                max_components,
+                attribute_allowlist,
+            ))
+        }
+        url if url.ends_with("BlockContractChanges") => {
+            let mut raw_msg = substreams::BlockContractChanges::decode(data.value.as_slice())?;
+            trace!(?raw_msg, "Received BlockContractChanges message");
+            if parse_mode == ParseMode::Tolerant {
+                for change in raw_msg.changes.iter_mut() {
+                    default_missing_changes(
+                        &mut change.contract_changes,
+                        &mut [],
+                        &mut change.component_changes,
+                    );
+                }
+            }
+            BlockContractChanges::try_from_message((
+                raw_msg,
+                extractor,
+                chain,
+                protocol_system.to_owned(),
+                protocol_types,
+                inp.final_block_height,
+                max_components,
+            ))
+            .map(Into::into)
+        }
+        url if url.ends_with("BlockEntityChanges") => {
+            let mut raw_msg = substreams::BlockEntityChanges::decode(data.value.as_slice())?;
+            trace!(?raw_msg, "Received BlockEntityChanges message");
+            if parse_mode == ParseMode::Tolerant {
+                for change in raw_msg.changes.iter_mut() {
+                    default_missing_changes(
+                        &mut [],
+                        &mut change.entity_changes,
+                        &mut change.component_changes,
+                    );
+                }
+            }
+            BlockEntityChanges::try_from_message((
+                raw_msg,
+                extractor,
+                chain,
+                protocol_system,
+                protocol_types,
+                inp.final_block_height,
+                max_components,
+                attribute_allowlist,
+            ))
+            .map(Into::into)
+        }
+        url => Err(ExtractionError::DecodeError(format!("Unknown message type: {url}"))),
+    }?;
+    if hash_payload {
+        changes.raw_payload_hash = Some(keccak256(&data.value).into());
+    }
+    Ok(changes)
+}
+
+/// Sets the change type of changes that miss one to a default, logging a warning for each.
+fn default_missing_changes(
+    contract_changes: &mut [substreams::ContractChange],
+    entity_changes: &mut [substreams::EntityChanges],
+    component_changes: &mut [substreams::ProtocolComponent],
+) {
+    let missing = |change: substreams::ChangeType| change == substreams::ChangeType::Unspecified;
+    for contract in contract_changes
+        .iter_mut()
+        .filter(|c| missing(c.change()))
+    {
+        warn!(
+            address = %Bytes::from(contract.address.clone()),
+            "Contract change misses its change type, assuming an update"
+        );
+        contract.set_change(substreams::ChangeType::Update);
+    }
+    for entity in entity_changes.iter_mut() {
+        for attribute in entity
+            .attributes
+            .iter_mut()
+            .filter(|a| missing(a.change()))
+        {
+            warn!(
+                component_id = %entity.component_id,
+                attribute = %attribute.name,
+                "Attribute change misses its change type, assuming an update"
+            );
+            attribute.set_change(substreams::ChangeType::Update);
+        }
+    }
+    for component in component_changes
+        .iter_mut()
+        .filter(|c| missing(c.change()))
+    {
+        warn!(
+            component_id = %component.id,
+            "Component change misses its change type, assuming a creation"
+        );
+        component.set_change(substreams::ChangeType::Creation);
+    }
+}
+
+/// Adds components created through a substreams store module to the decoded block changes.
+///
+/// Each `Create` delta emitted by `store_module` is expected to hold an encoded
+/// `TransactionChanges` message; only its transaction and component changes are used. New
+/// components are merged into the matching transaction of `changes`, transactions not yet part
+/// of the block are appended in index order.
+///
+/// Store deltas are only streamed in substreams development mode, so this only works for
+/// deployments that run in that mode.
+///
+/// # Errors
+///
+/// Returns a `DecodeError` if a delta can't be decoded or carries no transaction.
+pub fn decode_store_components(
+    inp: &BlockScopedData,
+    store_module: &str,
+    changes: &mut BlockChanges,
+    protocol_system: &str,
+    protocol_types: &HashMap<String, ProtocolType>,
+) -> Result<(), ExtractionError> {
+    let deltas = inp
+        .debug_store_outputs
+        .iter()
+        .filter(|output| output.name == store_module)
+        .flat_map(|output| output.debug_store_deltas.iter())
+        .filter(|delta| delta.operation() == store_delta::Operation::Create);
+
+    for delta in deltas {
+        let raw_msg = substreams::TransactionChanges::decode(delta.new_value.as_slice())?;
+        if raw_msg.tx.is_none() {
+            return Err(ExtractionError::DecodeError(format!(
+                "Store delta {} has no transaction",
+                delta.key
+            )));
+        }
+        let raw_msg = substreams::TransactionChanges {
+            tx: raw_msg.tx,
+            component_changes: raw_msg.component_changes,
+            ..Default::default()
+        };
+        let update = TxWithChanges::try_from_message((
+            raw_msg,
+            &changes.block,
+            protocol_system,
+            protocol_types,
+            None,
+        ))?;
+
+        match changes
+            .txs_with_update
+            .iter_mut()
+            .find(|tx| tx.tx.hash == update.tx.hash)
+        {
+            Some(tx) => tx
+                .protocol_components
+                .extend(update.protocol_components),
+            None => changes.txs_with_update.push(update),
+        }
+    }
+    changes
+        .txs_with_update
+        .sort_by_key(|tx| tx.tx.index);
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod test {
     use std::str::FromStr;
@@ -559,18 +1038,61 @@ mod test {
         extractor::models::fixtures::{
             block_entity_changes, block_state_changes, create_transaction,
         },
-        pb::testing::fixtures,
+        pb::{
+            sf::substreams::rpc::v2::{StoreDelta, StoreModuleOutput},
+            testing::fixtures,
+        },
     };
 
     #[test]
     fn test_parse_protocol_state_update() {
         let msg = fixtures::pb_state_changes();
 
-        let res = ProtocolComponentStateDelta::try_from_message(msg).unwrap();
+        let res = ProtocolComponentStateDelta::try_from_message((msg, None)).unwrap();
 
         assert_eq!(res, fixtures::protocol_state_delta());
     }
 
+    #[test]
+    fn test_parse_protocol_state_update_allowlist() {
+        let mut msg = fixtures::pb_state_changes();
+        msg.attributes
+            .push(substreams::Attribute {
+                name: "reserve2".to_owned(),
+                value: vec![],
+                change: substreams::ChangeType::Deletion.into(),
+            });
+        let allowlist = HashSet::from(["reserve1".to_owned()]);
+
+        let res = ProtocolComponentStateDelta::try_from_message((msg, Some(&allowlist))).unwrap();
+
+        let mut expected = fixtures::protocol_state_delta();
+        expected
+            .updated_attributes
+            .remove("reserve2");
+        assert_eq!(res, expected);
+    }
+
+    #[test]
+    fn test_parse_protocol_state_update_later_change_wins() {
+        let mut msg = fixtures::pb_state_changes();
+        msg.attributes
+            .push(substreams::Attribute {
+                name: "reserve2".to_owned(),
+                value: vec![],
+                change: substreams::ChangeType::Deletion.into(),
+            });
+
+        let res = ProtocolComponentStateDelta::try_from_message((msg, None)).unwrap();
+
+        let mut expected = fixtures::protocol_state_delta();
+        expected
+            .updated_attributes
+            .remove("reserve2");
+        expected.deleted_attributes = HashSet::from(["reserve2".to_owned()]);
+        assert_eq!(res, expected);
+    }
+
     #[rstest]
     fn test_parse_protocol_component() {
         let msg = fixtures::pb_protocol_component();
@@ -638,6 +1160,51 @@ mod test {
         )
     }
 
+    #[rstest]
+    #[case::known_types(&["WeightedPool", "LendingPool"], true)]
+    #[case::unknown_additional_type(&["WeightedPool"], false)]
+    fn test_parse_protocol_component_multiple_types(
+        #[case] known_types: &[&str],
+        #[case] is_ok: bool,
+    ) {
+        let mut msg = fixtures::pb_protocol_component();
+        msg.additional_protocol_types = vec![substreams::ProtocolType {
+            name: "LendingPool".to_string(),
+            ..Default::default()
+        }];
+        let protocol_types: HashMap<String, ProtocolType> = known_types
+            .iter()
+            .map(|name| (name.to_string(), ProtocolType::default()))
+            .collect();
+
+        let res = ProtocolComponent::try_from_message((
+            msg,
+            Chain::Ethereum,
+            "ambient",
+            &protocol_types,
+            Bytes::zero(32),
+            Default::default(),
+        ));
+
+        if is_ok {
+            let component = res.unwrap();
+            assert_eq!(
+                component
+                    .protocol_type_names()
+                    .collect::<Vec<_>>(),
+                vec!["WeightedPool", "LendingPool"]
+            );
+            assert!(component.has_protocol_type("LendingPool"));
+        } else {
+            assert_eq!(
+                res,
+                Err(ExtractionError::DecodeError(
+                    "Unknown protocol type name: LendingPool".to_string()
+                ))
+            );
+        }
+    }
+
     #[rstest]
     fn test_parse_component_balance() {
         let tx = transaction();
@@ -664,6 +1231,187 @@ mod test {
         assert_eq!(from_message.component_id, expected_component_id);
     }
 
+    #[rstest]
+    #[case::empty(vec![], Some(0.0))]
+    #[case::four_bytes(vec![0, 0, 1, 0], Some(256.0))]
+    #[case::sixteen_bytes([vec![0; 15], vec![7]].concat(), Some(7.0))]
+    #[case::too_wide(vec![1; 33], None)]
+    fn test_parse_component_balance_width(#[case] balance: Vec<u8>, #[case] expected: Option<f64>) {
+        let msg = substreams::BalanceChange {
+            token: Bytes::from("0x01").to_vec(),
+            balance: balance.clone(),
+            component_id: b"pool".to_vec(),
+        };
+
+        let res = ComponentBalance::try_from_message((msg, &transaction()));
+
+        match expected {
+            Some(balance_float) => {
+                let res = res.unwrap();
+                assert_eq!(res.balance, Bytes::from(balance));
+                assert_eq!(res.balance_float, balance_float);
+            }
+            None => assert!(matches!(
+                res,
+                Err(ExtractionError::DecodeError(msg)) if msg.contains("has 33 bytes")
+            )),
+        }
+    }
+
+    #[test]
+    fn test_parse_component_balance_lossless() {
+        // 2^256 - 1 is far beyond the precision of the derived float.
+        let max = vec![0xff; 32];
+        let msg = substreams::BalanceChange {
+            token: Bytes::from("0x01").to_vec(),
+            balance: max.clone(),
+            component_id: b"pool".to_vec(),
+        };
+
+        let res = ComponentBalance::try_from_message((msg, &transaction())).unwrap();
+
+        assert_eq!(U256::from_big_endian(&res.balance), U256::MAX);
+        assert_eq!(res.balance, Bytes::from(max));
+    }
+
+    #[test]
+    fn test_parse_tx_with_changes_collapses_balance_changes() {
+        let block = Block::try_from_message((fixtures::pb_blocks(1), Chain::Ethereum)).unwrap();
+        let balance_change = |balance: u64| substreams::BalanceChange {
+            token: Bytes::from("0x01").to_vec(),
+            balance: Bytes::from(balance).to_vec(),
+            component_id: b"pool".to_vec(),
+        };
+        let msg = substreams::TransactionChanges {
+            tx: Some(fixtures::pb_transactions(1, 1)),
+            balance_changes: vec![balance_change(100), balance_change(70)],
+            ..Default::default()
+        };
+
+        let res = TxWithChanges::try_from_message((msg, &block, "ambient", &HashMap::new(), None))
+            .unwrap();
+
+        let balances = &res.balance_changes["pool"];
+        assert_eq!(balances.len(), 1);
+        assert_eq!(balances[&Bytes::from("0x01")].balance, Bytes::from(70u64));
+        assert_eq!(balances[&Bytes::from("0x01")].modify_tx, res.tx.hash);
+    }
+
+    #[rstest]
+    #[case::with_base_fee(Some(U256::from(30_000_000_000u64)))]
+    #[case::without_base_fee(None)]
+    fn test_parse_block_base_fee(#[case] base_fee: Option<U256>) {
+        let msg = substreams::Block {
+            base_fee_per_gas: base_fee.map(|fee| {
+                let mut buf = [0u8; 32];
+                fee.to_big_endian(&mut buf);
+                buf.to_vec()
+            }),
+            ..fixtures::pb_blocks(1)
+        };
+        let msg = substreams::Block::decode(msg.encode_to_vec().as_slice()).unwrap();
+
+        let res = Block::try_from_message((msg, Chain::Ethereum)).unwrap();
+
+        assert_eq!(res.base_fee_per_gas, base_fee);
+        assert_eq!(res.number, 1);
+    }
+
+    #[test]
+    fn test_parse_block_base_fee_too_long() {
+        let msg =
+            substreams::Block { base_fee_per_gas: Some(vec![1u8; 33]), ..fixtures::pb_blocks(1) };
+
+        let res = Block::try_from_message((msg, Chain::Ethereum));
+
+        assert!(matches!(res, Err(ExtractionError::DecodeError(_))));
+    }
+
+    #[rstest]
+    #[case::with_gas(Some(21_000))]
+    #[case::without_gas(None)]
+    fn test_parse_transaction_gas_used(#[case] gas_used: Option<u64>) {
+        let msg = substreams::Transaction { gas_used, ..fixtures::pb_transactions(1, 1) };
+        let block_hash = Bytes::from(1u64).lpad(32, 0);
+        let msg = substreams::Transaction::decode(msg.encode_to_vec().as_slice()).unwrap();
+
+        let res = Transaction::try_from_message((msg, &block_hash)).unwrap();
+
+        assert_eq!(res.gas_used, gas_used);
+        assert_eq!(res.index, 1);
+    }
+
+    #[rstest]
+    #[case::call(vec![1; 20], None, TransactionType::Call)]
+    #[case::creation(vec![], None, TransactionType::Creation)]
+    #[case::explicit_creation(
+        vec![],
+        Some(substreams::TransactionType::Creation),
+        TransactionType::Creation
+    )]
+    #[case::system(vec![], Some(substreams::TransactionType::System), TransactionType::System)]
+    fn test_parse_transaction_type(
+        #[case] to: Vec<u8>,
+        #[case] tx_type: Option<substreams::TransactionType>,
+        #[case] expected: TransactionType,
+    ) {
+        let msg = substreams::Transaction {
+            to,
+            tx_type: tx_type.map(|t| t as i32),
+            ..fixtures::pb_transactions(1, 1)
+        };
+        let block_hash = Bytes::from(1u64).lpad(32, 0);
+
+        let res = Transaction::try_from_message((msg, &block_hash)).unwrap();
+
+        assert_eq!(res.tx_type, expected);
+        assert_eq!(res.is_creation(), expected == TransactionType::Creation);
+    }
+
+    #[rstest]
+    #[case::unspecified(None, TxStatus::Success)]
+    #[case::success(Some(substreams::TransactionStatus::Success), TxStatus::Success)]
+    #[case::reverted(Some(substreams::TransactionStatus::Reverted), TxStatus::Reverted)]
+    fn test_parse_transaction_status(
+        #[case] status: Option<substreams::TransactionStatus>,
+        #[case] expected: TxStatus,
+    ) {
+        let msg = substreams::Transaction {
+            status: status.map(|s| s as i32),
+            ..fixtures::pb_transactions(1, 1)
+        };
+        let block_hash = Bytes::from(1u64).lpad(32, 0);
+
+        let res = Transaction::try_from_message((msg, &block_hash)).unwrap();
+
+        assert_eq!(res.status, expected);
+    }
+
+    #[test]
+    fn test_parse_transaction_access_list() {
+        let msg = substreams::Transaction {
+            access_list: vec![substreams::AccessListEntry {
+                address: vec![1; 20],
+                storage_keys: vec![vec![2; 32]],
+            }],
+            ..fixtures::pb_transactions(1, 1)
+        };
+        let block_hash = Bytes::from(1u64).lpad(32, 0);
+
+        let res = Transaction::try_from_message((msg, &block_hash)).unwrap();
+
+        assert_eq!(
+            res.access_list,
+            Some(vec![(Bytes::from(vec![1; 20]), vec![Bytes::from(vec![2; 32])])])
+        );
+        assert_eq!(
+            Transaction::try_from_message((fixtures::pb_transactions(1, 1), &block_hash))
+                .unwrap()
+                .access_list,
+            None
+        );
+    }
+
     #[test]
     fn test_parse_block_contract_changes() {
         let msg = fixtures::pb_block_contract_changes(0);
@@ -675,11 +1423,39 @@ mod test {
             "ambient".to_string(),
             &HashMap::from([("WeightedPool".to_string(), ProtocolType::default())]),
             0,
+            DEFAULT_MAX_COMPONENTS_PER_BLOCK,
         ))
         .unwrap();
         assert_eq!(res, block_state_changes());
     }
 
+    #[test]
+    fn test_balance_changes_survive_aggregation() {
+        let changes: BlockChanges = BlockContractChanges::try_from_message((
+            fixtures::pb_block_contract_changes(0),
+            "test",
+            Chain::Ethereum,
+            "ambient".to_string(),
+            &HashMap::from([("WeightedPool".to_string(), ProtocolType::default())]),
+            0,
+            DEFAULT_MAX_COMPONENTS_PER_BLOCK,
+        ))
+        .unwrap()
+        .into();
+
+        let res = changes
+            .aggregate_updates(false)
+            .unwrap();
+
+        // Both transactions change the same balance, the later one wins.
+        let weth = Bytes::from_str("0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2").unwrap();
+        let component_id = "d417ff54652c09bd9f31f216b1a2e5d1e28c1dce1ba840c40d16f2b4d09b5902";
+        let balances = &res.component_balances[component_id];
+        assert_eq!(balances.len(), 1);
+        assert_eq!(balances[&weth].balance, Bytes::from(10.encode_to_vec()));
+        assert_eq!(balances[&weth].modify_tx, Bytes::from(vec![0x01]).lpad(32, 0));
+    }
+
     #[test]
     fn test_block_entity_changes_parse_msg() {
         let msg = fixtures::pb_block_entity_changes(0);
@@ -694,8 +1470,317 @@ mod test {
                 ("WeightedPool".to_string(), ProtocolType::default()),
             ]),
             420,
+            DEFAULT_MAX_COMPONENTS_PER_BLOCK,
+            None,
         ))
         .unwrap();
         assert_eq!(res, block_entity_changes());
     }
+
+    fn scoped_data(msg: impl prost::Message, type_url: &str) -> BlockScopedData {
+        let mut data = fixtures::pb_block_scoped_data(msg, None, Some(0));
+        data.output
+            .as_mut()
+            .unwrap()
+            .map_output
+            .as_mut()
+            .unwrap()
+            .type_url = type_url.to_owned();
+        data
+    }
+
+    #[test]
+    fn test_decode_block_scoped_contract_changes() {
+        let data = scoped_data(
+            fixtures::pb_block_contract_changes(0),
+            "type.googleapis.com/tycho.evm.v1.BlockContractChanges",
+        );
+
+        let res = decode_block_scoped(
+            &data,
+            "test",
+            Chain::Ethereum,
+            "ambient",
+            &HashMap::from([("WeightedPool".to_string(), ProtocolType::default())]),
+            &DecodeOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(res, block_state_changes().into());
+    }
+
+    #[test]
+    fn test_decode_block_scoped_entity_changes() {
+        let mut data = scoped_data(
+            fixtures::pb_block_entity_changes(0),
+            "type.googleapis.com/tycho.evm.v1.BlockEntityChanges",
+        );
+        data.final_block_height = 420;
+
+        let res = decode_block_scoped(
+            &data,
+            "test",
+            Chain::Ethereum,
+            "ambient",
+            &HashMap::from([
+                ("Pool".to_string(), ProtocolType::default()),
+                ("WeightedPool".to_string(), ProtocolType::default()),
+            ]),
+            &DecodeOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(res, block_entity_changes().into());
+    }
+
+    #[test]
+    fn test_decode_block_scoped_unknown_type() {
+        let data = scoped_data(fixtures::pb_block_entity_changes(0), "tycho.evm.v1.Unknown");
+
+        let res = decode_block_scoped(
+            &data,
+            "test",
+            Chain::Ethereum,
+            "ambient",
+            &HashMap::new(),
+            &DecodeOptions::default(),
+        );
+
+        assert_eq!(
+            res,
+            Err(ExtractionError::DecodeError("Unknown message type: tycho.evm.v1.Unknown".into()))
+        );
+    }
+
+    #[rstest]
+    #[case::contract_changes(
+        "tycho.evm.v1.BlockContractChanges",
+        fixtures::pb_block_contract_changes(0).encode_to_vec()
+    )]
+    #[case::entity_changes(
+        "tycho.evm.v1.BlockEntityChanges",
+        fixtures::pb_block_entity_changes(0).encode_to_vec()
+    )]
+    fn test_decode_block_scoped_too_many_components(#[case] type_url: &str, #[case] msg: Vec<u8>) {
+        let mut data = fixtures::pb_block_scoped_data((), None, Some(0));
+        let output = data
+            .output
+            .as_mut()
+            .unwrap()
+            .map_output
+            .as_mut()
+            .unwrap();
+        output.type_url = type_url.to_owned();
+        output.value = msg;
+
+        let res = decode_block_scoped(
+            &data,
+            "test",
+            Chain::Ethereum,
+            "ambient",
+            &HashMap::from([
+                ("Pool".to_string(), ProtocolType::default()),
+                ("WeightedPool".to_string(), ProtocolType::default()),
+            ]),
+            &DecodeOptions { max_components: 0, ..Default::default() },
+        );
+
+        assert!(matches!(res, Err(ExtractionError::DecodeError(msg)) if msg.contains("exceeding")));
+    }
+
+    #[test]
+    fn test_ensure_component_limit_counts_creations() {
+        let component = |change: substreams::ChangeType| substreams::ProtocolComponent {
+            change: change.into(),
+            ..Default::default()
+        };
+        let changes = [
+            component(substreams::ChangeType::Creation),
+            component(substreams::ChangeType::Update),
+            component(substreams::ChangeType::Deletion),
+        ];
+
+        assert!(ensure_component_limit(&changes, 1).is_ok());
+        assert!(ensure_component_limit(&changes, 0).is_err());
+    }
+
+    #[rstest]
+    #[case::matching(Some("map_changes"), true)]
+    #[case::unchecked(None, true)]
+    #[case::mismatch(Some("map_protocol_changes"), false)]
+    fn test_decode_block_scoped_module_name(
+        #[case] expected_module: Option<&str>,
+        #[case] is_ok: bool,
+    ) {
+        let data = scoped_data(
+            fixtures::pb_block_contract_changes(0),
+            "type.googleapis.com/tycho.evm.v1.BlockContractChanges",
+        );
+
+        let res = decode_block_scoped(
+            &data,
+            "test",
+            Chain::Ethereum,
+            "ambient",
+            &HashMap::from([("WeightedPool".to_string(), ProtocolType::default())]),
+            &DecodeOptions { expected_module, ..Default::default() },
+        );
+
+        if is_ok {
+            assert_eq!(res.unwrap(), block_state_changes().into());
+        } else {
+            assert_eq!(
+                res,
+                Err(ExtractionError::DecodeError(
+                    "Received output of module map_changes, expected map_protocol_changes".into()
+                ))
+            );
+        }
+    }
+
+    #[test]
+    fn test_decode_block_scoped_payload_hash() {
+        let data = scoped_data(
+            fixtures::pb_block_contract_changes(0),
+            "type.googleapis.com/tycho.evm.v1.BlockContractChanges",
+        );
+        let payload = data
+            .output
+            .as_ref()
+            .unwrap()
+            .map_output
+            .as_ref()
+            .unwrap()
+            .value
+            .clone();
+
+        let res = decode_block_scoped(
+            &data,
+            "test",
+            Chain::Ethereum,
+            "ambient",
+            &HashMap::from([("WeightedPool".to_string(), ProtocolType::default())]),
+            &DecodeOptions { hash_payload: true, ..Default::default() },
+        )
+        .unwrap();
+
+        assert_eq!(res.raw_payload_hash, Some(Bytes::from(keccak256(payload))));
+        // The hash is not part of equality.
+        assert_eq!(res, block_state_changes().into());
+    }
+
+    fn scoped_data_missing_fields() -> BlockScopedData {
+        let mut msg = fixtures::pb_block_contract_changes(0);
+        for change in msg.changes.iter_mut() {
+            if let Some(tx) = change.tx.as_mut() {
+                tx.to = vec![];
+            }
+            for contract in change.contract_changes.iter_mut() {
+                contract.change = substreams::ChangeType::Unspecified.into();
+                contract.balance = vec![];
+                contract.code = vec![];
+            }
+            for component in change.component_changes.iter_mut() {
+                component.change = substreams::ChangeType::Unspecified.into();
+            }
+        }
+        scoped_data(msg, "type.googleapis.com/tycho.evm.v1.BlockContractChanges")
+    }
+
+    #[test]
+    fn test_decode_block_scoped_missing_fields_strict() {
+        let res = decode_block_scoped(
+            &scoped_data_missing_fields(),
+            "test",
+            Chain::Ethereum,
+            "ambient",
+            &HashMap::from([("WeightedPool".to_string(), ProtocolType::default())]),
+            &DecodeOptions::default(),
+        );
+
+        assert!(matches!(res, Err(ExtractionError::DecodeError(_))));
+    }
+
+    #[test]
+    fn test_decode_block_scoped_missing_fields_tolerant() {
+        let res = decode_block_scoped(
+            &scoped_data_missing_fields(),
+            "test",
+            Chain::Ethereum,
+            "ambient",
+            &HashMap::from([("WeightedPool".to_string(), ProtocolType::default())]),
+            &DecodeOptions { parse_mode: ParseMode::Tolerant, ..Default::default() },
+        )
+        .unwrap();
+
+        assert!(!res.txs_with_update.is_empty());
+        for tx in res.txs_with_update.iter() {
+            assert_eq!(tx.tx.to, None);
+            assert!(tx
+                .account_deltas
+                .values()
+                .all(|delta| delta.is_update() && delta.balance.is_none() && delta.code.is_none()));
+            assert!(tx
+                .protocol_components
+                .values()
+                .all(|component| component.change == ChangeType::Creation));
+        }
+    }
+
+    #[test]
+    fn test_decode_store_components() {
+        let mut data = scoped_data(
+            fixtures::pb_block_contract_changes(0),
+            "type.googleapis.com/tycho.evm.v1.BlockContractChanges",
+        );
+        let created = substreams::TransactionChanges {
+            tx: Some(fixtures::pb_transactions(1, 7)),
+            component_changes: vec![fixtures::pb_protocol_component()],
+            ..Default::default()
+        };
+        data.debug_store_outputs = vec![StoreModuleOutput {
+            name: "store_components".to_owned(),
+            debug_store_deltas: vec![
+                StoreDelta {
+                    operation: store_delta::Operation::Create.into(),
+                    key: "pool:1".to_owned(),
+                    new_value: created.encode_to_vec(),
+                    ..Default::default()
+                },
+                StoreDelta {
+                    operation: store_delta::Operation::Update.into(),
+                    key: "pool:1".to_owned(),
+                    new_value: vec![0xff],
+                    ..Default::default()
+                },
+            ],
+            debug_info: None,
+        }];
+        let protocol_types = HashMap::from([("WeightedPool".to_string(), ProtocolType::default())]);
+        let mut changes = decode_block_scoped(
+            &data,
+            "test",
+            Chain::Ethereum,
+            "ambient",
+            &protocol_types,
+            &DecodeOptions::default(),
+        )
+        .unwrap();
+
+        decode_store_components(
+            &data,
+            "store_components",
+            &mut changes,
+            "ambient",
+            &protocol_types,
+        )
+        .unwrap();
+
+        let tx = changes.txs_with_update.last().unwrap();
+        assert_eq!(tx.tx.index, 7);
+        let component = &tx.protocol_components
+            ["d417ff54652c09bd9f31f216b1a2e5d1e28c1dce1ba840c40d16f2b4d09b5902"];
+        assert_eq!(component.change, ChangeType::Creation);
+        assert_eq!(component.creation_tx, tx.tx.hash);
+    }
 }
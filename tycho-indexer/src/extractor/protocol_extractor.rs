@@ -8,7 +8,6 @@ use async_trait::async_trait;
 use chrono::NaiveDateTime;
 use metrics::gauge;
 use mockall::automock;
-use prost::Message;
 use tokio::sync::Mutex;
 use tracing::{debug, error, info, instrument, trace, warn};
 
@@ -21,8 +20,8 @@ use tycho_core::{
             ProtocolComponentStateDelta,
         },
         token::{CurrencyToken, TokenOwnerStore},
-        Address, Balance, BlockHash, Chain, ChangeType, ExtractionState, ExtractorIdentity,
-        ProtocolType, TxHash,
+        Address, Balance, BlockHash, Chain, ChangeType, ComponentId, ExtractionState,
+        ExtractorIdentity, ProtocolType, TxHash,
     },
     storage::{
         ChainGateway, ContractStateGateway, ExtractionStateGateway, ProtocolGateway, StorageError,
@@ -35,17 +34,27 @@ use tycho_storage::postgres::cache::CachedGateway;
 
 use crate::{
     extractor::{
+        block_range::BlockRangeFilter,
         chain_state::ChainState,
-        models::{BlockChanges, BlockContractChanges, BlockEntityChanges},
-        protobuf_deserialisation::TryFromMessage,
+        component_dedupe::ComponentDedupeCache,
+        component_watermarks::ComponentWatermarks,
+        dead_letter::{extract_or_dead_letter, DeadLetterSink},
+        liquidity_filter::MinLiquidityFilter,
+        models::BlockChanges,
+        protobuf_deserialisation::{
+            decode_block_scoped, decode_store_components, DecodeOptions, ParseMode,
+            DEFAULT_MAX_COMPONENTS_PER_BLOCK,
+        },
         protocol_cache::{ProtocolDataCache, ProtocolMemoryCache},
-        reorg_buffer::ReorgBuffer,
+        reorg_buffer::{FinalityDepths, ReorgBuffer},
         BlockUpdateWithCursor, ExtractionError, Extractor, ExtractorMsg,
     },
-    pb,
     pb::sf::substreams::rpc::v2::{BlockScopedData, BlockUndoSignal, ModulesProgress},
 };
 
+/// Key of the extraction state attribute holding the sequence number of the last persisted message.
+const SEQUENCE_ATTRIBUTE: &str = "sequence";
+
 pub struct Inner {
     cursor: Vec<u8>,
     last_processed_block: Option<Block>,
@@ -53,6 +62,8 @@ pub struct Inner {
     last_report_ts: NaiveDateTime,
     last_report_block_number: u64,
     first_message_processed: bool,
+    /// Sequence number of the last emitted message.
+    sequence: u64,
 }
 
 pub struct ProtocolExtractor<G, T> {
@@ -68,6 +79,31 @@ pub struct ProtocolExtractor<G, T> {
     /// Allows to attach some custom logic, e.g. to fix encoding bugs without resync.
     post_processor: Option<fn(BlockChanges) -> BlockChanges>,
     reorg_buffer: Mutex<ReorgBuffer<BlockUpdateWithCursor<BlockChanges>>>,
+    /// Maximum number of new components accepted in a single block.
+    max_components_per_block: usize,
+    /// Name of the substreams module whose output this extractor expects to receive.
+    module_name: Option<String>,
+    /// Name of the substreams store module new components are additionally decoded from.
+    store_components_module: Option<String>,
+    /// Whether to attach the hash of the raw substreams payload to each decoded block.
+    hash_raw_payloads: bool,
+    /// Receives blocks that fail to decode, which are then skipped instead of failing extraction.
+    dead_letter_sink: Option<Arc<dyn DeadLetterSink>>,
+    /// Drops component creations that were already emitted recently.
+    component_dedupe: Option<Mutex<ComponentDedupeCache>>,
+    /// Drops created components with too little liquidity from emitted messages.
+    min_liquidity_filter: Option<MinLiquidityFilter>,
+    /// How missing fields of substreams messages are handled.
+    parse_mode: ParseMode,
+    /// If set, only protocol state attributes with these names are kept.
+    attribute_allowlist: Option<HashSet<String>>,
+    /// Blocks outside of this range are skipped without being decoded.
+    block_range: BlockRangeFilter,
+    /// If set, prefixes the ids of all decoded components, see
+    /// [`BlockChanges::namespace_component_ids`].
+    id_namespace: Option<String>,
+    /// Tracks the last block in which each component changed.
+    component_watermarks: Option<Mutex<ComponentWatermarks>>,
 }
 
 impl<G, T> ProtocolExtractor<G, T>
@@ -105,18 +141,32 @@ where
                         last_report_ts: chrono::Utc::now().naive_utc(),
                         last_report_block_number: 0,
                         first_message_processed: false,
+                        sequence: 0,
                     })),
                     protocol_types,
                     post_processor,
                     reorg_buffer: Mutex::new(ReorgBuffer::new()),
+                    max_components_per_block: DEFAULT_MAX_COMPONENTS_PER_BLOCK,
+                    module_name: None,
+                    store_components_module: None,
+                    hash_raw_payloads: false,
+                    dead_letter_sink: None,
+                    component_dedupe: None,
+                    min_liquidity_filter: None,
+                    parse_mode: ParseMode::default(),
+                    attribute_allowlist: None,
+                    block_range: BlockRangeFilter::default(),
+                    id_namespace: None,
+                    component_watermarks: None,
                 }
             }
-            Ok(cursor) => {
+            Ok((cursor, sequence)) => {
                 let cursor_hex = hex::encode(&cursor);
                 info!(
                     ?name,
                     ?chain,
                     cursor = &cursor_hex,
+                    sequence,
                     "Found existing cursor! Resuming extractor.."
                 );
                 ProtocolExtractor {
@@ -130,6 +180,7 @@ where
                         last_report_ts: chrono::Local::now().naive_utc(),
                         last_report_block_number: 0,
                         first_message_processed: false,
+                        sequence,
                     })),
                     protocol_system,
                     protocol_cache,
@@ -137,6 +188,18 @@ where
                     protocol_types,
                     post_processor,
                     reorg_buffer: Mutex::new(ReorgBuffer::new()),
+                    max_components_per_block: DEFAULT_MAX_COMPONENTS_PER_BLOCK,
+                    module_name: None,
+                    store_components_module: None,
+                    hash_raw_payloads: false,
+                    dead_letter_sink: None,
+                    component_dedupe: None,
+                    min_liquidity_filter: None,
+                    parse_mode: ParseMode::default(),
+                    attribute_allowlist: None,
+                    block_range: BlockRangeFilter::default(),
+                    id_namespace: None,
+                    component_watermarks: None,
                 }
             }
             Err(err) => return Err(ExtractionError::Setup(err.to_string())),
@@ -146,6 +209,127 @@ where
         Ok(res)
     }
 
+    /// Sets the maximum number of new components accepted in a single block.
+    ///
+    /// Blocks exceeding this limit fail to decode. Defaults to
+    /// [`DEFAULT_MAX_COMPONENTS_PER_BLOCK`].
+    pub fn with_max_components_per_block(mut self, max_components: usize) -> Self {
+        self.max_components_per_block = max_components;
+        self
+    }
+
+    /// Sets the name of the substreams module the received block data must originate from.
+    ///
+    /// Messages emitted by any other module fail to decode. By default, the module name is not
+    /// checked.
+    pub fn with_module_name(mut self, module_name: &str) -> Self {
+        self.module_name = Some(module_name.to_owned());
+        self
+    }
+
+    /// Sets the name of a substreams store module to additionally decode new components from.
+    ///
+    /// Components created in this store's deltas are added to each block's changes. Store deltas
+    /// are only streamed in substreams development mode. Disabled by default.
+    pub fn with_store_components_module(mut self, store_module: &str) -> Self {
+        self.store_components_module = Some(store_module.to_owned());
+        self
+    }
+
+    /// Attaches the Keccak-256 hash of the raw substreams payload to each decoded block.
+    ///
+    /// Allows operators to verify decoded messages against the original payload, e.g. for audit
+    /// replays. Disabled by default.
+    pub fn with_raw_payload_hashes(mut self) -> Self {
+        self.hash_raw_payloads = true;
+        self
+    }
+
+    /// Routes blocks that fail to decode to `sink` and continues with the next block.
+    ///
+    /// By default, a block that fails to decode stops the extractor.
+    pub fn with_dead_letter_sink(mut self, sink: Arc<dyn DeadLetterSink>) -> Self {
+        self.dead_letter_sink = Some(sink);
+        self
+    }
+
+    /// Drops creations of components identical to one of the last `capacity` created components.
+    ///
+    /// Useful for substreams that re-report component creations in later blocks. Disabled by
+    /// default.
+    pub fn with_component_dedupe(mut self, capacity: usize) -> Self {
+        self.component_dedupe = Some(Mutex::new(ComponentDedupeCache::new(capacity)));
+        self
+    }
+
+    /// Drops created components whose liquidity is below the threshold of `filter` from emitted
+    /// messages. Components are still persisted. Disabled by default.
+    pub fn with_min_liquidity_filter(mut self, filter: MinLiquidityFilter) -> Self {
+        self.min_liquidity_filter = Some(filter);
+        self
+    }
+
+    /// Sets how missing fields of substreams messages are handled, see [`ParseMode`]. Strict by
+    /// default.
+    pub fn with_parse_mode(mut self, parse_mode: ParseMode) -> Self {
+        self.parse_mode = parse_mode;
+        self
+    }
+
+    /// Only keeps protocol state attributes whose name is in `allowlist`, other attributes are
+    /// dropped while decoding. All attributes are kept by default.
+    pub fn with_attribute_allowlist(mut self, allowlist: HashSet<String>) -> Self {
+        self.attribute_allowlist = Some(allowlist);
+        self
+    }
+
+    /// Only processes blocks within `range`. Skipped blocks still advance the cursor. All blocks
+    /// are processed by default.
+    pub fn with_block_range(mut self, range: BlockRangeFilter) -> Self {
+        self.block_range = range;
+        self
+    }
+
+    /// Prefixes the ids of all components emitted by this extractor with `namespace`, so they
+    /// don't collide with ids of other extractors in a shared store. Unset by default.
+    pub fn with_id_namespace(mut self, namespace: &str) -> Self {
+        self.id_namespace = Some(namespace.to_string());
+        self
+    }
+
+    /// Treats blocks buried under the finality depth of this extractor's chain as final, see
+    /// [`FinalityDepths`]. By default, only the finality signal of the stream is used.
+    pub fn with_finality_depths(mut self, depths: &FinalityDepths) -> Self {
+        self.reorg_buffer
+            .get_mut()
+            .set_finality_depth(depths.get(self.chain));
+        self
+    }
+
+    /// Tracks the last block in which each component changed, see [`Self::stale_components`].
+    /// Disabled by default.
+    pub fn with_component_watermarks(mut self) -> Self {
+        self.component_watermarks = Some(Mutex::new(ComponentWatermarks::new()));
+        self
+    }
+
+    /// Returns the ids of components that didn't change in the last `threshold` blocks before
+    /// the last processed block.
+    ///
+    /// Empty if watermarks are not tracked or no block was processed yet. Updates of reverted
+    /// blocks are not forgotten, so a component may appear fresher than it is after a revert.
+    pub async fn stale_components(&self, threshold: u64) -> Vec<ComponentId> {
+        let (Some(watermarks), Some(block)) =
+            (&self.component_watermarks, self.get_last_processed_block().await)
+        else {
+            return Vec::new();
+        };
+        watermarks
+            .lock()
+            .await
+            .stale_components(block.number, threshold)
+    }
+
     async fn update_cursor(&self, cursor: String) {
         let mut state = self.inner.lock().await;
         state.cursor = cursor.into();
@@ -160,6 +344,13 @@ where
             .first_message_processed
     }
 
+    /// Returns the sequence number for the next emitted message.
+    async fn next_sequence(&self) -> u64 {
+        let mut state = self.inner.lock().await;
+        state.sequence += 1;
+        state.sequence
+    }
+
     async fn update_last_processed_block(&self, block: Block) {
         let mut state = self.inner.lock().await;
         state.last_processed_block = Some(block);
@@ -251,10 +442,14 @@ where
         };
 
         // collect token decimals and prices to calculate tvl in the next step
-        // most of this data should be in the cache.
+        // most of this data should be in the cache. Native token balances are valued at the
+        // price of the wrapped native token, so its price is always requested.
         let addresses = balances
             .values()
             .flat_map(|b| b.clone().into_keys())
+            .chain(self.chain.wrapped_native_token())
+            .collect::<HashSet<_>>()
+            .into_iter()
             .collect::<Vec<_>>();
 
         let prices = self
@@ -276,17 +471,7 @@ where
         // calculate new tvl values
         let tvl_updates = balances
             .iter()
-            .map(|(cid, bal)| {
-                let component_tvl: f64 = bal
-                    .iter()
-                    .filter_map(|(addr, bal)| {
-                        let price = *prices.get(addr)?;
-                        let tvl = bal.balance_float / price;
-                        Some(tvl)
-                    })
-                    .sum();
-                (cid.clone(), component_tvl)
-            })
+            .map(|(cid, bal)| (cid.clone(), component_tvl(self.chain, bal, &prices)))
             .collect::<HashMap<_, _>>();
 
         msg.component_tvl = tvl_updates;
@@ -459,105 +644,48 @@ where
             .collect();
         Ok(new_tokens)
     }
-}
-
-#[async_trait]
-impl<G, T> Extractor for ProtocolExtractor<G, T>
-where
-    G: ExtractorGateway,
-    T: TokenPreProcessor,
-{
-    fn get_id(&self) -> ExtractorIdentity {
-        ExtractorIdentity::new(self.chain, &self.name)
-    }
-
-    /// Make sure that the protocol types are present in the database.
-    async fn ensure_protocol_types(&self) {
-        let protocol_types: Vec<ProtocolType> = self
-            .protocol_types
-            .values()
-            .cloned()
-            .collect();
-        self.gateway
-            .ensure_protocol_types(&protocol_types)
-            .await;
-    }
 
-    async fn get_cursor(&self) -> String {
-        String::from_utf8(self.inner.lock().await.cursor.clone()).expect("Cursor is utf8")
-    }
-
-    async fn get_last_processed_block(&self) -> Option<Block> {
-        self.inner
-            .lock()
-            .await
-            .last_processed_block
-            .clone()
-    }
-
-    #[instrument(skip_all, fields(block_number))]
-    async fn handle_tick_scoped_data(
+    /// Processes a block, obtaining its changes from `decode` unless the block is skipped.
+    async fn handle_tick<D>(
         &self,
         inp: BlockScopedData,
-    ) -> Result<Option<ExtractorMsg>, ExtractionError> {
-        let data = inp
-            .output
-            .as_ref()
-            .unwrap()
-            .map_output
-            .as_ref()
-            .unwrap();
+        decode: D,
+    ) -> Result<Option<ExtractorMsg>, ExtractionError>
+    where
+        D: FnOnce(&BlockScopedData) -> Result<BlockChanges, ExtractionError> + Send,
+    {
+        if !self.block_range.accepts(&inp) {
+            trace!(range = ?self.block_range, "Skipping block outside of block range");
+            self.update_cursor(inp.cursor).await;
+            return Ok(None);
+        }
 
-        // Backwards Compatibility:
-        // Check if message_type ends with BlockAccountChanges or BlockEntityChanges. If it does,
-        // then we need to decode as the corresponding message type, then convert it to BlockChanges
-        let msg = match data.type_url.as_str() {
-            url if url.ends_with("BlockChanges") => {
-                let raw_msg = pb::tycho::evm::v1::BlockChanges::decode(data.value.as_slice())?;
-                trace!(?raw_msg, "Received BlockChanges message");
-                BlockChanges::try_from_message((
-                    raw_msg,
-                    &self.name,
-                    self.chain,
-                    &self.protocol_system,
-                    &self.protocol_types,
-                    inp.final_block_height,
-                ))
-            }
-            url if url.ends_with("BlockContractChanges") => {
-                let raw_msg =
-                    pb::tycho::evm::v1::BlockContractChanges::decode(data.value.as_slice())?;
-                trace!(?raw_msg, "Received BlockContractChanges message");
-                BlockContractChanges::try_from_message((
-                    raw_msg,
-                    &self.name,
-                    self.chain,
-                    self.protocol_system.clone(),
-                    &self.protocol_types,
-                    inp.final_block_height,
-                ))
-                .map(Into::into)
-            }
-            url if url.ends_with("BlockEntityChanges") => {
-                let raw_msg =
-                    pb::tycho::evm::v1::BlockEntityChanges::decode(data.value.as_slice())?;
-                trace!(?raw_msg, "Received BlockEntityChanges message");
-                BlockEntityChanges::try_from_message((
-                    raw_msg,
-                    &self.name,
-                    self.chain,
-                    &self.protocol_system,
-                    &self.protocol_types,
-                    inp.final_block_height,
-                ))
-                .map(Into::into)
-            }
-            _ => return Err(ExtractionError::DecodeError("Unknown message type".into())),
+        let msg = match &self.dead_letter_sink {
+            Some(sink) => match extract_or_dead_letter(&inp, decode, sink.as_ref())? {
+                Some(changes) => Ok(changes),
+                None => {
+                    self.update_cursor(inp.cursor).await;
+                    return Ok(None);
+                }
+            },
+            None => decode(&inp),
         };
 
         let msg = match msg {
-            Ok(changes) => {
+            Ok(mut changes) => {
                 tracing::Span::current().record("block_number", changes.block.number);
+                if let Some(store_module) = &self.store_components_module {
+                    decode_store_components(
+                        &inp,
+                        store_module,
+                        &mut changes,
+                        &self.protocol_system,
+                        &self.protocol_types,
+                    )?;
+                }
+                if let Some(namespace) = &self.id_namespace {
+                    changes.namespace_component_ids(namespace);
+                }
                 changes
             }
             Err(ExtractionError::Empty) => {
@@ -570,6 +698,14 @@ where
         let mut msg =
             if let Some(post_process_f) = self.post_processor { post_process_f(msg) } else { msg };
 
+        if let Some(dedupe) = &self.component_dedupe {
+            dedupe.lock().await.filter(&mut msg);
+        }
+
+        if let Some(watermarks) = &self.component_watermarks {
+            watermarks.lock().await.update(&msg);
+        }
+
         msg.new_tokens = self
             .construct_currency_tokens(&msg)
             .await?;
@@ -585,11 +721,12 @@ where
         // Depending on how Substreams handle them, this condition could be problematic for single
         // block finality blockchains.
         let is_syncing = inp.final_block_height >= msg.block.number;
+        let sequence = self.next_sequence().await;
         {
             // keep reorg buffer guard within a limited scope
             let mut reorg_buffer = self.reorg_buffer.lock().await;
             reorg_buffer
-                .insert_block(BlockUpdateWithCursor::new(msg.clone(), inp.cursor.clone()))
+                .insert_block(BlockUpdateWithCursor::new(msg.clone(), inp.cursor.clone(), sequence))
                 .map_err(ExtractionError::Storage)?;
 
             let mut msgs = reorg_buffer
@@ -605,7 +742,7 @@ where
                 let force_db_commit = if is_syncing { false } else { msgs.peek().is_none() };
 
                 self.gateway
-                    .advance(msg.block_update(), msg.cursor(), force_db_commit)
+                    .advance(msg.block_update(), msg.cursor(), msg.sequence(), force_db_commit)
                     .await?;
             }
         }
@@ -620,9 +757,13 @@ where
 
         self.update_cursor(inp.cursor).await;
 
-        let mut changes = msg.aggregate_updates()?;
+        let mut changes = msg.aggregate_updates(false)?;
         self.handle_tvl_changes(&mut changes)
             .await?;
+        if let Some(filter) = &self.min_liquidity_filter {
+            filter.apply(&mut changes);
+        }
+        changes.seq = sequence;
 
         if !is_syncing {
             debug!(
@@ -634,7 +775,77 @@ where
                 "ProcessedMessage"
             );
         }
-        return Ok(Some(Arc::new(changes)));
+        Ok(Some(Arc::new(changes)))
+    }
+}
+
+#[async_trait]
+impl<G, T> Extractor for ProtocolExtractor<G, T>
+where
+    G: ExtractorGateway,
+    T: TokenPreProcessor,
+{
+    fn get_id(&self) -> ExtractorIdentity {
+        ExtractorIdentity::new(self.chain, &self.name)
+    }
+
+    /// Make sure that the protocol types are present in the database.
+    async fn ensure_protocol_types(&self) {
+        let protocol_types: Vec<ProtocolType> = self
+            .protocol_types
+            .values()
+            .cloned()
+            .collect();
+        self.gateway
+            .ensure_protocol_types(&protocol_types)
+            .await;
+    }
+
+    async fn get_cursor(&self) -> String {
+        String::from_utf8(self.inner.lock().await.cursor.clone()).expect("Cursor is utf8")
+    }
+
+    async fn get_last_processed_block(&self) -> Option<Block> {
+        self.inner
+            .lock()
+            .await
+            .last_processed_block
+            .clone()
+    }
+
+    fn decode_block(&self, inp: &BlockScopedData) -> Result<BlockChanges, ExtractionError> {
+        decode_block_scoped(
+            inp,
+            &self.name,
+            self.chain,
+            &self.protocol_system,
+            &self.protocol_types,
+            &DecodeOptions {
+                max_components: self.max_components_per_block,
+                expected_module: self.module_name.as_deref(),
+                hash_payload: self.hash_raw_payloads,
+                parse_mode: self.parse_mode,
+                attribute_allowlist: self.attribute_allowlist.as_ref(),
+            },
+        )
+    }
+
+    #[instrument(skip_all, fields(block_number))]
+    async fn handle_tick_scoped_data(
+        &self,
+        inp: BlockScopedData,
+    ) -> Result<Option<ExtractorMsg>, ExtractionError> {
+        self.handle_tick(inp, |data| self.decode_block(data))
+            .await
+    }
+
+    #[instrument(skip_all, fields(block_number))]
+    async fn handle_decoded_tick(
+        &self,
+        inp: BlockScopedData,
+        changes: Result<BlockChanges, ExtractionError>,
+    ) -> Result<Option<ExtractorMsg>, ExtractionError> {
+        self.handle_tick(inp, |_| changes).await
     }
 
     #[instrument(skip_all, fields(target_hash, target_number))]
@@ -669,6 +880,11 @@ where
 
         let mut reorg_buffer = self.reorg_buffer.lock().await;
 
+        // Reverted creations may be emitted again, so they must not be deduplicated.
+        if let Some(dedupe) = &self.component_dedupe {
+            dedupe.lock().await.clear();
+        }
+
         // Purge the buffer
         let reverted_state = reorg_buffer
             .purge(block_hash)
@@ -697,7 +913,7 @@ where
                                         !reverted_creations.contains_key(id)
                                     {
                                         match new_component.change {
-                                            ChangeType::Update => {}
+                                            ChangeType::Update | ChangeType::Unspecified => {}
                                             ChangeType::Deletion => {
                                                 let mut reverted_deletion = new_component.clone();
                                                 reverted_deletion.change = ChangeType::Creation;
@@ -826,6 +1042,7 @@ where
                             slots: HashMap::new(),
                             balance: None, //TODO: handle balance changes
                             code: None,    //TODO: handle code changes
+                            proxy_impl: None,
                             change: ChangeType::Update,
                         })
                         .slots
@@ -988,9 +1205,11 @@ where
             account_deltas,
             new_tokens: HashMap::new(),
             new_protocol_components: reverted_components_deletions,
+            updated_protocol_components: HashMap::new(),
             deleted_protocol_components: reverted_components_creations,
             component_balances: combined_balances,
             component_tvl: HashMap::new(),
+            seq: self.next_sequence().await,
         };
 
         debug!("Successfully retrieved all previous states during revert!");
@@ -1006,6 +1225,32 @@ where
         todo!()
     }
 }
+
+/// Sums the value of a component's token balances.
+///
+/// Wrapping and unwrapping the native token (e.g. WETH deposit and withdraw) moves value between
+/// the native and the wrapped balance without a transfer. Native balances are therefore valued
+/// at the wrapped token's price, so such moves don't change the component's tvl.
+fn component_tvl(
+    chain: Chain,
+    balances: &HashMap<Address, ComponentBalance>,
+    prices: &HashMap<Address, f64>,
+) -> f64 {
+    let native_token = chain.native_token();
+    let wrapped_native_token = chain.wrapped_native_token();
+    balances
+        .iter()
+        .filter_map(|(addr, bal)| {
+            let price_token = match &wrapped_native_token {
+                Some(wrapped) if *addr == native_token => wrapped,
+                _ => addr,
+            };
+            let price = *prices.get(price_token)?;
+            Some(bal.balance_float / price)
+        })
+        .sum()
+}
+
 pub struct ExtractorPgGateway {
     name: String,
     chain: Chain,
@@ -1016,7 +1261,8 @@ pub struct ExtractorPgGateway {
 #[automock]
 #[async_trait]
 pub trait ExtractorGateway: Send + Sync {
-    async fn get_cursor(&self) -> Result<Vec<u8>, StorageError>;
+    /// Returns the last persisted cursor and the sequence number of the message emitted for it.
+    async fn get_cursor(&self) -> Result<(Vec<u8>, u64), StorageError>;
 
     async fn ensure_protocol_types(&self, new_protocol_types: &[ProtocolType]);
 
@@ -1024,6 +1270,7 @@ pub trait ExtractorGateway: Send + Sync {
         &self,
         changes: &BlockChanges,
         new_cursor: &str,
+        sequence: u64,
         force_commit: bool,
     ) -> Result<(), StorageError>;
 
@@ -1055,11 +1302,12 @@ impl ExtractorPgGateway {
         &self,
         new_cursor: &str,
         block_hash: BlockHash,
+        sequence: u64,
     ) -> Result<(), StorageError> {
         let state = ExtractionState::new(
             self.name.to_string(),
             self.chain,
-            None,
+            Some(serde_json::json!({ SEQUENCE_ATTRIBUTE: sequence })),
             new_cursor.as_bytes(),
             block_hash,
         );
@@ -1069,18 +1317,24 @@ impl ExtractorPgGateway {
         Ok(())
     }
 
-    async fn get_last_cursor(&self) -> Result<Vec<u8>, StorageError> {
+    async fn get_last_cursor(&self) -> Result<(Vec<u8>, u64), StorageError> {
         let state = self
             .state_gateway
             .get_state(&self.name, &self.chain)
             .await?;
-        Ok(state.cursor)
+        // States saved before sequence numbers were persisted resume from zero.
+        let sequence = state
+            .attributes
+            .get(SEQUENCE_ATTRIBUTE)
+            .and_then(serde_json::Value::as_u64)
+            .unwrap_or(0);
+        Ok((state.cursor, sequence))
     }
 }
 
 #[async_trait]
 impl ExtractorGateway for ExtractorPgGateway {
-    async fn get_cursor(&self) -> Result<Vec<u8>, StorageError> {
+    async fn get_cursor(&self) -> Result<(Vec<u8>, u64), StorageError> {
         self.get_last_cursor().await
     }
 
@@ -1095,6 +1349,7 @@ impl ExtractorGateway for ExtractorPgGateway {
         &self,
         changes: &BlockChanges,
         new_cursor: &str,
+        sequence: u64,
         force_commit: bool,
     ) -> Result<(), StorageError> {
         self.state_gateway
@@ -1209,7 +1464,7 @@ impl ExtractorGateway for ExtractorPgGateway {
                 .await?;
         }
 
-        self.save_cursor(new_cursor, changes.block.hash.clone())
+        self.save_cursor(new_cursor, changes.block.hash.clone(), sequence)
             .await?;
 
         let batch_size = if force_commit { 0 } else { self.db_tx_batch_size };
@@ -1249,16 +1504,62 @@ impl ExtractorGateway for ExtractorPgGateway {
 mod test {
     use float_eq::assert_float_eq;
     use mockall::mock;
+    use rstest::rstest;
 
     use super::*;
 
-    use crate::{pb::testing::fixtures as pb_fixtures, testing::MockGateway};
+    use crate::{
+        pb,
+        pb::{sf::substreams::v1::BlockRef, testing::fixtures as pb_fixtures},
+        testing::MockGateway,
+    };
 
     use tycho_core::{
-        models::blockchain::{Transaction, TxWithChanges},
+        models::{
+            blockchain::{Transaction, TxWithChanges},
+            NormalisedMessage,
+        },
         traits::TokenOwnerFinding,
     };
 
+    fn tvl_balances(native: f64, wrapped: f64) -> HashMap<Address, ComponentBalance> {
+        [(Chain::Ethereum.native_token(), native), (weth(), wrapped)]
+            .into_iter()
+            .map(|(token, balance_float)| {
+                let balance = ComponentBalance::new(
+                    token.clone(),
+                    Bytes::from(balance_float as u64),
+                    balance_float,
+                    Bytes::zero(32),
+                    "pc_1",
+                );
+                (token, balance)
+            })
+            .collect()
+    }
+
+    fn weth() -> Address {
+        Chain::Ethereum
+            .wrapped_native_token()
+            .unwrap()
+    }
+
+    #[rstest]
+    #[case::deposit(tvl_balances(3.0, 1.0), tvl_balances(2.0, 2.0))]
+    #[case::withdraw(tvl_balances(1.0, 3.0), tvl_balances(2.0, 2.0))]
+    fn test_component_tvl_wrapped_native(
+        #[case] before: HashMap<Address, ComponentBalance>,
+        #[case] after: HashMap<Address, ComponentBalance>,
+    ) {
+        let prices = HashMap::from([(weth(), 0.5)]);
+
+        let tvl_before = component_tvl(Chain::Ethereum, &before, &prices);
+        let tvl_after = component_tvl(Chain::Ethereum, &after, &prices);
+
+        assert_float_eq!(tvl_before, 8.0, rmax <= 1e-9);
+        assert_float_eq!(tvl_after, tvl_before, rmax <= 1e-9);
+    }
+
     mock! {
         pub TokenPreProcessor {}
 
@@ -1311,7 +1612,7 @@ mod test {
             .returning(|_| ());
         gw.expect_get_cursor()
             .times(1)
-            .returning(|| Ok("cursor".into()));
+            .returning(|| Ok(("cursor".into(), 0)));
 
         let extractor = create_extractor(gw).await;
         let res = extractor.get_cursor().await;
@@ -1327,10 +1628,10 @@ mod test {
             .returning(|_| ());
         gw.expect_get_cursor()
             .times(1)
-            .returning(|| Ok("cursor".into()));
+            .returning(|| Ok(("cursor".into(), 0)));
         gw.expect_advance()
             .times(1)
-            .returning(|_, _, _| Ok(()));
+            .returning(|_, _, _, _| Ok(()));
 
         let extractor = create_extractor(gw).await;
 
@@ -1365,6 +1666,177 @@ mod test {
         assert_eq!(extractor.get_cursor().await, "cursor@2");
     }
 
+    #[tokio::test]
+    async fn test_handle_decoded_tick() {
+        let mut gw = MockExtractorGateway::new();
+        gw.expect_ensure_protocol_types()
+            .times(1)
+            .returning(|_| ());
+        gw.expect_get_cursor()
+            .times(1)
+            .returning(|| Ok(("cursor".into(), 0)));
+        let extractor = create_extractor(gw).await;
+        let inp = pb_fixtures::pb_block_scoped_data(
+            pb::tycho::evm::v1::BlockChanges {
+                block: Some(pb_fixtures::pb_blocks(1)),
+                changes: vec![],
+            },
+            Some("cursor@1"),
+            Some(1),
+        );
+
+        let changes = extractor.decode_block(&inp);
+        let res = extractor
+            .handle_decoded_tick(inp, changes)
+            .await
+            .unwrap();
+
+        assert!(res.is_some());
+        assert_eq!(extractor.get_cursor().await, "cursor@1");
+    }
+
+    #[tokio::test]
+    async fn test_handle_tick_scoped_data_as_trait_object() {
+        let mut gw = MockExtractorGateway::new();
+        gw.expect_ensure_protocol_types()
+            .times(1)
+            .returning(|_| ());
+        gw.expect_get_cursor()
+            .times(1)
+            .returning(|| Ok(("cursor".into(), 0)));
+        // A single block is never drained from the reorg buffer.
+        gw.expect_advance()
+            .times(0)
+            .returning(|_, _, _, _| Ok(()));
+        let extractor: Arc<dyn Extractor> = Arc::new(create_extractor(gw).await);
+
+        // Extractors are shared across tasks by the runner, so they must be usable as `Send +
+        // Sync` trait objects.
+        let res = tokio::spawn(async move {
+            let msg = extractor
+                .handle_tick_scoped_data(pb_fixtures::pb_block_scoped_data(
+                    pb::tycho::evm::v1::BlockChanges {
+                        block: Some(pb_fixtures::pb_blocks(1)),
+                        changes: vec![],
+                    },
+                    Some("cursor@1"),
+                    Some(1),
+                ))
+                .await
+                .unwrap()
+                .unwrap();
+            (extractor.get_id(), msg.source(), extractor.get_cursor().await)
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(res.0, res.1);
+        assert_eq!(res.2, "cursor@1");
+    }
+
+    fn block_and_sequence(msg: &ExtractorMsg) -> (u64, u64) {
+        let changes = msg
+            .as_any()
+            .downcast_ref::<BlockAggregatedChanges>()
+            .expect("not good type");
+        (changes.block.number, changes.sequence())
+    }
+
+    #[tokio::test]
+    async fn test_sequence_increases_across_revert() {
+        let mut gw = MockExtractorGateway::new();
+        gw.expect_ensure_protocol_types()
+            .times(1)
+            .returning(|_| ());
+        gw.expect_get_cursor()
+            .times(1)
+            .returning(|| Ok(("cursor".into(), 0)));
+        gw.expect_advance()
+            .returning(|_, _, _, _| Ok(()));
+        gw.expect_get_protocol_states()
+            .returning(|_| Ok(Vec::new()));
+        gw.expect_get_contracts()
+            .returning(|_| Ok(Vec::new()));
+        gw.expect_get_components_balances()
+            .returning(|_| Ok(HashMap::new()));
+
+        let extractor = create_extractor(gw).await;
+
+        let mut emitted = Vec::new();
+        for version in 1..=3 {
+            let msg = extractor
+                .handle_tick_scoped_data(pb_fixtures::pb_block_scoped_data(
+                    pb::tycho::evm::v1::BlockChanges {
+                        block: Some(pb_fixtures::pb_blocks(version)),
+                        changes: vec![],
+                    },
+                    Some(format!("cursor@{}", version).as_str()),
+                    Some(1),
+                ))
+                .await
+                .unwrap()
+                .unwrap();
+            emitted.push(block_and_sequence(&msg));
+        }
+        let revert = extractor
+            .handle_revert(BlockUndoSignal {
+                last_valid_block: Some(BlockRef {
+                    id: "0x0000000000000000000000000000000000000000000000000000000000000002"
+                        .to_string(),
+                    number: 2,
+                }),
+                last_valid_cursor: "cursor@2".into(),
+            })
+            .await
+            .unwrap()
+            .unwrap();
+        emitted.push(block_and_sequence(&revert));
+
+        assert_eq!(emitted, vec![(1, 1), (2, 2), (3, 3), (2, 4)]);
+    }
+
+    #[tokio::test]
+    async fn test_sequence_resumes_from_persisted_state() {
+        let mut gw = MockExtractorGateway::new();
+        gw.expect_ensure_protocol_types()
+            .times(1)
+            .returning(|_| ());
+        gw.expect_get_cursor()
+            .times(1)
+            .returning(|| Ok(("cursor".into(), 41)));
+        gw.expect_advance()
+            .withf(|_, cursor, sequence, _| cursor == "cursor@1" && *sequence == 42)
+            .times(1)
+            .returning(|_, _, _, _| Ok(()));
+        gw.expect_get_protocol_states()
+            .returning(|_| Ok(Vec::new()));
+        gw.expect_get_contracts()
+            .returning(|_| Ok(Vec::new()));
+        gw.expect_get_components_balances()
+            .returning(|_| Ok(HashMap::new()));
+
+        let extractor = create_extractor(gw).await;
+
+        let mut emitted = Vec::new();
+        for version in 1..=2 {
+            let msg = extractor
+                .handle_tick_scoped_data(pb_fixtures::pb_block_scoped_data(
+                    pb::tycho::evm::v1::BlockChanges {
+                        block: Some(pb_fixtures::pb_blocks(version)),
+                        changes: vec![],
+                    },
+                    Some(format!("cursor@{}", version).as_str()),
+                    Some(version),
+                ))
+                .await
+                .unwrap()
+                .unwrap();
+            emitted.push(block_and_sequence(&msg));
+        }
+
+        assert_eq!(emitted, vec![(1, 42), (2, 43)]);
+    }
+
     #[tokio::test]
     async fn test_handle_tick_scoped_data_old_native_msg() {
         let mut gw = MockExtractorGateway::new();
@@ -1373,10 +1845,10 @@ mod test {
             .returning(|_| ());
         gw.expect_get_cursor()
             .times(1)
-            .returning(|| Ok("cursor".into()));
+            .returning(|| Ok(("cursor".into(), 0)));
         gw.expect_advance()
             .times(1)
-            .returning(|_, _, _| Ok(()));
+            .returning(|_, _, _, _| Ok(()));
 
         let extractor = create_extractor(gw).await;
 
@@ -1429,10 +1901,10 @@ mod test {
             .returning(|_| ());
         gw.expect_get_cursor()
             .times(1)
-            .returning(|| Ok("cursor".into()));
+            .returning(|| Ok(("cursor".into(), 0)));
         gw.expect_advance()
             .times(1)
-            .returning(|_, _, _| Ok(()));
+            .returning(|_, _, _, _| Ok(()));
 
         let extractor = create_extractor(gw).await;
 
@@ -1484,10 +1956,10 @@ mod test {
             .returning(|_| ());
         gw.expect_get_cursor()
             .times(1)
-            .returning(|| Ok("cursor".into()));
+            .returning(|| Ok(("cursor".into(), 0)));
         gw.expect_advance()
             .times(0)
-            .returning(|_, _, _| Ok(()));
+            .returning(|_, _, _, _| Ok(()));
 
         let extractor = create_extractor(gw).await;
 
@@ -1504,6 +1976,43 @@ mod test {
         assert_eq!(extractor.get_cursor().await, "cursor@420");
     }
 
+    #[tokio::test]
+    async fn test_handle_tick_scoped_data_unspecified_component() {
+        let mut gw = MockExtractorGateway::new();
+        gw.expect_ensure_protocol_types()
+            .times(1)
+            .returning(|_| ());
+        gw.expect_get_cursor()
+            .times(1)
+            .returning(|| Ok(("cursor".into(), 0)));
+        gw.expect_advance()
+            .times(0)
+            .returning(|_, _, _, _| Ok(()));
+
+        let extractor = create_extractor(gw).await;
+        let mut component = pb_fixtures::pb_protocol_component();
+        component.change = pb::tycho::evm::v1::ChangeType::Unspecified.into();
+        let msg = pb::tycho::evm::v1::BlockChanges {
+            block: Some(pb_fixtures::pb_blocks(1)),
+            changes: vec![pb::tycho::evm::v1::TransactionChanges {
+                tx: Some(pb_fixtures::pb_transactions(1, 1)),
+                component_changes: vec![component],
+                ..Default::default()
+            }],
+        };
+
+        let res = extractor
+            .handle_tick_scoped_data(pb_fixtures::pb_block_scoped_data(
+                msg,
+                Some("cursor@1"),
+                Some(1),
+            ))
+            .await;
+
+        assert!(matches!(res, Err(ExtractionError::DecodeError(_))));
+        assert_eq!(extractor.get_cursor().await, "cursor");
+    }
+
     fn token_prices() -> HashMap<Bytes, f64> {
         HashMap::from([
             (
@@ -1534,6 +2043,7 @@ mod test {
                                 .parse()
                                 .unwrap(),
                         ],
+                        change: ChangeType::Creation,
                         ..Default::default()
                     },
                 )]),
@@ -1586,7 +2096,7 @@ mod test {
         extractor_gw
             .expect_get_cursor()
             .times(1)
-            .returning(|| Ok("cursor".into()));
+            .returning(|| Ok(("cursor".into(), 0)));
         let extractor = ProtocolExtractor::new(
             extractor_gw,
             EXTRACTOR_NAME,
@@ -1707,7 +2217,7 @@ mod test {
         extractor_gw
             .expect_get_cursor()
             .times(1)
-            .returning(|| Ok("cursor".into()));
+            .returning(|| Ok(("cursor".into(), 0)));
         extractor_gw
             .expect_get_components_balances()
             .return_once(|_| Ok(HashMap::new()));
@@ -1904,7 +2414,7 @@ mod test_serial_db {
             let state = ExtractionState::new(
                 "test".to_string(),
                 Chain::Ethereum,
-                None,
+                Some(serde_json::json!({ SEQUENCE_ATTRIBUTE: 7 })),
                 "cursor@420".as_bytes(),
                 Bytes::from_str("88e96d4537bea4d9c05d12549907b32561d3bf31f45aae734cdc119f13406cb6")
                     .unwrap(),
@@ -1922,6 +2432,7 @@ mod test_serial_db {
                     .unwrap(),
                     parent_hash: Bytes::default(),
                     ts: db_fixtures::yesterday_one_am(),
+                    base_fee_per_gas: None,
                 }])
                 .await
                 .expect("block insertion succeeded");
@@ -1934,12 +2445,13 @@ mod test_serial_db {
                 .await
                 .expect("gw transaction failed");
 
-            let cursor = gw
+            let (cursor, sequence) = gw
                 .get_last_cursor()
                 .await
                 .expect("get cursor should succeed");
 
             assert_eq!(cursor, "cursor@420".as_bytes());
+            assert_eq!(sequence, 7);
         })
         .await;
     }
@@ -1993,6 +2505,7 @@ mod test_serial_db {
                         id: NATIVE_CREATED_CONTRACT.to_string(),
                         protocol_system: "test".to_string(),
                         protocol_type_name: "pool".to_string(),
+                        additional_protocol_types: Vec::new(),
                         chain: Chain::Ethereum,
                         tokens: vec![
                             Bytes::from_str("0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48").unwrap(),
@@ -2002,7 +2515,7 @@ mod test_serial_db {
                         creation_tx: Default::default(),
                         static_attributes: Default::default(),
                         created_at: Default::default(),
-                        change: Default::default(),
+                        change: ChangeType::Creation,
                     },
                 )]),
                 account_deltas: HashMap::new(),
@@ -2052,11 +2565,12 @@ mod test_serial_db {
                             id: component_id.clone(),
                             protocol_system: "test".to_string(),
                             protocol_type_name: "vm:pool".to_string(),
+                            additional_protocol_types: Vec::new(),
                             chain: Chain::Ethereum,
                             tokens: vec![base_token.clone(), quote_token],
                             contract_addresses: vec![Bytes::from(VM_CONTRACT)],
                             static_attributes: Default::default(),
-                            change: Default::default(),
+                            change: ChangeType::Creation,
                             creation_tx: VM_TX_HASH_0.parse().unwrap(),
                             created_at: Default::default(),
                         },
@@ -2139,6 +2653,7 @@ mod test_serial_db {
                 id: NATIVE_CREATED_CONTRACT.to_string(),
                 protocol_system: "test".to_string(),
                 protocol_type_name: "pool".to_string(),
+                additional_protocol_types: Vec::new(),
                 chain: Chain::Ethereum,
                 tokens: vec![
                     Bytes::from_str("0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48").unwrap(),
@@ -2151,10 +2666,10 @@ mod test_serial_db {
                 .unwrap(),
                 static_attributes: Default::default(),
                 created_at: Default::default(),
-                change: Default::default(),
+                change: ChangeType::Creation,
             }];
 
-            gw.advance(&msg, "cursor@500", false)
+            gw.advance(&msg, "cursor@500", 1, false)
                 .await
                 .expect("upsert should succeed");
 
@@ -2186,7 +2701,7 @@ mod test_serial_db {
             let msg = vm_creation_and_update();
             let exp = vm_account(0);
 
-            gw.advance(&msg, "cursor@500", true)
+            gw.advance(&msg, "cursor@500", 1, true)
                 .await
                 .expect("upsert should succeed");
 
@@ -2354,6 +2869,7 @@ mod test_serial_db {
                         id: "pc_2".to_string(),
                         protocol_system: "native_protocol_system".to_string(),
                         protocol_type_name: "pt_1".to_string(),
+                        additional_protocol_types: Vec::new(),
                         chain: Chain::Ethereum,
                         tokens: vec![
                             Bytes::from_str("0xdac17f958d2ee523a2206206994597c13d831ec7").unwrap(),
@@ -2366,11 +2882,13 @@ mod test_serial_db {
                         created_at: NaiveDateTime::from_timestamp_opt(base_ts + 5000, 0).unwrap(),
                     }),
                 ]),
+                updated_protocol_components: HashMap::new(),
                 deleted_protocol_components: HashMap::from([
                     ("pc_3".to_string(), ProtocolComponent {
                         id: "pc_3".to_string(),
                         protocol_system: "native_protocol_system".to_string(),
                         protocol_type_name: "pt_2".to_string(),
+                        additional_protocol_types: Vec::new(),
                         chain: Chain::Ethereum,
                         tokens: vec![
                             Bytes::from_str("0x6b175474e89094c44da98b954eedeac495271d0f").unwrap(),
@@ -2402,6 +2920,7 @@ mod test_serial_db {
                     ])),
                 ]),
                 component_tvl: HashMap::new(),
+                seq: 6,
                 account_deltas: Default::default(),
             };
 
@@ -2529,6 +3048,7 @@ mod test_serial_db {
                         ]),
                         balance: None,
                         code: None,
+                        proxy_impl: None,
                         change: ChangeType::Update,
                     }),
                     (Bytes::from_str("0x0000000000000000000000000000000000000002").unwrap(), AccountDelta {
@@ -2539,16 +3059,19 @@ mod test_serial_db {
                         ]),
                         balance: None,
                         code: None,
+                        proxy_impl: None,
                         change: ChangeType::Update,
                     }),
                 ]),
                 new_tokens: HashMap::new(),
                 new_protocol_components: HashMap::new(),
+                updated_protocol_components: HashMap::new(),
                 deleted_protocol_components: HashMap::from([
                     ("pc_3".to_string(), ProtocolComponent {
                         id: "pc_3".to_string(),
                         protocol_system: "vm_protocol_system".to_string(),
                         protocol_type_name: "pt_1".to_string(),
+                        additional_protocol_types: Vec::new(),
                         chain: Chain::Ethereum,
                         tokens: vec![
                             Bytes::from_str("0x6b175474e89094c44da98b954eedeac495271d0f").unwrap(),
@@ -2582,6 +3105,7 @@ mod test_serial_db {
                     ])),
                 ]),
                 component_tvl: HashMap::new(),
+                seq: 6,
                 state_deltas: Default::default(),
             };
             assert_eq!(
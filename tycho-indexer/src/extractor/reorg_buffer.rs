@@ -7,7 +7,7 @@ use tycho_core::{
     models::{
         blockchain::{Block, BlockScoped},
         protocol::ComponentBalance,
-        AttrStoreKey, ComponentId, StoreVal,
+        AttrStoreKey, Chain, ComponentId, StoreVal,
     },
     storage::{BlockIdentifier, BlockOrTimestamp, StorageError},
     Bytes,
@@ -47,6 +47,36 @@ impl TryFrom<BlockOrTimestamp> for BlockNumberOrTimestamp {
     }
 }
 
+/// Ethereum blocks are finalized after two epochs of 32 slots.
+const ETHEREUM_FINALITY_DEPTH: u64 = 64;
+
+/// Number of blocks after which a block is treated as final, per chain.
+///
+/// Complements the finality signal of the stream: a block is drained from the reorg buffer once
+/// either the stream reports it as final or it is buried under the chain's depth. Chains without a
+/// depth only rely on the stream's signal. By default, only Ethereum has a depth.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FinalityDepths(HashMap<Chain, u64>);
+
+impl Default for FinalityDepths {
+    fn default() -> Self {
+        Self(HashMap::from([(Chain::Ethereum, ETHEREUM_FINALITY_DEPTH)]))
+    }
+}
+
+impl FinalityDepths {
+    /// Sets the finality depth of `chain`, replacing its default.
+    pub fn with_chain_depth(mut self, chain: Chain, depth: u64) -> Self {
+        self.0.insert(chain, depth);
+        self
+    }
+
+    /// Returns the finality depth of `chain`, if it has one.
+    pub fn get(&self, chain: Chain) -> Option<u64> {
+        self.0.get(&chain).copied()
+    }
+}
+
 /// This buffer temporarily stores blockchain blocks that are not yet finalized. It allows for
 /// efficient handling of chain reorganisations (reorg) without requiring database rollbacks.
 ///
@@ -58,6 +88,7 @@ impl TryFrom<BlockOrTimestamp> for BlockNumberOrTimestamp {
 pub(crate) struct ReorgBuffer<B: BlockScoped> {
     block_messages: VecDeque<B>,
     strict: bool,
+    finality_depth: Option<u64>,
 }
 
 /// The finality status of a block or block-scoped data.
@@ -76,7 +107,13 @@ where
     B: BlockScoped + std::fmt::Debug,
 {
     pub(crate) fn new() -> Self {
-        Self { block_messages: VecDeque::new(), strict: false }
+        Self { block_messages: VecDeque::new(), strict: false, finality_depth: None }
+    }
+
+    /// Treats blocks buried under `depth` newer blocks as final, even if the finalized block
+    /// height passed to [`Self::drain_new_finalized_blocks`] is lower.
+    pub(crate) fn set_finality_depth(&mut self, depth: Option<u64>) {
+        self.finality_depth = depth;
     }
 
     /// Inserts a new block into the buffer. Ensures the new block is the expected next block,
@@ -101,10 +138,21 @@ where
     /// Drains blocks up to the specified finalized block height. The last finalized block is kept
     /// in the buffer. Returns the drained blocks ordered by ascending number or an error if the
     /// specified block is not found.
+    ///
+    /// If the buffer has a finality depth and the block at that depth below the most recent block
+    /// is buffered and higher than `final_block_height`, blocks are drained up to it instead.
     pub fn drain_new_finalized_blocks(
         &mut self,
         final_block_height: u64,
     ) -> Result<Vec<B>, StorageError> {
+        let final_block_height = self
+            .depth_final_block_height()
+            .filter(|&height| {
+                height > final_block_height &&
+                    self.find_index(|b| b.block().number == height)
+                        .is_some()
+            })
+            .unwrap_or(final_block_height);
         let target_index = self.find_index(|b| b.block().number == final_block_height);
         let first = self
             .get_block_range(None, None)?
@@ -125,6 +173,16 @@ where
         }
     }
 
+    fn depth_final_block_height(&self) -> Option<u64> {
+        let depth = self.finality_depth?;
+        let latest = self
+            .block_messages
+            .back()?
+            .block()
+            .number;
+        Some(latest.saturating_sub(depth))
+    }
+
     fn find_index<F: Fn(&B) -> bool>(&self, predicate: F) -> Option<usize> {
         for (index, block_message) in self.block_messages.iter().enumerate() {
             if predicate(block_message) {
@@ -696,6 +754,74 @@ mod test {
         assert!(unknown.is_err());
     }
 
+    #[test]
+    fn test_drain_finalized_blocks_by_chain_depth() {
+        let depths = FinalityDepths::default()
+            .with_chain_depth(Chain::Ethereum, 3)
+            .with_chain_depth(Chain::Arbitrum, 1);
+        let mut ethereum_buffer = ReorgBuffer::new();
+        ethereum_buffer.set_finality_depth(depths.get(Chain::Ethereum));
+        let mut arbitrum_buffer = ReorgBuffer::new();
+        arbitrum_buffer.set_finality_depth(depths.get(Chain::Arbitrum));
+        let block_changes = |number| {
+            BlockChanges::new(
+                "test".to_string(),
+                Chain::Ethereum,
+                testing::block(number),
+                0,
+                false,
+                vec![],
+            )
+        };
+
+        // The stream's finality signal lags behind, it only ever reports the oldest buffered block.
+        let drain_lagging = |buffer: &mut ReorgBuffer<BlockChanges>| {
+            let oldest = buffer.block_messages[0].block.number;
+            buffer
+                .drain_new_finalized_blocks(oldest)
+                .unwrap()
+                .len()
+        };
+
+        let mut ethereum_drained = Vec::new();
+        let mut arbitrum_drained = Vec::new();
+        for number in 1..=5 {
+            ethereum_buffer
+                .insert_block(block_changes(number))
+                .unwrap();
+            arbitrum_buffer
+                .insert_block(block_changes(number))
+                .unwrap();
+            ethereum_drained.push(drain_lagging(&mut ethereum_buffer));
+            arbitrum_drained.push(drain_lagging(&mut arbitrum_buffer));
+        }
+
+        assert_eq!(ethereum_drained, vec![0, 0, 0, 0, 1]);
+        assert_eq!(arbitrum_drained, vec![0, 0, 1, 1, 1]);
+        assert_eq!(ethereum_buffer.block_messages.len(), 4);
+        assert_eq!(arbitrum_buffer.block_messages.len(), 2);
+        assert_eq!(FinalityDepths::default().get(Chain::Ethereum), Some(64));
+        assert_eq!(FinalityDepths::default().get(Chain::Starknet), None);
+    }
+
+    #[test]
+    fn test_drain_finalized_blocks_signal_ahead_of_depth() {
+        let mut reorg_buffer = ReorgBuffer::new();
+        reorg_buffer.set_finality_depth(Some(2));
+        reorg_buffer.strict = true;
+        for version in 1..=3 {
+            reorg_buffer
+                .insert_block(get_block_changes(version))
+                .unwrap();
+        }
+
+        let finalized = reorg_buffer
+            .drain_new_finalized_blocks(3)
+            .unwrap();
+
+        assert_eq!(finalized, vec![get_block_changes(1), get_block_changes(2)]);
+    }
+
     #[test]
     fn test_purge() {
         let mut reorg_buffer = ReorgBuffer::new();
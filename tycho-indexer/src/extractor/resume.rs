@@ -0,0 +1,119 @@
+use tracing::warn;
+
+use tycho_core::{
+    models::ExtractorIdentity,
+    storage::{ExtractionStateGateway, StorageError},
+};
+
+/// Where a substreams stream should start after (re)connecting.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResumeState {
+    /// No usable cursor was persisted, the stream starts from the configured start block.
+    Fresh,
+    /// The stream resumes from the persisted cursor.
+    Resume(String),
+}
+
+impl ResumeState {
+    /// Loads the persisted cursor of the extractor `id`.
+    ///
+    /// Falls back to a fresh start if no state was persisted yet, or if the stored cursor is
+    /// empty or not a printable ASCII string, as substreams cursors always are.
+    ///
+    /// # Errors
+    ///
+    /// Returns any storage error other than `StorageError::NotFound`.
+    pub async fn load<G>(gateway: &G, id: &ExtractorIdentity) -> Result<Self, StorageError>
+    where
+        G: ExtractionStateGateway + Sync + ?Sized,
+    {
+        let state = match gateway
+            .get_state(&id.name, &id.chain)
+            .await
+        {
+            Ok(state) => state,
+            Err(StorageError::NotFound(_, _)) => return Ok(Self::Fresh),
+            Err(e) => return Err(e),
+        };
+        match String::from_utf8(state.cursor) {
+            Ok(cursor) if is_valid_cursor(&cursor) => Ok(Self::Resume(cursor)),
+            _ => {
+                warn!(?id, "Stored cursor is corrupt, starting fresh");
+                Ok(Self::Fresh)
+            }
+        }
+    }
+
+    /// Returns the cursor to pass to the substreams client, `None` for a fresh start.
+    pub fn cursor(&self) -> Option<&str> {
+        match self {
+            Self::Fresh => None,
+            Self::Resume(cursor) => Some(cursor),
+        }
+    }
+}
+
+fn is_valid_cursor(cursor: &str) -> bool {
+    !cursor.is_empty() &&
+        cursor
+            .bytes()
+            .all(|b| b.is_ascii_graphic())
+}
+
+#[cfg(test)]
+mod test {
+    use async_trait::async_trait;
+    use rstest::rstest;
+
+    use tycho_core::{
+        models::{Chain, ExtractionState},
+        Bytes,
+    };
+
+    use super::*;
+
+    struct StoredState(Option<Vec<u8>>);
+
+    #[async_trait]
+    impl ExtractionStateGateway for StoredState {
+        async fn get_state(
+            &self,
+            name: &str,
+            chain: &Chain,
+        ) -> Result<ExtractionState, StorageError> {
+            match &self.0 {
+                Some(cursor) => Ok(ExtractionState::new(
+                    name.to_string(),
+                    *chain,
+                    None,
+                    cursor,
+                    Bytes::zero(32),
+                )),
+                None => {
+                    Err(StorageError::NotFound("ExtractionState".to_string(), name.to_string()))
+                }
+            }
+        }
+
+        async fn save_state(&self, _state: &ExtractionState) -> Result<(), StorageError> {
+            Ok(())
+        }
+    }
+
+    #[rstest]
+    #[case::resume(Some(b"cursor@420".to_vec()), ResumeState::Resume("cursor@420".to_string()))]
+    #[case::not_found(None, ResumeState::Fresh)]
+    #[case::empty(Some(vec![]), ResumeState::Fresh)]
+    #[case::invalid_utf8(Some(vec![0xff, 0xfe]), ResumeState::Fresh)]
+    #[case::control_characters(Some(b"cursor\n\0".to_vec()), ResumeState::Fresh)]
+    #[tokio::test]
+    async fn test_load(#[case] cursor: Option<Vec<u8>>, #[case] expected: ResumeState) {
+        let id = ExtractorIdentity::new(Chain::Ethereum, "ambient");
+
+        let res = ResumeState::load(&StoredState(cursor), &id)
+            .await
+            .unwrap();
+
+        assert_eq!(res, expected);
+    }
+}
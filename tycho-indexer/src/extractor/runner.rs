@@ -1,9 +1,10 @@
-use std::{collections::HashMap, env, path::Path, sync::Arc};
+use std::{collections::HashMap, env, num::NonZeroUsize, path::Path, sync::Arc};
 
 use anyhow::{format_err, Context, Result};
 use async_trait::async_trait;
 use aws_config::meta::region::RegionProviderChain;
 use aws_sdk_s3::Client;
+use futures03::stream::BoxStream;
 use metrics::gauge;
 use prost::Message;
 use serde::Deserialize;
@@ -28,9 +29,13 @@ use tycho_storage::postgres::cache::CachedGateway;
 use crate::{
     extractor::{
         chain_state::ChainState,
+        models::BlockChanges,
+        parallel_decoder::ParallelDecoder,
         post_processors::POST_PROCESSOR_REGISTRY,
+        protobuf_deserialisation::DEFAULT_MAX_COMPONENTS_PER_BLOCK,
         protocol_cache::ProtocolMemoryCache,
         protocol_extractor::{ExtractorPgGateway, ProtocolExtractor},
+        reorg_buffer::FinalityDepths,
         ExtractionError, Extractor, ExtractorMsg,
     },
     pb::sf::substreams::v1::Package,
@@ -105,9 +110,13 @@ impl MessageSender for ExtractorHandle {
 // Define the SubscriptionsMap type alias
 type SubscriptionsMap = HashMap<u64, Sender<ExtractorMsg>>;
 
+/// A substreams response, along with the changes of a new block if they were decoded ahead of
+/// time.
+type DecodedResponse = (BlockResponse, Option<Result<BlockChanges, ExtractionError>>);
+
 pub struct ExtractorRunner {
     extractor: Arc<dyn Extractor>,
-    substreams: SubstreamsStream,
+    substreams: BoxStream<'static, Result<DecodedResponse>>,
     subscriptions: Arc<Mutex<SubscriptionsMap>>,
     next_subscriber_id: u64,
     control_rx: Receiver<ControlMessage>,
@@ -126,7 +135,7 @@ impl ExtractorRunner {
     ) -> Self {
         ExtractorRunner {
             extractor,
-            substreams,
+            substreams: Box::pin(substreams.map(|response| response.map(|r| (r, None)))),
             subscriptions,
             next_subscriber_id: 0,
             control_rx,
@@ -134,6 +143,33 @@ impl ExtractorRunner {
         }
     }
 
+    /// Decodes up to `parallelism` blocks concurrently, ahead of the extractor handling them.
+    pub fn with_parallel_decoding(mut self, parallelism: NonZeroUsize) -> Self {
+        let extractor = self.extractor.clone();
+        let decoder = ParallelDecoder::new(
+            move |response: Result<DecodedResponse>| match response {
+                Ok((BlockResponse::New(data), None)) => {
+                    let changes = extractor.decode_block(&data);
+                    Ok((BlockResponse::New(data), Some(changes)))
+                }
+                other => other,
+            },
+            parallelism,
+            // Leave room for later blocks to be decoded while the oldest one is being handled.
+            parallelism.saturating_mul(NonZeroUsize::new(2).unwrap()),
+        );
+        self.substreams = Box::pin(
+            decoder
+                .run(self.substreams)
+                .map(|decoded| {
+                    decoded
+                        .map_err(anyhow::Error::from)
+                        .and_then(|r| r)
+                }),
+        );
+        self
+    }
+
     pub fn run(mut self) -> JoinHandle<Result<(), ExtractionError>> {
         let runtime = self
             .runtime_handle
@@ -172,7 +208,7 @@ impl ExtractorRunner {
                                 tracing::Span::current().record("otel.status_code", "error");
                                 return Err(ExtractionError::SubstreamsError(format!("{}: stream ended", id)));
                             }
-                            Some(Ok(BlockResponse::New(data))) => {
+                            Some(Ok((BlockResponse::New(data), changes))) => {
                                 let block_number = data.clock.as_ref().map(|v| v.number).unwrap_or(0);
                                 tracing::Span::current().record("block_number", block_number);
                                 gauge!(
@@ -185,7 +221,13 @@ impl ExtractorRunner {
                                 let start_time = std::time::Instant::now();
 
                                 // TODO: change interface to take a reference to avoid this clone
-                                match self.extractor.handle_tick_scoped_data(data.clone()).await {
+                                let res = match changes {
+                                    Some(changes) => {
+                                        self.extractor.handle_decoded_tick(data.clone(), changes).await
+                                    }
+                                    None => self.extractor.handle_tick_scoped_data(data.clone()).await,
+                                };
+                                match res {
                                     Ok(Some(msg)) => {
                                         trace!("Propagating new block data message.");
                                         Self::propagate_msg(&self.subscriptions, msg).await
@@ -207,7 +249,7 @@ impl ExtractorRunner {
                                     "extractor" => id.name.to_string()
                                 ).set(duration.as_millis() as f64);
                             }
-                            Some(Ok(BlockResponse::Undo(undo_signal))) => {
+                            Some(Ok((BlockResponse::Undo(undo_signal), _))) => {
                                 info!(block=?&undo_signal.last_valid_block,  "Revert requested!");
                                 match self.extractor.handle_revert(undo_signal.clone()).await {
                                     Ok(Some(msg)) => {
@@ -314,6 +356,21 @@ pub struct ExtractorConfig {
     pub initialized_accounts_block: i64,
     #[serde(default)]
     pub post_processor: Option<String>,
+    /// Maximum number of new components accepted in a single block.
+    #[serde(default)]
+    pub max_components_per_block: Option<usize>,
+    /// Name of a substreams store module to additionally decode new components from. Store
+    /// deltas are only available when running substreams in development mode.
+    #[serde(default)]
+    pub store_components_module: Option<String>,
+    /// Number of blocks decoded concurrently ahead of being processed, blocks are decoded one at
+    /// a time while processing them if unset.
+    #[serde(default)]
+    pub decode_parallelism: Option<NonZeroUsize>,
+    /// Number of blocks after which a block is treated as final, overriding the chain's default
+    /// from [`FinalityDepths`].
+    #[serde(default)]
+    pub finality_depth: Option<u64>,
 }
 
 impl ExtractorConfig {
@@ -345,6 +402,10 @@ impl ExtractorConfig {
             initialized_accounts,
             initialized_accounts_block,
             post_processor,
+            max_components_per_block: None,
+            store_components_module: None,
+            decode_parallelism: None,
+            finality_depth: None,
         }
     }
 }
@@ -484,20 +545,33 @@ impl ExtractorBuilder {
             })
             .transpose()?;
 
-        self.extractor = Some(Arc::new(
-            ProtocolExtractor::new(
-                gw,
-                &self.config.name,
-                self.config.chain,
-                chain_state,
-                self.config.name.clone(),
-                protocol_cache.clone(),
-                protocol_types,
-                token_pre_processor.clone(),
-                post_processor,
-            )
-            .await?,
-        ));
+        let mut extractor = ProtocolExtractor::new(
+            gw,
+            &self.config.name,
+            self.config.chain,
+            chain_state,
+            self.config.name.clone(),
+            protocol_cache.clone(),
+            protocol_types,
+            token_pre_processor.clone(),
+            post_processor,
+        )
+        .await?
+        .with_max_components_per_block(
+            self.config
+                .max_components_per_block
+                .unwrap_or(DEFAULT_MAX_COMPONENTS_PER_BLOCK),
+        )
+        .with_module_name(&self.config.module_name);
+        let mut finality_depths = FinalityDepths::default();
+        if let Some(depth) = self.config.finality_depth {
+            finality_depths = finality_depths.with_chain_depth(self.config.chain, depth);
+        }
+        extractor = extractor.with_finality_depths(&finality_depths);
+        if let Some(store_module) = &self.config.store_components_module {
+            extractor = extractor.with_store_components_module(store_module);
+        }
+        self.extractor = Some(Arc::new(extractor));
 
         Ok(self)
     }
@@ -539,13 +613,16 @@ impl ExtractorBuilder {
 
         let id = extractor.get_id();
         let (ctrl_tx, ctrl_rx) = mpsc::channel(128);
-        let runner = ExtractorRunner::new(
+        let mut runner = ExtractorRunner::new(
             extractor,
             stream,
             Arc::new(Mutex::new(HashMap::new())),
             ctrl_rx,
             self.runtime_handle,
         );
+        if let Some(parallelism) = self.config.decode_parallelism {
+            runner = runner.with_parallel_decoding(parallelism);
+        }
 
         let handle = runner.run();
         Ok((handle, ExtractorHandle::new(id, ctrl_tx)))
@@ -618,6 +695,10 @@ mod test {
             Arc::new(self.clone())
         }
 
+        fn sequence(&self) -> u64 {
+            0
+        }
+
         fn as_any(&self) -> &dyn std::any::Any {
             self
         }
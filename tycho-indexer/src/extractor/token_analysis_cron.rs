@@ -121,18 +121,18 @@ async fn analyze_batch(
         }
 
         debug!(?t.address, "Analyzing token");
-        let (token_quality, gas, tax) = match analyzer
+        let detection = match analyzer
             .analyze(t.address.clone(), BlockTag::Latest)
             .await
         {
-            Ok(t) => t,
+            Ok(detection) => detection,
             Err(error) => {
                 warn!(?error, "Token quality detection failed");
                 continue;
             }
         };
 
-        match token_quality {
+        match detection.quality {
             TokenQuality::Good => {
                 t.quality = 100;
             }
@@ -141,15 +141,23 @@ async fn analyze_batch(
                 // Remove 1 to the quality for each attempt. If it fails 5 times we won't try again.
                 t.quality -= 1;
             }
+            TokenQuality::NonTransferable { reason } => {
+                debug!(?t.address, ?reason, "Token detected as non-transferable!");
+                t.quality = 0;
+            }
         }
 
         // If it's a fee token, set quality to 50
-        if tax.map_or(false, |tax_value| tax_value > 0) {
+        if detection
+            .tax
+            .map_or(false, |tax_value| tax_value > 0)
+        {
             t.quality = 50;
         }
 
-        t.tax = tax.unwrap_or(0);
-        t.gas = gas
+        t.tax = detection.tax.unwrap_or(0);
+        t.gas = detection
+            .transfer_gas
             .map(|g| vec![Some(g)])
             .unwrap_or_else(Vec::new);
     }
@@ -0,0 +1,249 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use futures03::{Stream, StreamExt};
+use tracing::debug;
+
+use tycho_core::{
+    models::{
+        blockchain::BlockTag,
+        token::{CurrencyToken, TokenOwnerStore},
+        Address, Balance, ChangeType,
+    },
+    traits::TokenPreProcessor,
+};
+
+use crate::extractor::models::BlockChanges;
+
+/// Enriches a stream of blocks with metadata for the tokens their new components reference.
+///
+/// Token addresses are collected from every component in a block, and all addresses that were
+/// not seen in a previous block are fetched in a single `get_tokens` batch. Tokens are only
+/// fetched once for the lifetime of the pipeline, tokens that couldn't be fetched are retried with
+/// the next block referencing them.
+pub struct ComponentTokenPipeline<T> {
+    token_pre_processor: T,
+    seen_tokens: HashSet<Address>,
+}
+
+impl<T> ComponentTokenPipeline<T>
+where
+    T: TokenPreProcessor,
+{
+    pub fn new(token_pre_processor: T) -> Self {
+        Self { token_pre_processor, seen_tokens: HashSet::new() }
+    }
+
+    /// Fetches the tokens referenced for the first time by the components of this block.
+    pub async fn process(&mut self, block: BlockChanges) -> (BlockChanges, Vec<CurrencyToken>) {
+        let mut new_addresses = Vec::new();
+        for address in block
+            .protocol_components()
+            .into_iter()
+            .flat_map(|pc| pc.tokens)
+        {
+            if !self.seen_tokens.contains(&address) && !new_addresses.contains(&address) {
+                new_addresses.push(address);
+            }
+        }
+
+        if new_addresses.is_empty() {
+            return (block, Vec::new());
+        }
+
+        debug!(n_tokens = new_addresses.len(), block = block.block.number, "FetchingNewTokens");
+        let token_finder = TokenOwnerStore::new(token_owners(&block));
+        let tokens = self
+            .token_pre_processor
+            .get_tokens(new_addresses, Arc::new(token_finder), BlockTag::Number(block.block.number))
+            .await;
+        self.seen_tokens
+            .extend(tokens.iter().map(|t| t.address.clone()));
+
+        (block, tokens)
+    }
+
+    /// Consumes a stream of blocks and emits each block together with its newly fetched tokens.
+    pub fn run<S>(mut self, blocks: S) -> impl Stream<Item = (BlockChanges, Vec<CurrencyToken>)>
+    where
+        S: Stream<Item = BlockChanges> + Unpin,
+    {
+        async_stream::stream! {
+            let mut blocks = blocks;
+            while let Some(block) = blocks.next().await {
+                yield self.process(block).await;
+            }
+        }
+    }
+}
+
+/// Uses the first contract of each newly created component as owner of the component's tokens.
+fn token_owners(block: &BlockChanges) -> HashMap<Address, (Address, Balance)> {
+    block
+        .txs_with_update
+        .iter()
+        .flat_map(|tx| {
+            tx.protocol_components
+                .iter()
+                .filter(|(_, component)| component.change == ChangeType::Creation)
+                .filter_map(|(c_id, component)| {
+                    let owner = component.contract_addresses.first()?;
+                    let balances = tx.balance_changes.get(c_id)?;
+                    Some(
+                        balances
+                            .iter()
+                            .map(move |(token, balance)| {
+                                (token.clone(), (owner.clone(), balance.balance.clone()))
+                            }),
+                    )
+                })
+                .flatten()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use futures03::stream;
+    use mockall::{
+        mock,
+        predicate::{always, eq},
+    };
+
+    use tycho_core::{
+        models::{
+            blockchain::{Block, Transaction, TxWithChanges},
+            protocol::ProtocolComponent,
+            Chain,
+        },
+        traits::TokenOwnerFinding,
+        Bytes,
+    };
+
+    use super::*;
+
+    mock! {
+        pub TokenPreProcessor {}
+
+        #[async_trait::async_trait]
+        impl TokenPreProcessor for TokenPreProcessor {
+            async fn get_tokens(
+                &self,
+                addresses: Vec<Bytes>,
+                token_finder: Arc<dyn TokenOwnerFinding>,
+                block: BlockTag,
+            ) -> Vec<CurrencyToken>;
+        }
+    }
+
+    fn block_with_tokens(number: u64, tokens: &[&str]) -> BlockChanges {
+        let component =
+            ProtocolComponent::builder(&format!("pc_{number}"), "ambient", "pool", Chain::Ethereum)
+                .tokens(
+                    tokens
+                        .iter()
+                        .map(|t| Bytes::from(*t))
+                        .collect(),
+                )
+                .change(ChangeType::Creation)
+                .build()
+                .unwrap();
+        BlockChanges::new(
+            "test".to_string(),
+            Chain::Ethereum,
+            Block { number, ..Default::default() },
+            0,
+            false,
+            vec![TxWithChanges::new(
+                HashMap::from([(component.id.clone(), component)]),
+                HashMap::new(),
+                HashMap::new(),
+                HashMap::new(),
+                Transaction::default(),
+            )],
+        )
+    }
+
+    fn token(address: &str) -> CurrencyToken {
+        CurrencyToken::new(&Bytes::from(address), "TKN", 18, 0, &[], Chain::Ethereum, 100)
+    }
+
+    #[tokio::test]
+    async fn test_seen_tokens_are_not_refetched() {
+        let mut preprocessor = MockTokenPreProcessor::new();
+        preprocessor
+            .expect_get_tokens()
+            .with(
+                eq(vec![Bytes::from("0x01"), Bytes::from("0x02")]),
+                always(),
+                eq(BlockTag::Number(1)),
+            )
+            .times(1)
+            .returning(|_, _, _| vec![token("0x01"), token("0x02")]);
+        preprocessor
+            .expect_get_tokens()
+            .with(eq(vec![Bytes::from("0x03")]), always(), eq(BlockTag::Number(2)))
+            .times(1)
+            .returning(|_, _, _| vec![token("0x03")]);
+        let pipeline = ComponentTokenPipeline::new(preprocessor);
+
+        let res: Vec<_> = pipeline
+            .run(stream::iter(vec![
+                block_with_tokens(1, &["0x01", "0x02"]),
+                block_with_tokens(2, &["0x02", "0x03"]),
+                block_with_tokens(3, &["0x01", "0x03"]),
+            ]))
+            .map(|(block, tokens)| {
+                (
+                    block.block.number,
+                    tokens
+                        .into_iter()
+                        .map(|t| t.address)
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect()
+            .await;
+
+        assert_eq!(
+            res,
+            vec![
+                (1, vec![Bytes::from("0x01"), Bytes::from("0x02")]),
+                (2, vec![Bytes::from("0x03")]),
+                (3, vec![]),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_missing_tokens_are_refetched() {
+        let mut preprocessor = MockTokenPreProcessor::new();
+        preprocessor
+            .expect_get_tokens()
+            .with(
+                eq(vec![Bytes::from("0x01"), Bytes::from("0x02")]),
+                always(),
+                eq(BlockTag::Number(1)),
+            )
+            .times(1)
+            .returning(|_, _, _| vec![token("0x01")]);
+        preprocessor
+            .expect_get_tokens()
+            .with(eq(vec![Bytes::from("0x02")]), always(), eq(BlockTag::Number(2)))
+            .times(1)
+            .returning(|_, _, _| vec![token("0x02")]);
+        let mut pipeline = ComponentTokenPipeline::new(preprocessor);
+
+        let (_, first) = pipeline
+            .process(block_with_tokens(1, &["0x01", "0x02"]))
+            .await;
+        let (_, second) = pipeline
+            .process(block_with_tokens(2, &["0x01", "0x02"]))
+            .await;
+
+        assert_eq!(first, vec![token("0x01")]);
+        assert_eq!(second, vec![token("0x02")]);
+    }
+}
@@ -0,0 +1,110 @@
+use std::{collections::HashMap, sync::Arc};
+
+use tycho_core::models::{protocol::ComponentBalance, token::CurrencyToken, Address};
+
+use crate::extractor::{liquidity_filter::PriceFn, u256_num::bytes_to_f64};
+
+/// The value of a component's balances in a reference token.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReferenceTvl {
+    /// Sum of the values of all priced balances, in units of the reference token.
+    pub total: f64,
+    /// Tokens whose balances could not be valued, sorted by address.
+    pub unpriced: Vec<Address>,
+}
+
+/// Expresses component tvl in a single reference token, e.g. USDC or the native token.
+///
+/// The price function returns the price of one whole token, i.e. adjusted for its decimals, in
+/// the reference token. Balances of tokens without a price or with unknown decimals don't count
+/// towards the total and are reported as unpriced instead.
+#[derive(Clone)]
+pub struct TvlConverter {
+    price: Arc<PriceFn>,
+}
+
+impl TvlConverter {
+    pub fn new<F>(price: F) -> Self
+    where
+        F: Fn(&Address) -> Option<f64> + Send + Sync + 'static,
+    {
+        Self { price: Arc::new(price) }
+    }
+
+    /// Converts the raw balances of a component into the reference token.
+    ///
+    /// `tokens` provides the decimals of the balances' tokens.
+    pub fn convert(
+        &self,
+        balances: &HashMap<Address, ComponentBalance>,
+        tokens: &HashMap<Address, CurrencyToken>,
+    ) -> ReferenceTvl {
+        let mut total = 0.0;
+        let mut unpriced = Vec::new();
+        for (address, balance) in balances.iter() {
+            match self.value(address, balance, tokens.get(address)) {
+                Some(value) => total += value,
+                None => unpriced.push(address.clone()),
+            }
+        }
+        unpriced.sort_unstable();
+        ReferenceTvl { total, unpriced }
+    }
+
+    fn value(
+        &self,
+        address: &Address,
+        balance: &ComponentBalance,
+        token: Option<&CurrencyToken>,
+    ) -> Option<f64> {
+        let price = (self.price)(address)?;
+        let amount = bytes_to_f64(&balance.balance)? / 10f64.powi(token?.decimals as i32);
+        Some(amount * price)
+    }
+}
+
+impl std::fmt::Debug for TvlConverter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TvlConverter")
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tycho_core::{models::Chain, Bytes};
+
+    use super::*;
+
+    #[test]
+    fn test_convert() {
+        let usdc = Bytes::from("0x01");
+        let unpriced = Bytes::from("0x02");
+        let priced = usdc.clone();
+        let converter = TvlConverter::new(move |t| (*t == priced).then_some(2.0));
+        let balance = |token: &Bytes, amount: u64| {
+            (
+                token.clone(),
+                ComponentBalance::new(
+                    token.clone(),
+                    Bytes::from(amount),
+                    amount as f64,
+                    Bytes::zero(32),
+                    "pc_1",
+                ),
+            )
+        };
+        let balances = HashMap::from([balance(&usdc, 1_500_000), balance(&unpriced, 10)]);
+        let tokens = HashMap::from([
+            (usdc.clone(), CurrencyToken::new(&usdc, "USDC", 6, 0, &[], Chain::Ethereum, 100)),
+            (
+                unpriced.clone(),
+                CurrencyToken::new(&unpriced, "DUST", 18, 0, &[], Chain::Ethereum, 100),
+            ),
+        ]);
+
+        let res = converter.convert(&balances, &tokens);
+
+        assert_eq!(res, ReferenceTvl { total: 3.0, unpriced: vec![unpriced] });
+    }
+}
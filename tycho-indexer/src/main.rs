@@ -3,6 +3,7 @@ use std::{
     collections::HashMap,
     fs::File,
     io::Read,
+    num::NonZeroU32,
     process,
     str::FromStr,
     sync::{mpsc, Arc},
@@ -20,7 +21,7 @@ use tracing_subscriber::EnvFilter;
 
 use tycho_core::{
     models::{
-        blockchain::{Block, Transaction},
+        blockchain::{Block, Transaction, TransactionType, TxStatus},
         contract::AccountDelta,
         Address, Chain, ExtractionState, ImplementationType,
     },
@@ -29,7 +30,7 @@ use tycho_core::{
     Bytes,
 };
 use tycho_ethereum::{
-    account_extractor::contract::EVMAccountExtractor,
+    account_extractor::contract::EVMAccountExtractor, rate_limiter::RateLimiter,
     token_analyzer::rpc_client::EthereumRpcClient, token_pre_processor::EthereumTokenPreProcessor,
 };
 use tycho_indexer::{
@@ -202,6 +203,9 @@ fn run_indexer(global_args: GlobalArgs, index_args: IndexArgs) -> Result<(), Ext
             let (extraction_tasks, other_tasks) = create_indexing_tasks(
                 &global_args,
                 &index_args.substreams_args.rpc_url,
+                index_args
+                    .substreams_args
+                    .rpc_requests_per_second,
                 &index_args
                     .chains
                     .iter()
@@ -280,6 +284,9 @@ async fn run_spkg(global_args: GlobalArgs, run_args: RunSpkgArgs) -> Result<(),
     let (extraction_tasks, mut other_tasks) = create_indexing_tasks(
         &global_args,
         &run_args.substreams_args.rpc_url,
+        run_args
+            .substreams_args
+            .rpc_requests_per_second,
         &[Chain::from_str(&run_args.chain).unwrap()],
         Utc::now().naive_utc(),
         config,
@@ -319,6 +326,7 @@ async fn run_rpc(global_args: GlobalArgs) -> Result<(), ExtractionError> {
 async fn create_indexing_tasks(
     global_args: &GlobalArgs,
     rpc_url: &str,
+    rpc_requests_per_second: Option<NonZeroU32>,
     chains: &[Chain],
     retention_horizon: NaiveDateTime,
     extractors_config: ExtractorConfigs,
@@ -344,12 +352,15 @@ async fn create_indexing_tasks(
         .set_retention_horizon(retention_horizon)
         .build()
         .await?;
-    let token_processor = EthereumTokenPreProcessor::new_from_url(
+    let mut token_processor = EthereumTokenPreProcessor::new_from_url(
         rpc_url,
         *chains
             .first()
             .expect("No chain provided"), //TODO: handle multichain?
     );
+    if let Some(requests_per_second) = rpc_requests_per_second {
+        token_processor = token_processor.with_rate_limiter(RateLimiter::new(requests_per_second));
+    }
 
     let (tasks, extractor_handles): (Vec<_>, Vec<_>) =
         // TODO: accept substreams configuration from cli.
@@ -449,6 +460,10 @@ async fn initialize_accounts(
         from: Bytes::from([0u8; 20]),
         to: None,
         index: 0,
+        gas_used: None,
+        tx_type: TransactionType::Creation,
+        status: TxStatus::Success,
+        access_list: None,
     };
 
     cached_gw
@@ -92,6 +92,7 @@ pub mod fixtures {
                 attribute_schema: vec![],
                 implementation_type: 0,
             }),
+            additional_protocol_types: vec![],
         }
     }
 
@@ -110,6 +111,7 @@ pub mod fixtures {
                 .lpad(32, 0)
                 .to_vec(),
             ts: base_ts + version * 1000,
+            base_fee_per_gas: None,
         }
     }
 
@@ -125,6 +127,10 @@ pub mod fixtures {
                 .lpad(20, 0)
                 .to_vec(),
             index,
+            gas_used: None,
+            tx_type: None,
+            status: None,
+            access_list: vec![],
         }
     }
 
@@ -156,6 +162,7 @@ pub mod fixtures {
                         .to_vec(),
                     number: 1,
                     ts: 1000,
+                    base_fee_per_gas: None,
                 }),
 
                 changes: vec![
@@ -171,6 +178,10 @@ pub mod fixtures {
                                 .lpad(20, 0)
                                 .to_vec(),
                             index: 2,
+                            gas_used: None,
+                            tx_type: None,
+                            status: None,
+                            access_list: vec![],
                         }),
                         contract_changes: vec![ContractChange {
                             address: Bytes::from(vec![0x61, 0x62, 0x63, 0x64])
@@ -238,6 +249,7 @@ pub mod fixtures {
                                 attribute_schema: vec![],
                                 implementation_type: 0,
                             }),
+                            additional_protocol_types: vec![],
                         }],
                         balance_changes: vec![BalanceChange {
                             token: hex::decode(
@@ -264,6 +276,10 @@ pub mod fixtures {
                                 .lpad(20, 0)
                                 .to_vec(),
                             index: 5,
+                            gas_used: None,
+                            tx_type: None,
+                            status: None,
+                            access_list: vec![],
                         }),
                         contract_changes: vec![ContractChange {
                             address: Bytes::from(vec![0x61, 0x62, 0x63, 0x64])
@@ -339,6 +355,7 @@ pub mod fixtures {
                             attribute_schema: vec![],
                             implementation_type: 0,
                         }),
+                        additional_protocol_types: vec![],
                     }],
                     balance_changes: vec![
                         BalanceChange {
@@ -403,6 +420,7 @@ pub mod fixtures {
                             attribute_schema: vec![],
                             implementation_type: 0,
                         }),
+                        additional_protocol_types: vec![],
                     }],
                     balance_changes: vec![
                         BalanceChange {
@@ -517,6 +535,7 @@ pub mod fixtures {
                             attribute_schema: vec![],
                             implementation_type: 0,
                         }),
+                        additional_protocol_types: vec![],
                     }],
                     balance_changes: vec![],
                 }],
@@ -583,6 +602,7 @@ pub mod fixtures {
                         .to_vec(),
                     number: 1,
                     ts: yesterday_midnight().timestamp() as u64,
+                    base_fee_per_gas: None,
                 }),
                 changes: vec![
                     TransactionEntityChanges {
@@ -591,6 +611,10 @@ pub mod fixtures {
                             from: Bytes::zero(20).to_vec(),
                             to: Bytes::zero(20).to_vec(),
                             index: 10,
+                            gas_used: None,
+                            tx_type: None,
+                            status: None,
+                            access_list: vec![],
                         }),
                         entity_changes: vec![
                             EntityChanges {
@@ -653,6 +677,10 @@ pub mod fixtures {
                                 .lpad(20, 0)
                                 .to_vec(),
                             index: 11,
+                            gas_used: None,
+                            tx_type: None,
+                            status: None,
+                            access_list: vec![],
                         }),
                         entity_changes: vec![EntityChanges {
                             component_id: "State1".to_owned(),
@@ -699,6 +727,7 @@ pub mod fixtures {
                                 attribute_schema: vec![],
                                 implementation_type: 0,
                             }),
+                            additional_protocol_types: vec![],
                         }],
                         balance_changes: vec![BalanceChange {
                             token: Bytes::from_str("0x6B175474E89094C44Da98b954EedeAC495271d0F")
@@ -749,6 +778,7 @@ pub mod fixtures {
                             attribute_schema: vec![],
                             implementation_type: 0,
                         }),
+                        additional_protocol_types: vec![],
                     }],
                     balance_changes: vec![BalanceChange {
                         token: address_from_str(USDC_ADDRESS),
@@ -784,6 +814,7 @@ pub mod fixtures {
                             attribute_schema: vec![],
                             implementation_type: 0,
                         }),
+                        additional_protocol_types: vec![],
                     }],
                     balance_changes: vec![
                         BalanceChange {
@@ -894,6 +925,7 @@ pub mod fixtures {
                                 attribute_schema: vec![],
                                 implementation_type: 0,
                             }),
+                            additional_protocol_types: vec![],
                         }],
                         balance_changes: vec![],
                     },
@@ -964,6 +996,7 @@ pub mod fixtures {
                             attribute_schema: vec![],
                             implementation_type: 0,
                         }),
+                        additional_protocol_types: vec![],
                     }],
                     balance_changes: vec![BalanceChange {
                         token: address_from_str(WETH_ADDRESS),
@@ -1016,6 +1049,7 @@ pub mod fixtures {
                     parent_hash: vec![0x21, 0x22, 0x23, 0x24],
                     number: 1,
                     ts: 1000,
+                    base_fee_per_gas: None,
                 }),
 
                 changes: vec![
@@ -1025,6 +1059,10 @@ pub mod fixtures {
                             from: vec![0x41, 0x42, 0x43, 0x44],
                             to: vec![0x51, 0x52, 0x53, 0x54],
                             index: 2,
+                            gas_used: None,
+                            tx_type: None,
+                            status: None,
+                            access_list: vec![],
                         }),
                         contract_changes: vec![ContractChange {
                             address: vec![0x61, 0x62, 0x63, 0x64],
@@ -1085,6 +1123,7 @@ pub mod fixtures {
                                 attribute_schema: vec![],
                                 implementation_type: 0,
                             }),
+                            additional_protocol_types: vec![],
                         }],
                         balance_changes: vec![BalanceChange {
                             token: hex::decode(
@@ -1109,6 +1148,10 @@ pub mod fixtures {
                             from: vec![0x41, 0x42, 0x43, 0x44],
                             to: vec![0x51, 0x52, 0x53, 0x54],
                             index: 5,
+                            gas_used: None,
+                            tx_type: None,
+                            status: None,
+                            access_list: vec![],
                         }),
                         contract_changes: vec![ContractChange {
                             address: vec![0x61, 0x62, 0x63, 0x64],
@@ -1174,6 +1217,7 @@ pub mod fixtures {
                             attribute_schema: vec![],
                             implementation_type: 0,
                         }),
+                        additional_protocol_types: vec![],
                     }],
                     balance_changes: vec![
                         BalanceChange {
@@ -1239,6 +1283,7 @@ pub mod fixtures {
                             attribute_schema: vec![],
                             implementation_type: 0,
                         }),
+                        additional_protocol_types: vec![],
                     }],
                     balance_changes: vec![
                         BalanceChange {
@@ -1356,6 +1401,7 @@ pub mod fixtures {
                             attribute_schema: vec![],
                             implementation_type: 0,
                         }),
+                        additional_protocol_types: vec![],
                     }],
                     balance_changes: vec![],
                 }],
@@ -1421,6 +1467,7 @@ pub mod fixtures {
                     parent_hash: vec![0x21, 0x22, 0x23, 0x24],
                     number: 1,
                     ts: yesterday_midnight().timestamp() as u64,
+                    base_fee_per_gas: None,
                 }),
                 changes: vec![
                     TransactionChanges {
@@ -1429,6 +1476,10 @@ pub mod fixtures {
                             from: vec![0x0, 0x0, 0x0, 0x0],
                             to: vec![0x0, 0x0, 0x0, 0x0],
                             index: 10,
+                            gas_used: None,
+                            tx_type: None,
+                            status: None,
+                            access_list: vec![],
                         }),
                         contract_changes: vec![],
                         entity_changes: vec![
@@ -1476,6 +1527,10 @@ pub mod fixtures {
                             from: vec![0x41, 0x42, 0x43, 0x44],
                             to: vec![0x51, 0x52, 0x53, 0x54],
                             index: 11,
+                            gas_used: None,
+                            tx_type: None,
+                            status: None,
+                            access_list: vec![],
                         }),
                         contract_changes: vec![],
                         entity_changes: vec![EntityChanges {
@@ -1523,6 +1578,7 @@ pub mod fixtures {
                                 attribute_schema: vec![],
                                 implementation_type: 0,
                             }),
+                            additional_protocol_types: vec![],
                         }],
                         balance_changes: vec![BalanceChange {
                             token: Bytes::from_str("0x6B175474E89094C44Da98b954EedeAC495271d0F")
@@ -1574,6 +1630,7 @@ pub mod fixtures {
                             attribute_schema: vec![],
                             implementation_type: 0,
                         }),
+                        additional_protocol_types: vec![],
                     }],
                     balance_changes: vec![BalanceChange {
                         token: address_from_str(USDC_ADDRESS),
@@ -1610,6 +1667,7 @@ pub mod fixtures {
                             attribute_schema: vec![],
                             implementation_type: 0,
                         }),
+                        additional_protocol_types: vec![],
                     }],
                     balance_changes: vec![
                         BalanceChange {
@@ -1723,6 +1781,7 @@ pub mod fixtures {
                                 attribute_schema: vec![],
                                 implementation_type: 0,
                             }),
+                            additional_protocol_types: vec![],
                         }],
                         balance_changes: vec![],
                     },
@@ -1795,6 +1854,7 @@ pub mod fixtures {
                             attribute_schema: vec![],
                             implementation_type: 0,
                         }),
+                        additional_protocol_types: vec![],
                     }],
                     balance_changes: vec![BalanceChange {
                         token: address_from_str(WETH_ADDRESS),
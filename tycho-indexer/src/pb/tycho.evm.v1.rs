@@ -17,6 +17,9 @@ pub struct Block {
     /// The block timestamp.
     #[prost(uint64, tag = "4")]
     pub ts: u64,
+    /// The EIP-1559 base fee per gas as big-endian bytes, if the chain has one.
+    #[prost(bytes = "vec", optional, tag = "5")]
+    pub base_fee_per_gas: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
 }
 /// A struct describing a transaction.
 #[allow(clippy::derive_partial_eq_without_eq)]
@@ -34,6 +37,29 @@ pub struct Transaction {
     /// The transactions index within the block.
     #[prost(uint64, tag = "4")]
     pub index: u64,
+    /// The gas used by the transaction, if known.
+    #[prost(uint64, optional, tag = "5")]
+    pub gas_used: ::core::option::Option<u64>,
+    /// The type of the transaction. If unspecified, it is inferred from `to`: transactions without
+    /// a receiver are contract creations.
+    #[prost(enumeration = "TransactionType", optional, tag = "6")]
+    pub tx_type: ::core::option::Option<i32>,
+    /// Whether the transaction succeeded. If unspecified, the transaction is assumed to have
+    /// succeeded.
+    #[prost(enumeration = "TransactionStatus", optional, tag = "7")]
+    pub status: ::core::option::Option<i32>,
+    /// The storage slots the transaction declared it accesses (EIP-2930), if known.
+    #[prost(message, repeated, tag = "8")]
+    pub access_list: ::prost::alloc::vec::Vec<AccessListEntry>,
+}
+/// The storage slots of a contract declared in a transaction's access list.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AccessListEntry {
+    #[prost(bytes = "vec", tag = "1")]
+    pub address: ::prost::alloc::vec::Vec<u8>,
+    #[prost(bytes = "vec", repeated, tag = "2")]
+    pub storage_keys: ::prost::alloc::vec::Vec<::prost::alloc::vec::Vec<u8>>,
 }
 /// A custom struct representing an arbitrary attribute of a protocol component.
 #[allow(clippy::derive_partial_eq_without_eq)]
@@ -85,6 +111,9 @@ pub struct ProtocolComponent {
     /// / Represents the functionality of the component.
     #[prost(message, optional, tag = "6")]
     pub protocol_type: ::core::option::Option<ProtocolType>,
+    /// Further protocol types, for components playing multiple roles.
+    #[prost(message, repeated, tag = "8")]
+    pub additional_protocol_types: ::prost::alloc::vec::Vec<ProtocolType>,
 }
 /// A struct for following the changes of Total Value Locked (TVL) of a protocol component.
 /// Note that if the ProtocolComponent contains multiple contracts, the TVL is tracked for the
@@ -184,6 +213,71 @@ pub struct BlockChanges {
     #[prost(message, repeated, tag = "2")]
     pub changes: ::prost::alloc::vec::Vec<TransactionChanges>,
 }
+/// Enum to specify the type of a transaction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum TransactionType {
+    Unspecified = 0,
+    Call = 1,
+    Creation = 2,
+    /// Internal or system transaction, which may have an empty `to` without creating a contract.
+    System = 3,
+}
+impl TransactionType {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            TransactionType::Unspecified => "TRANSACTION_TYPE_UNSPECIFIED",
+            TransactionType::Call => "TRANSACTION_TYPE_CALL",
+            TransactionType::Creation => "TRANSACTION_TYPE_CREATION",
+            TransactionType::System => "TRANSACTION_TYPE_SYSTEM",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "TRANSACTION_TYPE_UNSPECIFIED" => Some(Self::Unspecified),
+            "TRANSACTION_TYPE_CALL" => Some(Self::Call),
+            "TRANSACTION_TYPE_CREATION" => Some(Self::Creation),
+            "TRANSACTION_TYPE_SYSTEM" => Some(Self::System),
+            _ => None,
+        }
+    }
+}
+/// Enum to specify whether a transaction succeeded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum TransactionStatus {
+    Unspecified = 0,
+    Success = 1,
+    /// The transaction failed and its state changes were reverted.
+    Reverted = 2,
+}
+impl TransactionStatus {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            TransactionStatus::Unspecified => "TRANSACTION_STATUS_UNSPECIFIED",
+            TransactionStatus::Success => "TRANSACTION_STATUS_SUCCESS",
+            TransactionStatus::Reverted => "TRANSACTION_STATUS_REVERTED",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "TRANSACTION_STATUS_UNSPECIFIED" => Some(Self::Unspecified),
+            "TRANSACTION_STATUS_SUCCESS" => Some(Self::Success),
+            "TRANSACTION_STATUS_REVERTED" => Some(Self::Reverted),
+            _ => None,
+        }
+    }
+}
 /// Enum to specify the type of a change.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
 #[repr(i32)]
@@ -537,6 +537,7 @@ mod test {
                     id: "component2".to_string(),
                     protocol_system: "vm_swap".to_string(),
                     protocol_type_name: "swap".to_string(),
+                    additional_protocol_types: Vec::new(),
                     chain: Chain::Ethereum,
                     tokens: Vec::new(),
                     contract_addresses: Vec::new(),
@@ -604,6 +605,7 @@ mod test {
                         id: "component3".to_string(),
                         protocol_system: "native_swap".to_string(),
                         protocol_type_name: "swap".to_string(),
+                        additional_protocol_types: Vec::new(),
                         chain: Chain::Ethereum,
                         tokens: Vec::new(),
                         contract_addresses: Vec::new(),
@@ -619,6 +621,7 @@ mod test {
                         id: "component4".to_string(),
                         protocol_system: "native_swap".to_string(),
                         protocol_type_name: "swap".to_string(),
+                        additional_protocol_types: Vec::new(),
                         chain: Chain::Ethereum,
                         tokens: Vec::new(),
                         contract_addresses: Vec::new(),
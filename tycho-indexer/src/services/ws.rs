@@ -433,6 +433,10 @@ mod tests {
             Arc::new(self.clone())
         }
 
+        fn sequence(&self) -> u64 {
+            0
+        }
+
         fn as_any(&self) -> &dyn std::any::Any {
             self
         }
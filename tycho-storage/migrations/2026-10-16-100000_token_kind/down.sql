@@ -0,0 +1,5 @@
+ALTER TABLE token
+    DROP COLUMN IF EXISTS underlying_asset,
+    DROP COLUMN IF EXISTS kind;
+
+DROP TYPE IF EXISTS token_kind;
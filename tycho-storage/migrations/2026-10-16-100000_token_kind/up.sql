@@ -0,0 +1,8 @@
+CREATE TYPE token_kind AS ENUM(
+    'standard',
+    'vault'
+);
+
+ALTER TABLE token
+    ADD COLUMN kind token_kind NOT NULL DEFAULT 'standard',
+    ADD COLUMN underlying_asset bytea NULL;
@@ -0,0 +1,2 @@
+ALTER TABLE "transaction"
+    ADD COLUMN gas_used int8 NULL;
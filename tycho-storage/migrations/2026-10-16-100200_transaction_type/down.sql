@@ -0,0 +1,4 @@
+ALTER TABLE "transaction"
+    DROP COLUMN IF EXISTS tx_type;
+
+DROP TYPE IF EXISTS transaction_type;
@@ -0,0 +1,8 @@
+CREATE TYPE transaction_type AS ENUM(
+    'call',
+    'creation',
+    'system'
+);
+
+ALTER TABLE "transaction"
+    ADD COLUMN tx_type transaction_type NOT NULL DEFAULT 'call';
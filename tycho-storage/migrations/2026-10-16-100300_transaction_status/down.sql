@@ -0,0 +1,4 @@
+ALTER TABLE "transaction"
+    DROP COLUMN IF EXISTS status;
+
+DROP TYPE IF EXISTS tx_status;
@@ -0,0 +1,7 @@
+CREATE TYPE tx_status AS ENUM(
+    'success',
+    'reverted'
+);
+
+ALTER TABLE "transaction"
+    ADD COLUMN status tx_status NOT NULL DEFAULT 'success';
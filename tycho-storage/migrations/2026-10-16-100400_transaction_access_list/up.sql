@@ -0,0 +1,2 @@
+ALTER TABLE "transaction"
+    ADD COLUMN access_list jsonb NULL;
@@ -0,0 +1,2 @@
+ALTER TABLE token
+    ADD COLUMN name varchar(255) NULL;
@@ -0,0 +1,2 @@
+ALTER TABLE token
+    ADD COLUMN rebasing bool NOT NULL DEFAULT false;
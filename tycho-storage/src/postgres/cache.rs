@@ -1132,6 +1132,7 @@ mod test_serial_db {
                 id: protocol_component_id.clone(),
                 protocol_system: "ambient".to_string(),
                 protocol_type_name: "ambient_pool".to_string(),
+                additional_protocol_types: Vec::new(),
                 chain: Default::default(),
                 tokens: vec![usdc_address.clone()],
                 contract_addresses: vec![],
@@ -1404,6 +1405,10 @@ mod test_serial_db {
                 from: Bytes::from("0x4648451b5F87FF8F0F7D622bD40574bb97E25980"),
                 to: Some(Bytes::from("0x6B175474E89094C44Da98b954EedeAC495271d0F")),
                 index: 1,
+                gas_used: None,
+                tx_type: models::blockchain::TransactionType::Call,
+                status: models::blockchain::TxStatus::Success,
+                access_list: None,
             },
             _ => panic!("Block version not found"),
         }
@@ -131,6 +131,20 @@ impl PostgresGateway {
                     from: new.from.clone(),
                     to: new.to.clone().unwrap_or_default(),
                     index: new.index as i64,
+                    gas_used: new.gas_used.map(|gas| gas as i64),
+                    tx_type: new.tx_type.into(),
+                    status: new.status.into(),
+                    access_list: new
+                        .access_list
+                        .as_ref()
+                        .map(|list| {
+                            serde_json::to_value(list).map_err(|_| {
+                                StorageError::Unexpected(
+                                    "Failed to encode access list.".to_string(),
+                                )
+                            })
+                        })
+                        .transpose()?,
                 })
             })
             .collect::<Result<Vec<orm::NewTransaction>, StorageError>>()?;
@@ -165,12 +179,25 @@ impl PostgresGateway {
             .first::<(orm::Transaction, BlockHash)>(conn)
             .await
             .map(|(mut orm_tx, block_hash)| {
+                let access_list = orm_tx
+                    .access_list
+                    .take()
+                    .map(serde_json::from_value)
+                    .transpose()
+                    .map_err(|_| {
+                        StorageError::DecodeError("Failed to decode access list.".to_string())
+                    })?;
                 Ok(Transaction {
                     hash: std::mem::take(&mut orm_tx.hash),
                     block_hash,
                     from: std::mem::take(&mut orm_tx.from),
-                    to: Some(std::mem::take(&mut orm_tx.to)),
+                    // Transactions without a receiver are stored with an empty one.
+                    to: Some(std::mem::take(&mut orm_tx.to)).filter(|to| !to.is_empty()),
                     index: orm_tx.index as u64,
+                    gas_used: orm_tx.gas_used.map(|gas| gas as u64),
+                    tx_type: orm_tx.tx_type.into(),
+                    status: orm_tx.status.into(),
+                    access_list,
                 })
             })
             .map_err(|err| {
@@ -392,6 +419,10 @@ mod test {
             from: Bytes::from("0x4648451b5f87ff8f0f7d622bd40574bb97e25980"),
             to: Some(Bytes::from("0x6b175474e89094c44da98b954eedeac495271d0f")),
             index: 1,
+            gas_used: None,
+            tx_type: TransactionType::Call,
+            status: TxStatus::Success,
+            access_list: None,
         }
     }
 
@@ -419,6 +450,10 @@ mod test {
             transaction("0xbadbabe000000000000000000000000000000000000000000000000000000000");
         tx.block_hash =
             Bytes::from("0xb495a1d7e6663152ae92708da4843337b958146015a2802f4193a410044698c9");
+        tx.gas_used = Some(21_000);
+        tx.to = None;
+        tx.tx_type = TransactionType::System;
+        tx.status = TxStatus::Reverted;
 
         gw.upsert_tx(&[tx.clone()], &mut conn)
             .await
@@ -431,6 +466,31 @@ mod test {
         assert_eq!(tx, retrieved_tx);
     }
 
+    #[tokio::test]
+    async fn test_add_tx_with_access_list() {
+        let mut conn = setup_db().await;
+        setup_data(&mut conn).await;
+        let gw = EVMGateway::from_connection(&mut conn).await;
+        let tx = transaction("0xbadbabe000000000000000000000000000000000000000000000000000000000")
+            .with_access_list(Some(vec![
+                (
+                    Bytes::from("0x6b175474e89094c44da98b954eedeac495271d0f"),
+                    vec![Bytes::from(1u64).lpad(32, 0), Bytes::from(2u64).lpad(32, 0)],
+                ),
+                (Bytes::from("0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2"), vec![]),
+            ]));
+
+        gw.upsert_tx(&[tx.clone()], &mut conn)
+            .await
+            .unwrap();
+        let retrieved_tx = gw
+            .get_tx(&tx.hash.clone(), &mut conn)
+            .await
+            .unwrap();
+
+        assert_eq!(retrieved_tx, tx);
+    }
+
     #[tokio::test]
     async fn test_upsert_tx() {
         let mut conn = setup_db().await;
@@ -444,6 +504,10 @@ mod test {
             from: Bytes::from("0x4648451b5F87FF8F0F7D622bD40574bb97E25980"),
             to: Some(Bytes::from("0x6B175474E89094C44Da98b954EedeAC495271d0F")),
             index: 1,
+            gas_used: None,
+            tx_type: TransactionType::Call,
+            status: TxStatus::Success,
+            access_list: None,
         };
 
         gw.upsert_tx(&[tx.clone()], &mut conn)
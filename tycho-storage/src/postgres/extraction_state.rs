@@ -13,14 +13,15 @@ impl PostgresGateway {
         let block_chain_id = self.get_chain_id(chain);
 
         match orm::ExtractionState::by_name(name, block_chain_id, conn).await {
-            Ok(Some((orm_state, block_hash))) => {
+            Ok(Some((orm_state, block_hash, block_number))) => {
                 let state = ExtractionState::new(
                     orm_state.name,
                     *chain,
                     orm_state.attributes,
                     &orm_state.cursor.unwrap_or_default(),
                     block_hash,
-                );
+                )
+                .with_block_number(block_number as u64);
                 Ok(state)
             }
             Ok(None) => Err(StorageError::NotFound("ExtractionState".to_owned(), name.to_owned())),
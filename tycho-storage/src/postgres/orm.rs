@@ -96,21 +96,22 @@ impl ExtractionState {
     /// - `conn`: A mutable reference to an asynchronous PostgreSQL connection.
     ///
     /// # Returns
-    /// - `Ok(Some(ExtractionState))` if a matching `ExtractionState` is found.
+    /// - `Ok(Some((ExtractionState, block_hash, block_number)))` if a matching `ExtractionState` is
+    ///   found.
     /// - `Ok(None)` if no matching entry is found in the database.
     /// - `Err(DieselError)` if a Diesel error occurs during the query.
     pub async fn by_name(
         extractor: &str,
         chain_id: i64,
         conn: &mut AsyncPgConnection,
-    ) -> QueryResult<Option<(ExtractionState, Bytes)>> {
+    ) -> QueryResult<Option<(ExtractionState, Bytes, i64)>> {
         extraction_state::table
             .inner_join(chain::table)
             .inner_join(block::table)
             .filter(extraction_state::name.eq(extractor))
             .filter(chain::id.eq(chain_id))
-            .select((ExtractionState::as_select(), block::hash))
-            .first::<(ExtractionState, Bytes)>(conn)
+            .select((ExtractionState::as_select(), block::hash, block::number))
+            .first::<(ExtractionState, Bytes, i64)>(conn)
             .await
             .optional()
     }
@@ -226,6 +227,10 @@ pub struct Transaction {
     pub index: i64,
     pub inserted_ts: NaiveDateTime,
     pub modified_ts: NaiveDateTime,
+    pub gas_used: Option<i64>,
+    pub tx_type: TransactionType,
+    pub status: TxStatus,
+    pub access_list: Option<serde_json::Value>,
 }
 
 impl Transaction {
@@ -276,6 +281,10 @@ pub struct NewTransaction {
     pub from: Address,
     pub to: Address,
     pub index: i64,
+    pub gas_used: Option<i64>,
+    pub tx_type: TransactionType,
+    pub status: TxStatus,
+    pub access_list: Option<serde_json::Value>,
 }
 
 #[derive(Identifiable, Queryable, Selectable)]
@@ -331,6 +340,84 @@ impl From<models::ImplementationType> for ImplementationType {
     }
 }
 
+#[derive(Debug, DbEnum, Clone, Copy, PartialEq)]
+#[ExistingTypePath = "crate::postgres::schema::sql_types::TokenKind"]
+pub enum TokenKind {
+    Standard,
+    Vault,
+}
+
+impl From<models::token::TokenKind> for TokenKind {
+    fn from(value: models::token::TokenKind) -> Self {
+        match value {
+            models::token::TokenKind::Standard => Self::Standard,
+            models::token::TokenKind::Vault => Self::Vault,
+        }
+    }
+}
+
+impl From<TokenKind> for models::token::TokenKind {
+    fn from(value: TokenKind) -> Self {
+        match value {
+            TokenKind::Standard => Self::Standard,
+            TokenKind::Vault => Self::Vault,
+        }
+    }
+}
+
+#[derive(Debug, DbEnum, Clone, Copy, PartialEq)]
+#[ExistingTypePath = "crate::postgres::schema::sql_types::TransactionType"]
+pub enum TransactionType {
+    Call,
+    Creation,
+    System,
+}
+
+impl From<models::blockchain::TransactionType> for TransactionType {
+    fn from(value: models::blockchain::TransactionType) -> Self {
+        match value {
+            models::blockchain::TransactionType::Call => Self::Call,
+            models::blockchain::TransactionType::Creation => Self::Creation,
+            models::blockchain::TransactionType::System => Self::System,
+        }
+    }
+}
+
+impl From<TransactionType> for models::blockchain::TransactionType {
+    fn from(value: TransactionType) -> Self {
+        match value {
+            TransactionType::Call => Self::Call,
+            TransactionType::Creation => Self::Creation,
+            TransactionType::System => Self::System,
+        }
+    }
+}
+
+#[derive(Debug, DbEnum, Clone, Copy, PartialEq)]
+#[ExistingTypePath = "crate::postgres::schema::sql_types::TxStatus"]
+pub enum TxStatus {
+    Success,
+    Reverted,
+}
+
+impl From<models::blockchain::TxStatus> for TxStatus {
+    fn from(value: models::blockchain::TxStatus) -> Self {
+        match value {
+            models::blockchain::TxStatus::Success => Self::Success,
+            models::blockchain::TxStatus::Reverted => Self::Reverted,
+        }
+    }
+}
+
+impl From<TxStatus> for models::blockchain::TxStatus {
+    fn from(value: TxStatus) -> Self {
+        match value {
+            TxStatus::Success => Self::Success,
+            TxStatus::Reverted => Self::Reverted,
+        }
+    }
+}
+
 #[derive(Identifiable, Queryable, Selectable)]
 #[diesel(table_name = protocol_type)]
 #[diesel(check_for_backend(diesel::pg::Pg))]
@@ -1231,6 +1318,10 @@ pub struct Token {
     pub inserted_ts: NaiveDateTime,
     pub modified_ts: NaiveDateTime,
     pub quality: i32,
+    pub kind: TokenKind,
+    pub underlying_asset: Option<Address>,
+    pub name: Option<String>,
+    pub rebasing: bool,
 }
 
 #[derive(AsChangeset, Insertable, Debug)]
@@ -1243,6 +1334,10 @@ pub struct NewToken {
     pub tax: i64,
     pub gas: Vec<Option<i64>>,
     pub quality: i32,
+    pub kind: TokenKind,
+    pub underlying_asset: Option<Address>,
+    pub name: Option<String>,
+    pub rebasing: bool,
 }
 
 impl NewToken {
@@ -1258,6 +1353,10 @@ impl NewToken {
                 .map(|g| g.map(|u| u as i64))
                 .collect(),
             quality: token.quality as i32,
+            kind: token.kind.into(),
+            underlying_asset: token.underlying_asset.clone(),
+            name: Some(token.name.clone()),
+            rebasing: token.rebasing,
         }
     }
 }
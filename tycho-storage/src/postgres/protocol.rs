@@ -413,6 +413,15 @@ impl PostgresGateway {
             account::dsl::*, protocol_component::dsl::*, protocol_component_holds_contract::dsl::*,
             protocol_component_holds_token::dsl::*, token::dsl::*,
         };
+        if let Some(pc) = new
+            .iter()
+            .find(|pc| pc.change == ChangeType::Unspecified)
+        {
+            return Err(StorageError::Unexpected(format!(
+                "Unspecified change type for protocol component {}",
+                pc.id
+            )));
+        }
         let mut values: Vec<orm::NewProtocolComponent> = Vec::with_capacity(new.len());
         let tx_hashes: Vec<TxHash> = new
             .iter()
@@ -961,7 +970,7 @@ impl PostgresGateway {
                     .iter()
                     .map(|u| u.map(|g| g as u64))
                     .collect();
-                models::token::CurrencyToken::new(
+                let currency_token = models::token::CurrencyToken::new(
                     &address_,
                     orm_token.symbol.as_str(),
                     orm_token.decimals as u32,
@@ -969,7 +978,17 @@ impl PostgresGateway {
                     gas_usage.as_slice(),
                     chain,
                     orm_token.quality as u32,
-                )
+                );
+                models::token::CurrencyToken {
+                    // Tokens stored before names were persisted fall back to their symbol.
+                    name: orm_token
+                        .name
+                        .unwrap_or_else(|| currency_token.symbol.clone()),
+                    kind: orm_token.kind.into(),
+                    underlying_asset: orm_token.underlying_asset,
+                    rebasing: orm_token.rebasing,
+                    ..currency_token
+                }
             })
             .collect();
 
@@ -1095,6 +1114,10 @@ impl PostgresGateway {
                         tax.eq(t.tax as i64),
                         quality.eq(t.quality as i32),
                         gas.eq(gas_val),
+                        kind.eq(orm::TokenKind::from(t.kind)),
+                        underlying_asset.eq(&t.underlying_asset),
+                        name.eq(&t.name),
+                        rebasing.eq(t.rebasing),
                     ))
                     .filter(id.eq(db_id))
                     .execute(conn)
@@ -2955,6 +2978,7 @@ mod test {
 
         let inserted_token = db_fixtures::get_token_by_symbol(&mut conn, usdt_symbol.clone()).await;
         assert_eq!(inserted_token.symbol, usdt_symbol);
+        assert_eq!(inserted_token.name, Some(usdt_symbol.clone()));
         assert_eq!(inserted_token.decimals, 6);
         let inserted_account = &orm::Account::by_address(
             &Bytes::from_str(USDT.trim_start_matches("0x")).expect("address ok"),
@@ -2991,6 +3015,10 @@ mod test {
             .entity
             .remove(0);
         prev.gas = vec![Some(20000)];
+        prev.name = "Dai Stablecoin".to_string();
+        prev.rebasing = true;
+        prev.kind = models::token::TokenKind::Vault;
+        prev.underlying_asset = Some(Bytes::from(WETH));
 
         gw.update_tokens(&[prev.clone()], &mut conn)
             .await
@@ -3189,6 +3217,28 @@ mod test {
         assert!(contract.is_ok())
     }
 
+    #[tokio::test]
+    async fn test_add_protocol_components_rejects_unspecified_change() {
+        let mut conn = setup_db().await;
+        setup_data(&mut conn).await;
+        let gw = EVMGateway::from_connection(&mut conn).await;
+        let component = models::protocol::ProtocolComponent {
+            change: ChangeType::Unspecified,
+            ..create_test_protocol_component("state1")
+        };
+
+        let res = gw
+            .add_protocol_components(&[component], &mut conn)
+            .await;
+
+        assert_eq!(
+            res,
+            Err(StorageError::Unexpected(
+                "Unspecified change type for protocol component state1".to_string()
+            ))
+        );
+    }
+
     fn create_test_protocol_component(id: &str) -> models::protocol::ProtocolComponent {
         models::protocol::ProtocolComponent::new(
             id,
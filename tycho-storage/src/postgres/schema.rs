@@ -8,6 +8,18 @@ pub mod sql_types {
     #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
     #[diesel(postgres_type(name = "implementation_type"))]
     pub struct ImplementationType;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "token_kind"))]
+    pub struct TokenKind;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "transaction_type"))]
+    pub struct TransactionType;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "tx_status"))]
+    pub struct TxStatus;
 }
 
 diesel::table! {
@@ -253,6 +265,9 @@ diesel::table! {
 }
 
 diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::TokenKind;
+
     token (id) {
         id -> Int8,
         account_id -> Int8,
@@ -264,6 +279,11 @@ diesel::table! {
         inserted_ts -> Timestamptz,
         modified_ts -> Timestamptz,
         quality -> Int4,
+        kind -> TokenKind,
+        underlying_asset -> Nullable<Bytea>,
+        #[max_length = 255]
+        name -> Nullable<Varchar>,
+        rebasing -> Bool,
     }
 }
 
@@ -278,6 +298,10 @@ diesel::table! {
 }
 
 diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::TransactionType;
+    use super::sql_types::TxStatus;
+
     transaction (id) {
         id -> Int8,
         hash -> Bytea,
@@ -287,6 +311,10 @@ diesel::table! {
         block_id -> Int8,
         inserted_ts -> Timestamptz,
         modified_ts -> Timestamptz,
+        gas_used -> Nullable<Int8>,
+        tx_type -> TransactionType,
+        status -> TxStatus,
+        access_list -> Nullable<Jsonb>,
     }
 }
 